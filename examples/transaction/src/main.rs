@@ -1,10 +1,10 @@
 extern crate braintree;
 extern crate clap;
 
-use braintree::{Braintree, CreditCard, Environment};
+use braintree::{Braintree, test_values};
 use braintree::transaction;
 
-fn print_transaction(transaction: transaction::Transaction, merchant_id: String) {
+fn print_transaction(transaction: transaction::Transaction, merchant_id: &str) {
     println!("        ID: {}", transaction.id);
     println!("      Type: {:?}", transaction.typ);
     println!("    Amount: {}", transaction.amount);
@@ -14,13 +14,7 @@ fn print_transaction(transaction: transaction::Transaction, merchant_id: String)
 }
 
 fn main() {
-    let merchant_id = std::env::var("MERCHANT_ID").expect("environment variable MERCHANT_ID is not defined");
-    let bt = Braintree::new(
-        Environment::Sandbox,
-        merchant_id.clone(),
-        std::env::var("PUBLIC_KEY").expect("environment variable PUBLIC_KEY is not defined"),
-        std::env::var("PRIVATE_KEY").expect("environment variable PRIVATE_KEY is not defined"),
-    );
+    let bt = Braintree::from_env().expect("failed to load Braintree settings from the environment");
 
     let app_m = clap::App::new("Braintree Example")
         .subcommand(
@@ -47,14 +41,13 @@ fn main() {
 
     match app_m.subcommand() {
         ("create", Some(sub_m)) => {
-            let amount = String::from(sub_m.value_of("amount").unwrap());
+            let amount = match sub_m.value_of("amount").unwrap().parse() {
+                Ok(amount) => amount,
+                Err(err) => return println!("\nError: {}\n", err),
+            };
             let result = bt.transaction().create(transaction::Request{
                 amount: amount,
-                credit_card: Some(CreditCard{
-                    number: Some(String::from("4111111111111111")),
-                    expiration_date: Some(String::from("10/20")),
-                    ..Default::default()
-                }),
+                credit_card: Some(test_values::card(test_values::card_numbers::VISA)),
                 options: Some(braintree::transaction::Options{
                     submit_for_settlement: Some(true),
                     ..Default::default()
@@ -65,7 +58,7 @@ fn main() {
             match result {
                 Ok(transaction) => {
                     println!("\n  Successfully created a transaction! Details to follow:\n");
-                    print_transaction(transaction, merchant_id);
+                    print_transaction(transaction, bt.merchant_id().unwrap_or_default());
                     println!("");
                 },
                 Err(err) => println!("\nError: {}\n", err),
@@ -79,7 +72,7 @@ fn main() {
             match result {
                 Ok(transaction) => {
                     println!("\n  Found a transaction! Details to follow:\n");
-                    print_transaction(transaction, merchant_id);
+                    print_transaction(transaction, bt.merchant_id().unwrap_or_default());
                     println!("");
                 },
                 Err(err) => println!("\nError: {}\n", err),
@@ -103,7 +96,7 @@ fn main() {
             match result {
                 Ok(transaction) => {
                     println!("\nSuccessfully refunded {}, details to follow:\n", transaction.id);
-                    print_transaction(transaction, merchant_id);
+                    print_transaction(transaction, bt.merchant_id().unwrap_or_default());
                     println!("");
                 },
                 Err(err) => println!("\nError: {}\n", err),
@@ -1,7 +1,6 @@
-use std::fmt::Write;
-use xml;
 
-use crate::ToXml;
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::{ToXml, Writer};
 #[derive(Debug, Default)]
 pub struct Address {
     pub company: Option<String>,
@@ -19,42 +18,37 @@ pub struct Address {
 }
 
 impl ToXml for Address {
-    fn to_xml(&self, name: Option<&str>) -> String {
-        let name = xml::escape(&name.unwrap_or("address"));
-        let mut s = String::new();
-        write!(s, "<{}>", name).unwrap();
-        write_xml!(s, "company", self.company);
-        write_xml!(s, "country-code-alpha2", self.country_code_alpha2);
-        write_xml!(s, "country-code-alpha3", self.country_code_alpha3);
-        write_xml!(s, "country-code-numeric", self.country_code_numeric);
-        write_xml!(s, "country-name", self.country_name);
-        write_xml!(s, "extended-address", self.extended_address);
-        write_xml!(s, "first-name", self.first_name);
-        write_xml!(s, "last-name", self.last_name);
-        write_xml!(s, "locality", self.locality);
-        write_xml!(s, "postal-code", self.postal_code);
-        write_xml!(s, "region", self.region);
-        write_xml!(s, "street-address", self.street_address);
-        write!(s, "</{}>", name).unwrap();
-        s
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("company", &self.company);
+        w.field("country-code-alpha2", &self.country_code_alpha2);
+        w.field("country-code-alpha3", &self.country_code_alpha3);
+        w.field("country-code-numeric", &self.country_code_numeric);
+        w.field("country-name", &self.country_name);
+        w.field("extended-address", &self.extended_address);
+        w.field("first-name", &self.first_name);
+        w.field("last-name", &self.last_name);
+        w.field("locality", &self.locality);
+        w.field("postal-code", &self.postal_code);
+        w.field("region", &self.region);
+        w.field("street-address", &self.street_address);
     }
 }
 
-impl From<&elementtree::Element> for Address {
-    fn from(root: &elementtree::Element) -> Address {
-        Address{
-            company: Some(String::from(root.find("company").unwrap().text())),
-            country_code_alpha2 : Some(String::from(root.find("country-code-alpha2").unwrap().text())),
-            country_code_alpha3 : Some(String::from(root.find("country-code-alpha3").unwrap().text())),
-            country_code_numeric: Some(String::from(root.find("country-code-numeric").unwrap().text())),
-            country_name: Some(String::from(root.find("country-name").unwrap().text())),
-            extended_address: Some(String::from(root.find("extended-address").unwrap().text())),
-            first_name: Some(String::from(root.find("first-name").unwrap().text())),
-            last_name: Some(String::from(root.find("last-name").unwrap().text())),
-            locality: Some(String::from(root.find("locality").unwrap().text())),
-            postal_code: Some(String::from(root.find("postal-code").unwrap().text())),
-            region: Some(String::from(root.find("region").unwrap().text())),
-            street_address: Some(String::from(root.find("street-address").unwrap().text())),
-        }
+impl FromXml for Address {
+    fn from_xml(node: &Node) -> Result<Address, DecodeError> {
+        Ok(Address{
+            company: node.child_opt("company")?,
+            country_code_alpha2 : node.child_opt("country-code-alpha2")?,
+            country_code_alpha3 : node.child_opt("country-code-alpha3")?,
+            country_code_numeric: node.child_opt("country-code-numeric")?,
+            country_name: node.child_opt("country-name")?,
+            extended_address: node.child_opt("extended-address")?,
+            first_name: node.child_opt("first-name")?,
+            last_name: node.child_opt("last-name")?,
+            locality: node.child_opt("locality")?,
+            postal_code: node.child_opt("postal-code")?,
+            region: node.child_opt("region")?,
+            street_address: node.child_opt("street-address")?,
+        })
     }
 }
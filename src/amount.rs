@@ -0,0 +1,279 @@
+use std;
+use std::error::Error as StdError;
+use std::str::FromStr;
+
+/// An exact, non-negative amount of money, held as an integer number of the
+/// currency's minor units (e.g. cents).
+///
+/// Amounts are usually parsed from a decimal string. Parsing without a
+/// currency assumes two decimal places, which is correct for most
+/// currencies:
+///
+/// ```rust
+/// # use braintree::Amount;
+/// let amount: Amount = "10".parse().unwrap();
+/// assert_eq!(amount.to_string(), "10.00");
+/// assert!("-10.00".parse::<Amount>().is_err());
+/// assert!("10.001".parse::<Amount>().is_err());
+/// ```
+///
+/// Amounts compare by value, whatever their number of decimal places, so
+/// `"10.00"` USD equals `"10.000"` KWD. They carry no currency of their
+/// own; don't compare amounts in different currencies.
+///
+/// For currencies with a different number of decimal places, use
+/// `parse_for_currency()` instead:
+///
+/// ```rust
+/// # use braintree::Amount;
+/// let amount = Amount::parse_for_currency("1000", "JPY").unwrap();
+/// assert_eq!(amount.to_string(), "1000");
+/// assert!(Amount::parse_for_currency("1000.50", "JPY").is_err());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Amount {
+    minor_units: u64,
+    exponent: u32,
+}
+
+impl Amount {
+    /// Creates an amount from a number of minor units, e.g. `1050` cents
+    /// for `"10.50"` USD.
+    pub fn from_minor_units(minor_units: u64, currency_iso_code: &str) -> Amount {
+        Amount{minor_units, exponent: minor_unit_exponent(currency_iso_code)}
+    }
+
+    /// Parses a decimal amount, checking its precision against the number of
+    /// decimal places used by the given ISO 4217 currency.
+    pub fn parse_for_currency(s: &str, currency_iso_code: &str) -> Result<Amount, ParseAmountError> {
+        parse(s, minor_unit_exponent(currency_iso_code))
+    }
+
+    /// The amount as a number of minor units.
+    pub fn minor_units(&self) -> u64 {
+        self.minor_units
+    }
+
+    /// The number of decimal places in the amount's currency.
+    pub fn exponent(&self) -> u32 {
+        self.exponent
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor_units == 0
+    }
+}
+
+impl Amount {
+    /// The amount in units of `10^-exponent`, which must be at least the
+    /// amount's own exponent. Exponents never exceed `MAX_EXPONENT`, as
+    /// `parse()` refuses larger ones, so this can't overflow.
+    fn scaled(&self, exponent: u32) -> u128 {
+        u128::from(self.minor_units) * 10u128.pow(exponent - self.exponent)
+    }
+}
+
+impl PartialEq for Amount {
+    fn eq(&self, other: &Amount) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Amount {}
+
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Amount) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Amount {
+    fn cmp(&self, other: &Amount) -> std::cmp::Ordering {
+        let exponent = self.exponent.max(other.exponent);
+        self.scaled(exponent).cmp(&other.scaled(exponent))
+    }
+}
+
+/// Hashes the amount without trailing zeros, so that equal amounts hash
+/// alike.
+impl std::hash::Hash for Amount {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        let (mut minor_units, mut exponent) = (self.minor_units, self.exponent);
+        while exponent > 0 && minor_units % 10 == 0 {
+            minor_units /= 10;
+            exponent -= 1;
+        }
+        minor_units.hash(state);
+        exponent.hash(state);
+    }
+}
+
+impl Default for Amount {
+    fn default() -> Amount {
+        Amount{minor_units: 0, exponent: DEFAULT_EXPONENT}
+    }
+}
+
+impl FromStr for Amount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Amount, ParseAmountError> {
+        parse(s, DEFAULT_EXPONENT)
+    }
+}
+
+/// Formats the amount the way Braintree expects it, e.g. `"10.00"`.
+impl std::fmt::Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.exponent == 0 {
+            return write!(f, "{}", self.minor_units);
+        }
+        let scale = 10u64.pow(self.exponent);
+        write!(f, "{}.{:0width$}", self.minor_units / scale, self.minor_units % scale, width = self.exponent as usize)
+    }
+}
+
+/// The reason a string could not be parsed as an `Amount`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseAmountError {
+    /// The string was not a plain decimal number like `"10.00"`.
+    Malformed,
+    /// The amount was negative. Use a refund or credit to move money back to
+    /// a customer.
+    Negative,
+    /// The amount had more decimal places than its currency allows.
+    TooPrecise,
+    /// The amount was too large to represent.
+    Overflow,
+}
+
+impl std::fmt::Display for ParseAmountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match *self {
+            ParseAmountError::Malformed => "amount is not a decimal number",
+            ParseAmountError::Negative => "amount is negative",
+            ParseAmountError::TooPrecise => "amount has more decimal places than its currency allows",
+            ParseAmountError::Overflow => "amount is too large",
+        })
+    }
+}
+
+impl StdError for ParseAmountError {}
+
+const DEFAULT_EXPONENT: u32 = 2;
+/// The most decimal places an amount can have, as `10^19` is the largest
+/// power of ten that fits in a `u64`.
+const MAX_EXPONENT: u32 = 19;
+
+/// The number of decimal places used by an ISO 4217 currency.
+fn minor_unit_exponent(currency_iso_code: &str) -> u32 {
+    match currency_iso_code.to_ascii_uppercase().as_ref() {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" |
+        "RWF" | "UGX" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => DEFAULT_EXPONENT,
+    }
+}
+
+fn parse(s: &str, exponent: u32) -> Result<Amount, ParseAmountError> {
+    if s.starts_with('-') {
+        return Err(ParseAmountError::Negative);
+    }
+    let (whole, fraction) = match s.find('.') {
+        Some(i) if i + 1 < s.len() => (&s[..i], &s[i + 1..]),
+        Some(_) => return Err(ParseAmountError::Malformed),
+        None => (s, ""),
+    };
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(ParseAmountError::Malformed);
+    }
+
+    // Trailing zeros don't add precision, so "1000.00" is a valid JPY amount.
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > exponent as usize {
+        return Err(ParseAmountError::TooPrecise);
+    }
+
+    let scale = 10u64.checked_pow(exponent).ok_or(ParseAmountError::Overflow)?;
+    let whole: u64 = whole.parse().map_err(|_| ParseAmountError::Overflow)?;
+    let fraction: u64 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse::<u64>().unwrap() * 10u64.pow(exponent - fraction.len() as u32)
+    };
+    let minor_units = whole.checked_mul(scale)
+        .and_then(|units| units.checked_add(fraction))
+        .ok_or(ParseAmountError::Overflow)?;
+    Ok(Amount{minor_units, exponent})
+}
+
+/// Parses an amount returned by Braintree when its currency isn't known,
+/// keeping however many decimal places it was sent with, up to the 19 that
+/// fit in a `u64`.
+pub(crate) fn parse_response(s: &str) -> Result<Amount, ParseAmountError> {
+    let decimals = s.find('.').map(|i| s.len() - i - 1).unwrap_or(0).min(MAX_EXPONENT as usize) as u32;
+    parse(s, decimals.max(DEFAULT_EXPONENT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_response, Amount, ParseAmountError};
+
+    #[test]
+    fn parses_by_currency_exponent() {
+        let yen = Amount::parse_for_currency("1000", "JPY").unwrap();
+        assert_eq!((yen.minor_units(), yen.exponent()), (1000, 0));
+        assert_eq!(Amount::parse_for_currency("1000.5", "jpy"), Err(ParseAmountError::TooPrecise));
+
+        for currency in &["KWD", "BHD"] {
+            let amount = Amount::parse_for_currency("1.234", currency).unwrap();
+            assert_eq!((amount.minor_units(), amount.exponent()), (1234, 3));
+            assert_eq!(amount.to_string(), "1.234");
+            assert_eq!(Amount::parse_for_currency("1.2345", currency), Err(ParseAmountError::TooPrecise));
+        }
+        assert_eq!(Amount::parse_for_currency("1.5", "BHD").unwrap().to_string(), "1.500");
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        assert_eq!("-1.00".parse::<Amount>(), Err(ParseAmountError::Negative));
+        assert_eq!("10.001".parse::<Amount>(), Err(ParseAmountError::TooPrecise));
+        assert_eq!("10.".parse::<Amount>(), Err(ParseAmountError::Malformed));
+        assert_eq!(".50".parse::<Amount>(), Err(ParseAmountError::Malformed));
+        assert_eq!("1,000.00".parse::<Amount>(), Err(ParseAmountError::Malformed));
+        assert_eq!("".parse::<Amount>(), Err(ParseAmountError::Malformed));
+        assert_eq!("184467440737095516.15".parse::<Amount>().unwrap().minor_units(), u64::MAX);
+        assert_eq!("184467440737095516.16".parse::<Amount>(), Err(ParseAmountError::Overflow));
+        assert_eq!("99999999999999999999".parse::<Amount>(), Err(ParseAmountError::Overflow));
+    }
+
+    #[test]
+    fn compares_by_value() {
+        use std::collections::HashSet;
+
+        let usd: Amount = "10.00".parse().unwrap();
+        let kwd = Amount::parse_for_currency("10.000", "KWD").unwrap();
+        let yen = Amount::parse_for_currency("10", "JPY").unwrap();
+        assert_eq!(usd, kwd);
+        assert_eq!(usd, yen);
+        assert!(Amount::parse_for_currency("10.001", "KWD").unwrap() > usd);
+        assert!(Amount::parse_for_currency("9", "JPY").unwrap() < usd);
+        assert_eq!([usd, kwd, yen].iter().collect::<HashSet<_>>().len(), 1);
+    }
+
+    #[test]
+    fn rejects_over_long_fractions() {
+        let amount = parse_response("0.0000000000000000001").unwrap();
+        assert_eq!((amount.minor_units(), amount.exponent()), (1, 19));
+        assert_eq!(amount.to_string(), "0.0000000000000000001");
+        assert!(amount > Amount::default());
+
+        assert_eq!(parse_response("0.00000000000000000001"), Err(ParseAmountError::TooPrecise));
+        assert_eq!(parse_response("1.123456789012345678901234567890"), Err(ParseAmountError::TooPrecise));
+        assert_eq!(parse_response("1.50000000000000000000000000").unwrap().to_string(), "1.5000000000000000000");
+        assert_eq!(parse_response("10.50000000000000000000000000"), Err(ParseAmountError::Overflow));
+        assert_eq!(parse_response("10.500").unwrap().to_string(), "10.500");
+        assert_eq!(super::parse("1", 20), Err(ParseAmountError::Overflow));
+    }
+}
@@ -0,0 +1,340 @@
+use hyper;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{self, Error};
+use crate::guardrails::Guardrails;
+use crate::metrics::Metrics;
+use crate::middleware::Middleware;
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::transport::{HttpSettings, HttpTransport, Transport};
+use crate::credentials::{ApiKey, Credentials};
+use crate::{Braintree, Environment};
+
+/// Configures a `Braintree` handle. Obtained from `Braintree::builder()`:
+///
+/// ```rust,no_run
+/// # use braintree::{Braintree, Environment};
+/// # use std::time::Duration;
+/// let bt = Braintree::builder()
+///     .environment(Environment::Sandbox)
+///     .merchant_id("<merchant_id>")
+///     .public_key("<public_key>")
+///     .private_key("<private_key>")
+///     .connect_timeout(Duration::from_secs(5))
+///     .read_timeout(Duration::from_secs(60))
+///     .build()
+///     .expect("failed to set up Braintree");
+/// ```
+pub struct BraintreeBuilder {
+    environment: Environment,
+    merchant_id: Option<String>,
+    public_key: Option<String>,
+    private_key: Option<Secret>,
+    credentials: Option<Box<dyn Credentials>>,
+    base_url: Option<String>,
+    http: HttpSettings,
+    user_agent_suffix: Option<String>,
+    api_version: u8,
+    transport: Option<Box<dyn Transport>>,
+    retry: RetryPolicy,
+    metrics: Option<Arc<dyn Metrics>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    guardrails: Option<Guardrails>,
+    dry_run: bool,
+}
+
+impl BraintreeBuilder {
+    pub(crate) fn new() -> BraintreeBuilder {
+        BraintreeBuilder{
+            environment: Environment::Sandbox,
+            merchant_id: None,
+            public_key: None,
+            private_key: None,
+            credentials: None,
+            base_url: None,
+            http: HttpSettings::default(),
+            user_agent_suffix: None,
+            api_version: 4,
+            transport: None,
+            retry: RetryPolicy::default(),
+            metrics: None,
+            middleware: Vec::new(),
+            guardrails: None,
+            dry_run: false,
+        }
+    }
+
+    /// The environment to connect to. Defaults to `Environment::Sandbox`.
+    pub fn environment(mut self, environment: Environment) -> BraintreeBuilder {
+        self.environment = environment;
+        self
+    }
+
+    pub fn merchant_id<S: Into<String>>(mut self, merchant_id: S) -> BraintreeBuilder {
+        self.merchant_id = Some(merchant_id.into());
+        self
+    }
+
+    pub fn public_key<S: Into<String>>(mut self, public_key: S) -> BraintreeBuilder {
+        self.public_key = Some(public_key.into());
+        self
+    }
+
+    pub fn private_key<S: Into<String>>(mut self, private_key: S) -> BraintreeBuilder {
+        self.private_key = Some(Secret::credential(private_key));
+        self
+    }
+
+    /// Authenticate with other credentials than API keys, e.g. an OAuth
+    /// `AccessToken`. These take the place of the environment, merchant ID
+    /// and keys, which are ignored if this is set.
+    pub fn credentials<C: Credentials + 'static>(mut self, credentials: C) -> BraintreeBuilder {
+        self.credentials = Some(Box::new(credentials));
+        self
+    }
+
+    /// Send requests to a different host than the environment's, e.g. a
+    /// local mock server, such as `http://localhost:3000`.
+    pub fn base_url<S: Into<String>>(mut self, base_url: S) -> BraintreeBuilder {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// How long to wait for a connection to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> BraintreeBuilder {
+        self.http.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// How long to wait for data to arrive while reading a response.
+    pub fn read_timeout(mut self, timeout: Duration) -> BraintreeBuilder {
+        self.http.read_timeout = Some(timeout);
+        self
+    }
+
+    /// How long to wait for a request to be written.
+    pub fn write_timeout(mut self, timeout: Duration) -> BraintreeBuilder {
+        self.http.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Send requests through an HTTP proxy.
+    pub fn proxy<S: Into<String>>(mut self, host: S, port: u16) -> BraintreeBuilder {
+        self.http.proxy = Some((host.into(), port));
+        self
+    }
+
+    /// Trust an additional PEM-encoded root certificate.
+    pub fn add_root_certificate<B: Into<Vec<u8>>>(mut self, pem: B) -> BraintreeBuilder {
+        self.http.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Text appended to the `User-Agent` header, e.g. your application's name
+    /// and version.
+    pub fn user_agent_suffix<S: Into<String>>(mut self, suffix: S) -> BraintreeBuilder {
+        self.user_agent_suffix = Some(suffix.into());
+        self
+    }
+
+    /// The version of the Braintree API to request. Defaults to 4.
+    pub fn api_version(mut self, version: u8) -> BraintreeBuilder {
+        self.api_version = version;
+        self
+    }
+
+    /// Send requests with a custom transport instead of over the network.
+    /// The timeout, proxy and certificate settings only apply to the default
+    /// transport, and are ignored if this is set.
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> BraintreeBuilder {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> BraintreeBuilder {
+        self.retry = policy;
+        self
+    }
+
+    /// Report measurements of every request and gateway operation.
+    pub fn metrics<M: Metrics + 'static>(mut self, metrics: M) -> BraintreeBuilder {
+        self.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Add a middleware to the end of the chain that every request passes
+    /// through. See the `middleware` module.
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> BraintreeBuilder {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Refuse requests that break these guardrails. See the `guardrails`
+    /// module.
+    pub fn guardrails(mut self, guardrails: Guardrails) -> BraintreeBuilder {
+        self.guardrails = Some(guardrails);
+        self
+    }
+
+    /// Render requests instead of sending them. Every call then returns
+    /// `Err(Error::DryRun(..))`; see the `dry_run` module.
+    pub fn dry_run(mut self, dry_run: bool) -> BraintreeBuilder {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn build(mut self) -> error::Result<Braintree> {
+        let creds: Box<dyn Credentials> = match self.credentials.take() {
+            Some(credentials) => credentials,
+            None => {
+                let merchant_id = self.merchant_id.take().ok_or_else(|| Error::Setup(String::from("missing merchant id")))?;
+                let public_key = self.public_key.take().ok_or_else(|| Error::Setup(String::from("missing public key")))?;
+                let private_key = self.private_key.take().ok_or_else(|| Error::Setup(String::from("missing private key")))?;
+                Box::new(ApiKey::with_secret(self.environment, merchant_id, public_key, private_key))
+            },
+        };
+        self.shared()?.client(creds)
+    }
+
+    /// Everything but the credentials, which may be shared by several
+    /// handles.
+    pub(crate) fn shared(self) -> error::Result<Shared> {
+        let transport: Arc<dyn Transport> = match self.transport {
+            Some(transport) => Arc::from(transport),
+            None => Arc::new(HttpTransport::new(&self.http).map_err(|err| Error::Setup(format!("failed to set up TLS: {}", err)))?),
+        };
+
+        let mut user_agent = format!("Braintree Rust {}", env!("CARGO_PKG_VERSION"));
+        if let Some(suffix) = self.user_agent_suffix {
+            user_agent.push(' ');
+            user_agent.push_str(&suffix);
+        }
+
+        Ok(Shared{
+            base_url: self.base_url,
+            transport,
+            user_agent,
+            api_version: self.api_version,
+            retry: self.retry,
+            metrics: self.metrics,
+            middleware: self.middleware,
+            guardrails: self.guardrails,
+            dry_run: self.dry_run,
+        })
+    }
+}
+
+/// The settings and transport shared by every handle built from a builder.
+pub(crate) struct Shared {
+    base_url: Option<String>,
+    transport: Arc<dyn Transport>,
+    user_agent: String,
+    api_version: u8,
+    retry: RetryPolicy,
+    metrics: Option<Arc<dyn Metrics>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    guardrails: Option<Guardrails>,
+    dry_run: bool,
+}
+
+impl Shared {
+    /// Builds a handle that authenticates with `creds`.
+    pub(crate) fn client(&self, creds: Box<dyn Credentials>) -> error::Result<Braintree> {
+        let base_url = self.base_url.as_deref().unwrap_or_else(|| creds.environment().base_url()).trim_end_matches('/');
+        let invalid = |err| Error::Setup(format!("invalid base url {:?}: {}", base_url, err));
+        let root_url = hyper::Url::parse(&format!("{}/", base_url)).map_err(invalid)?;
+        let merchant_url = match creds.merchant_id() {
+            Some(merchant_id) => Some(root_url.join(&format!("merchants/{}/", merchant_id)).map_err(invalid)?),
+            None => None,
+        };
+
+        Ok(Braintree{
+            creds,
+            transport: self.transport.clone(),
+            base_url: root_url,
+            merchant_url,
+            user_agent: self.user_agent.clone(),
+            api_version: self.api_version,
+            retry: self.retry.clone(),
+            metrics: self.metrics.clone(),
+            middleware: self.middleware.clone(),
+            guardrails: self.guardrails.clone(),
+            dry_run: self.dry_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hyper::status::StatusCode;
+
+    use super::BraintreeBuilder;
+    use crate::error::Error;
+    use crate::transport::Stub;
+    use crate::Braintree;
+
+    fn builder() -> BraintreeBuilder {
+        Braintree::builder()
+            .merchant_id("my_merchant_id")
+            .public_key("my_public_key")
+            .private_key("my_private_key")
+    }
+
+    #[test]
+    fn collects_http_settings() {
+        let builder = builder()
+            .connect_timeout(Duration::from_secs(5))
+            .read_timeout(Duration::from_secs(60))
+            .write_timeout(Duration::from_secs(30))
+            .proxy("proxy.example.com", 3128)
+            .add_root_certificate("-----BEGIN CERTIFICATE-----");
+        assert_eq!(builder.http.connect_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(builder.http.read_timeout, Some(Duration::from_secs(60)));
+        assert_eq!(builder.http.write_timeout, Some(Duration::from_secs(30)));
+        assert_eq!(builder.http.proxy, Some((String::from("proxy.example.com"), 3128)));
+        assert_eq!(builder.http.root_certificates, [b"-----BEGIN CERTIFICATE-----".to_vec()]);
+
+        let defaults = self::builder().http;
+        assert_eq!((defaults.connect_timeout, defaults.read_timeout, defaults.proxy), (None, None, None));
+    }
+
+    fn sent_headers(builder: BraintreeBuilder) -> hyper::header::Headers {
+        let stub = Stub::new(|_| (StatusCode::NotFound, String::new()));
+        let bt = builder.transport(stub.clone()).build().unwrap();
+        let _ = bt.transaction().find(String::from("abc"));
+        stub.requests()[0].headers.clone()
+    }
+
+    #[test]
+    fn sends_the_user_agent_and_api_version() {
+        let header = |headers: &hyper::header::Headers, name| String::from_utf8(headers.get_raw(name).unwrap()[0].clone()).unwrap();
+        let version = env!("CARGO_PKG_VERSION");
+
+        let headers = sent_headers(builder());
+        assert_eq!(header(&headers, "User-Agent"), format!("Braintree Rust {}", version));
+        assert_eq!(header(&headers, "X-ApiVersion"), "4");
+
+        let headers = sent_headers(builder().user_agent_suffix("my-app/1.2").api_version(6));
+        assert_eq!(header(&headers, "User-Agent"), format!("Braintree Rust {} my-app/1.2", version));
+        assert_eq!(header(&headers, "X-ApiVersion"), "6");
+    }
+
+    #[test]
+    fn sends_requests_to_the_base_url() {
+        let stub = Stub::new(|_| (StatusCode::NotFound, String::new()));
+        let bt = builder().base_url("http://localhost:3000/").transport(stub.clone()).build().unwrap();
+        let _ = bt.transaction().find(String::from("abc"));
+        assert_eq!(stub.requests()[0].url.as_str(), "http://localhost:3000/merchants/my_merchant_id/transactions/abc");
+
+        let bt = builder().transport(stub.clone()).build().unwrap();
+        let _ = bt.transaction().find(String::from("abc"));
+        let expected = format!("{}/merchants/my_merchant_id/transactions/abc", crate::Environment::Sandbox.base_url());
+        assert_eq!(stub.requests()[1].url.as_str(), expected);
+
+        assert!(matches!(builder().base_url("not a url").transport(stub).build(), Err(Error::Setup(_))));
+    }
+}
@@ -0,0 +1,293 @@
+//! A transport that records API exchanges to a file and replays them, so
+//! that tests can run without network access or credentials.
+//!
+//! Record a cassette once against the sandbox:
+//!
+//! ```rust,no_run
+//! # use braintree::Braintree;
+//! # use braintree::cassette::Cassette;
+//! # use braintree::transport::{HttpSettings, HttpTransport};
+//! let network = HttpTransport::new(&HttpSettings::default()).unwrap();
+//! let bt = Braintree::builder()
+//!     .merchant_id("<merchant_id>")
+//!     .public_key("<public_key>")
+//!     .private_key("<private_key>")
+//!     .transport(Cassette::record("tests/cassettes/create_transaction.toml", network))
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//! and then replay it in CI, with any credentials at all:
+//!
+//! ```rust,no_run
+//! # use braintree::Braintree;
+//! # use braintree::cassette::Cassette;
+//! let bt = Braintree::builder()
+//!     .merchant_id("merchant")
+//!     .public_key("public")
+//!     .private_key("private")
+//!     .transport(Cassette::replay("tests/cassettes/create_transaction.toml").unwrap())
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//! Cassettes are TOML files with one `[[interactions]]` entry per request.
+//! Card numbers, CVVs, nonces, credentials and personal data are masked in
+//! both bodies before they're written, and the merchant ID is replaced in
+//! every path.
+//! Requests are matched on their method, path and body, ignoring whitespace
+//! between elements; identical requests are answered in the order they were
+//! recorded.
+
+use hyper;
+use hyper::header::Headers;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use toml;
+
+use crate::trace::redact;
+use crate::transport::{Request, Response, Transport};
+
+/// Response headers that aren't recorded, since the body is stored
+/// decompressed and possibly redacted.
+const SKIPPED_HEADERS: &[&str] = &["content-encoding", "content-length", "set-cookie", "transfer-encoding"];
+
+/// Records or replays API exchanges. See the module documentation.
+pub struct Cassette {
+    path: PathBuf,
+    inner: Option<Box<dyn Transport>>,
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Interaction {
+    method: String,
+    path: String,
+    request_body: String,
+    status: u16,
+    response_headers: Vec<(String, String)>,
+    response_body: String,
+    /// Whether the interaction has been replayed already.
+    used: bool,
+}
+
+impl Cassette {
+    /// Send requests with `inner`, writing each exchange to `path`. Any
+    /// existing cassette at `path` is replaced.
+    pub fn record<P: AsRef<Path>, T: Transport + 'static>(path: P, inner: T) -> Cassette {
+        Cassette{
+            path: path.as_ref().to_path_buf(),
+            inner: Some(Box::new(inner)),
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Answer requests from the cassette at `path`, without sending anything.
+    /// A request with no matching interaction fails with a `NotFound` error.
+    pub fn replay<P: AsRef<Path>>(path: P) -> io::Result<Cassette> {
+        let path = path.as_ref().to_path_buf();
+        let interactions = parse(&fs::read_to_string(&path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("invalid cassette {}: {}", path.display(), err)))?;
+        Ok(Cassette{path, inner: None, interactions: Mutex::new(interactions)})
+    }
+
+    fn record_exchange(&self, inner: &dyn Transport, request: Request) -> hyper::Result<Response> {
+        let method = request.method.to_string();
+        let path = normalize_path(&request.url);
+        let request_body = request.body.as_ref().map(|body| redact(&String::from_utf8_lossy(body))).unwrap_or_default();
+
+        let response = inner.send(request)?;
+        let (status, headers) = (response.status, response.headers.clone());
+        let mut body = Vec::new();
+        response.decoded_body()?.read_to_end(&mut body)?;
+
+        let response_headers = headers.iter()
+            .filter(|header| !SKIPPED_HEADERS.contains(&header.name().to_lowercase().as_str()))
+            .map(|header| (String::from(header.name()), header.value_string()))
+            .collect();
+        let mut interactions = self.interactions.lock().unwrap();
+        interactions.push(Interaction{
+            method,
+            path,
+            request_body,
+            status: status.to_u16(),
+            response_headers,
+            response_body: redact(&String::from_utf8_lossy(&body)),
+            used: false,
+        });
+        fs::write(&self.path, format(&interactions))?;
+
+        let mut headers = headers;
+        headers.remove::<hyper::header::ContentEncoding>();
+        Ok(Response{status, headers, body: Box::new(io::Cursor::new(body))})
+    }
+
+    fn replay_exchange(&self, request: Request) -> hyper::Result<Response> {
+        let method = request.method.to_string();
+        let path = normalize_path(&request.url);
+        let body = request.body.as_ref().map(|body| normalize_body(&redact(&String::from_utf8_lossy(body)))).unwrap_or_default();
+
+        let mut interactions = self.interactions.lock().unwrap();
+        let interaction = interactions.iter_mut()
+            .find(|i| !i.used && i.method == method && i.path == path && normalize_body(&i.request_body) == body)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no recorded interaction for {} {} in {}", method, path, self.path.display())))?;
+        interaction.used = true;
+
+        let mut headers = Headers::new();
+        for (name, value) in &interaction.response_headers {
+            headers.append_raw(name.clone(), value.clone().into_bytes());
+        }
+        Ok(Response{
+            status: hyper::status::StatusCode::from_u16(interaction.status),
+            headers,
+            body: Box::new(io::Cursor::new(interaction.response_body.clone().into_bytes())),
+        })
+    }
+}
+
+impl Transport for Cassette {
+    fn send(&self, request: Request) -> hyper::Result<Response> {
+        match self.inner {
+            Some(ref inner) => self.record_exchange(inner.as_ref(), request),
+            None => self.replay_exchange(request),
+        }
+    }
+}
+
+/// The URL's path and query, with the merchant ID replaced.
+fn normalize_path(url: &hyper::Url) -> String {
+    let mut segments: Vec<&str> = url.path().split('/').collect();
+    if let Some(i) = segments.iter().position(|segment| *segment == "merchants") {
+        if i + 1 < segments.len() {
+            segments[i + 1] = "{merchant_id}";
+        }
+    }
+    let mut path = segments.join("/");
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    path
+}
+
+/// Drops whitespace between elements, so that formatting doesn't matter.
+fn normalize_body(body: &str) -> String {
+    body.split('<')
+        .map(|part| match part.find('>') {
+            Some(end) if part[end + 1..].trim().is_empty() => &part[..end + 1],
+            _ => part,
+        })
+        .collect::<Vec<_>>()
+        .join("<")
+        .trim()
+        .to_string()
+}
+
+fn format(interactions: &[Interaction]) -> String {
+    let entries = interactions.iter().map(|i| {
+        let mut headers = toml::Table::new();
+        for (name, value) in &i.response_headers {
+            headers.insert(name.clone(), toml::Value::String(value.clone()));
+        }
+        let mut entry = toml::Table::new();
+        entry.insert(String::from("method"), toml::Value::String(i.method.clone()));
+        entry.insert(String::from("path"), toml::Value::String(i.path.clone()));
+        entry.insert(String::from("request_body"), toml::Value::String(i.request_body.clone()));
+        entry.insert(String::from("status"), toml::Value::Integer(i64::from(i.status)));
+        entry.insert(String::from("response_headers"), toml::Value::Table(headers));
+        entry.insert(String::from("response_body"), toml::Value::String(i.response_body.clone()));
+        toml::Value::Table(entry)
+    }).collect();
+    let mut root = toml::Table::new();
+    root.insert(String::from("interactions"), toml::Value::Array(entries));
+    root.to_string()
+}
+
+fn parse(text: &str) -> Result<Vec<Interaction>, String> {
+    let root: toml::Table = text.parse().map_err(|err: toml::de::Error| err.to_string())?;
+    let entries = match root.get("interactions") {
+        Some(toml::Value::Array(entries)) => entries,
+        Some(_) => return Err(String::from("interactions must be an array")),
+        None => return Ok(Vec::new()),
+    };
+    entries.iter().enumerate().map(|(n, entry)| {
+        let string = |key: &str| entry.get(key).and_then(toml::Value::as_str).map(String::from)
+            .ok_or_else(|| format!("interaction {} is missing {}", n + 1, key));
+        let status = entry.get("status").and_then(toml::Value::as_integer)
+            .and_then(|status| u16::try_from(status).ok())
+            .ok_or_else(|| format!("interaction {} is missing status", n + 1))?;
+        let response_headers = entry.get("response_headers").and_then(toml::Value::as_table)
+            .map(|headers| headers.iter().filter_map(|(name, value)| Some((name.clone(), String::from(value.as_str()?)))).collect())
+            .unwrap_or_default();
+        Ok(Interaction{
+            method: string("method")?,
+            path: string("path")?,
+            request_body: string("request_body").unwrap_or_default(),
+            status,
+            response_headers,
+            response_body: string("response_body").unwrap_or_default(),
+            used: false,
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper;
+    use std::fs;
+    use std::io::{self, Read};
+
+    use super::Cassette;
+    use crate::transport::{Request, Response, Transport};
+
+    struct Sandbox;
+
+    impl Transport for Sandbox {
+        fn send(&self, request: Request) -> hyper::Result<Response> {
+            let body = format!("<transaction><id>{}</id><credit-card><number>4111111111111111</number></credit-card></transaction>",
+                               request.url.path().rsplit('/').next().unwrap());
+            Ok(Response{
+                status: hyper::status::StatusCode::Ok,
+                headers: hyper::header::Headers::new(),
+                body: Box::new(io::Cursor::new(body.into_bytes())),
+            })
+        }
+    }
+
+    fn request(url: &str, body: &str) -> Request {
+        Request{
+            method: hyper::method::Method::Put,
+            url: hyper::Url::parse(url).unwrap(),
+            headers: hyper::header::Headers::new(),
+            body: Some(body.as_bytes().to_vec()),
+        }
+    }
+
+    fn body(response: Response) -> String {
+        let mut body = String::new();
+        response.decoded_body().unwrap().read_to_string(&mut body).unwrap();
+        body
+    }
+
+    #[test]
+    fn records_and_replays() {
+        let path = std::env::temp_dir().join(format!("braintree-cassette-{}.toml", std::process::id()));
+        let recorder = Cassette::record(&path, Sandbox);
+        let recorded = body(recorder.send(request("https://example.com/merchants/abc/transactions/tx1", "<transaction>\n  <cvv>123</cvv>\n</transaction>")).unwrap());
+        assert!(recorded.contains("4111111111111111"));
+
+        let cassette = fs::read_to_string(&path).unwrap();
+        assert!(!cassette.contains("4111111111111111"));
+        assert!(cassette.contains("/merchants/{merchant_id}/transactions/tx1"));
+
+        let player = Cassette::replay(&path).unwrap();
+        let replayed = body(player.send(request("https://example.com/merchants/xyz/transactions/tx1", "<transaction><cvv>456</cvv></transaction>")).unwrap());
+        assert_eq!(replayed, "<transaction><id>tx1</id><credit-card><number>************1111</number></credit-card></transaction>");
+
+        // Each interaction is only replayed once.
+        assert!(player.send(request("https://example.com/merchants/xyz/transactions/tx1", "<transaction><cvv>456</cvv></transaction>")).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,10 +1,6 @@
-use elementtree;
-use std::convert::From;
-use std::io::Read;
-use std::fmt::Write;
-use xml;
 
-use crate::ToXml;
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::{ToXml, Writer};
 /// A request to retrieve a new client token.
 #[derive(Debug)]
 pub struct Request {
@@ -34,18 +30,11 @@ impl Default for Request {
 }
 
 impl ToXml for Request {
-    fn to_xml(&self, name: Option<&str>) -> String {
-        let name = xml::escape(&name.unwrap_or("client-token"));
-        let mut s = String::new();
-        write!(s, "<{}>", name).unwrap();
-
-        write!(s, "<version type=\"integer\">{}</version>", self.version).unwrap();
-        write_xml!(s, "customer-id", self.customer_id);
-        write_xml!(s, "merchant-account-id", self.merchant_account_id);
-        if let Some(ref options) = self.options { write!(s, "{}", options.to_xml(None)).unwrap(); }
-
-        write!(s, "</{}>", name).unwrap();
-        s
+    fn write_xml(&self, w: &mut Writer) {
+        w.value("version", &self.version);
+        w.field("customer-id", &self.customer_id);
+        w.field("merchant-account-id", &self.merchant_account_id);
+        w.optional_element("options", &self.options);
     }
 }
 
@@ -65,17 +54,10 @@ pub struct Options {
 }
 
 impl ToXml for Options {
-    fn to_xml(&self, name: Option<&str>) -> String {
-        let name = xml::escape(&name.unwrap_or("options"));
-        let mut s = String::new();
-        write!(s, "<{}>", name).unwrap();
-
-        write_xml_type!(s, "fail-on-duplicate-payment-method", "boolean", self.fail_on_duplicate_payment_method);
-        write_xml_type!(s, "make-default", "boolean", self.make_default);
-        write_xml_type!(s, "verify-card", "boolean", self.verify_card);
-
-        write!(s, "</{}>", name).unwrap();
-        s
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("fail-on-duplicate-payment-method", &self.fail_on_duplicate_payment_method);
+        w.field("make-default", &self.make_default);
+        w.field("verify-card", &self.verify_card);
     }
 }
 
@@ -84,11 +66,10 @@ pub struct ClientToken {
     pub value: String,
 }
 
-impl From<Box<dyn Read>> for ClientToken {
-    fn from(xml: Box<dyn Read>) -> ClientToken {
-        let root = elementtree::Element::from_reader(xml).unwrap();
-        ClientToken{
-            value: String::from(root.find("value").unwrap().text()),
-        }
+impl FromXml for ClientToken {
+    fn from_xml(node: &Node) -> Result<ClientToken, DecodeError> {
+        Ok(ClientToken{
+            value: node.child("value")?,
+        })
     }
 }
@@ -0,0 +1,262 @@
+//! Loading settings from environment variables or a configuration file.
+//!
+//! Environment variables are read by `Braintree::from_env()`:
+//!
+//! | Variable                 | Setting                                  |
+//! |--------------------------|------------------------------------------|
+//! | `BRAINTREE_ENVIRONMENT`  | `sandbox` (the default), `production`, `development` or `qa` |
+//! | `BRAINTREE_MERCHANT_ID`  | Merchant ID (required)                   |
+//! | `BRAINTREE_PUBLIC_KEY`   | Public key (required)                    |
+//! | `BRAINTREE_PRIVATE_KEY`  | Private key (required)                   |
+//! | `BRAINTREE_BASE_URL`     | Overrides the environment's URL          |
+//!
+//! Configuration files are TOML, with one table per named profile, so that
+//! the credentials for several merchants or environments can live side by
+//! side:
+//!
+//! ```toml
+//! [profiles.default]
+//! environment = "sandbox"
+//! merchant_id = "<merchant_id>"
+//! public_key = "<public_key>"
+//! private_key = "<private_key>"
+//!
+//! [profiles.eu]
+//! environment = "production"
+//! merchant_id = "<merchant_id>"
+//! public_key = "<public_key>"
+//! private_key = "<private_key>"
+//! read_timeout = 30
+//! ```
+//!
+//! Besides the settings above, a profile may set `base_url`,
+//! `user_agent_suffix` and the `connect_timeout`, `read_timeout` and
+//! `write_timeout` in seconds.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use toml;
+
+use crate::builder::BraintreeBuilder;
+use crate::error::{self, Error};
+
+/// The profile used by `Braintree::from_config()` unless `BRAINTREE_PROFILE`
+/// names another one.
+pub const DEFAULT_PROFILE: &str = "default";
+
+impl BraintreeBuilder {
+    /// Start a builder from the `BRAINTREE_*` environment variables.
+    pub fn from_env() -> error::Result<BraintreeBuilder> {
+        let mut builder = BraintreeBuilder::new();
+        if let Some(environment) = var("BRAINTREE_ENVIRONMENT")? {
+            builder = builder.environment(environment.parse()?);
+        }
+        builder = builder
+            .merchant_id(required_var("BRAINTREE_MERCHANT_ID")?)
+            .public_key(required_var("BRAINTREE_PUBLIC_KEY")?)
+            .private_key(required_var("BRAINTREE_PRIVATE_KEY")?);
+        if let Some(base_url) = var("BRAINTREE_BASE_URL")? {
+            builder = builder.base_url(base_url);
+        }
+        Ok(builder)
+    }
+
+    /// Start a builder from the named profile in a TOML configuration file.
+    pub fn from_profile<P: AsRef<Path>>(path: P, profile: &str) -> error::Result<BraintreeBuilder> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| Error::Setup(format!("couldn't read {}: {}", path.display(), err)))?;
+        let config: toml::Table = text.parse()
+            .map_err(|err| Error::Setup(format!("couldn't parse {}: {}", path.display(), err)))?;
+        let settings = config.get("profiles")
+            .and_then(|profiles| profiles.get(profile))
+            .and_then(toml::Value::as_table)
+            .ok_or_else(|| Error::Setup(format!("no profile named {:?} in {}", profile, path.display())))?;
+
+        let mut builder = BraintreeBuilder::new();
+        for (key, value) in settings {
+            let invalid = |expected: &str| Error::Setup(format!("{} in profile {:?} must be {}", key, profile, expected));
+            let string = || value.as_str().map(String::from).ok_or_else(|| invalid("a string"));
+            let seconds = || match *value {
+                toml::Value::Integer(secs) if secs >= 0 => Ok(Duration::from_secs(secs as u64)),
+                toml::Value::Float(secs) if secs >= 0.0 => Ok(Duration::from_secs_f64(secs)),
+                _ => Err(invalid("a number of seconds")),
+            };
+            builder = match key.as_str() {
+                "environment" => builder.environment(string()?.parse()?),
+                "merchant_id" => builder.merchant_id(string()?),
+                "public_key" => builder.public_key(string()?),
+                "private_key" => builder.private_key(string()?),
+                "base_url" => builder.base_url(string()?),
+                "user_agent_suffix" => builder.user_agent_suffix(string()?),
+                "connect_timeout" => builder.connect_timeout(seconds()?),
+                "read_timeout" => builder.read_timeout(seconds()?),
+                "write_timeout" => builder.write_timeout(seconds()?),
+                _ => return Err(Error::Setup(format!("unknown setting {:?} in profile {:?}", key, profile))),
+            };
+        }
+        Ok(builder)
+    }
+}
+
+/// The profile to load when none is given explicitly.
+pub(crate) fn default_profile() -> error::Result<String> {
+    Ok(var("BRAINTREE_PROFILE")?.unwrap_or_else(|| String::from(DEFAULT_PROFILE)))
+}
+
+fn var(name: &str) -> error::Result<Option<String>> {
+    match env::var(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(Error::Setup(format!("{} is not valid unicode", name))),
+    }
+}
+
+fn required_var(name: &str) -> error::Result<String> {
+    var(name)?.ok_or_else(|| Error::Setup(format!("environment variable {} is not set", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{default_profile, DEFAULT_PROFILE};
+    use crate::builder::BraintreeBuilder;
+    use crate::error::Error;
+    use crate::Environment;
+
+    /// Writes a configuration file that's removed when dropped.
+    struct ConfigFile(PathBuf);
+
+    impl ConfigFile {
+        fn new(name: &str, text: &str) -> ConfigFile {
+            let path = env::temp_dir().join(format!("braintree-config-{}-{}.toml", name, std::process::id()));
+            fs::write(&path, text).unwrap();
+            ConfigFile(path)
+        }
+    }
+
+    impl Drop for ConfigFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    const PROFILES: &str = r#"
+        [profiles.default]
+        environment = "sandbox"
+        merchant_id = "sandbox_merchant"
+        public_key = "sandbox_public"
+        private_key = "sandbox_private"
+
+        [profiles.eu]
+        environment = "Production"
+        merchant_id = "eu_merchant"
+        public_key = "eu_public"
+        private_key = "eu_private"
+        base_url = "http://localhost:4000/"
+        read_timeout = 30
+        connect_timeout = 2.5
+    "#;
+
+    fn setup_error(result: Result<BraintreeBuilder, Error>) -> String {
+        match result {
+            Err(Error::Setup(message)) => message,
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn parses_environments_ignoring_case() {
+        for (name, environment) in [
+            ("sandbox", Environment::Sandbox),
+            ("Sandbox", Environment::Sandbox),
+            ("PRODUCTION", Environment::Production),
+            ("development", Environment::Development),
+            ("Development", Environment::Development),
+            ("qa", Environment::Qa),
+            ("QA", Environment::Qa),
+        ] {
+            assert_eq!(name.parse::<Environment>().unwrap(), environment);
+            assert_eq!(Environment::from_str(name).unwrap(), environment);
+        }
+        assert!("staging".parse::<Environment>().is_err());
+        assert!("".parse::<Environment>().is_err());
+    }
+
+    #[test]
+    fn loads_profiles() {
+        let file = ConfigFile::new("profiles", PROFILES);
+
+        let bt = BraintreeBuilder::from_profile(&file.0, DEFAULT_PROFILE).unwrap().build().unwrap();
+        assert_eq!(bt.environment(), Environment::Sandbox);
+        assert_eq!(bt.merchant_id(), Some("sandbox_merchant"));
+
+        let bt = BraintreeBuilder::from_profile(&file.0, "eu").unwrap().build().unwrap();
+        assert_eq!(bt.environment(), Environment::Production);
+        assert_eq!(bt.merchant_id(), Some("eu_merchant"));
+        assert_eq!(bt.base_url.as_str(), "http://localhost:4000/");
+
+        assert!(setup_error(BraintreeBuilder::from_profile(&file.0, "us")).starts_with("no profile named \"us\""));
+        assert!(setup_error(BraintreeBuilder::from_profile(file.0.with_extension("missing"), DEFAULT_PROFILE)).starts_with("couldn't read"));
+    }
+
+    #[test]
+    fn rejects_invalid_profiles() {
+        let missing = ConfigFile::new("missing", "[profiles.default]\nmerchant_id = \"m\"\npublic_key = \"p\"\n");
+        let result = BraintreeBuilder::from_profile(&missing.0, DEFAULT_PROFILE).unwrap().build();
+        assert!(matches!(result, Err(Error::Setup(ref message)) if message == "missing private key"));
+
+        for (name, text, expected) in [
+            ("unknown", "[profiles.default]\nmerchant = \"m\"\n", "unknown setting \"merchant\" in profile \"default\""),
+            ("type", "[profiles.default]\nmerchant_id = 42\n", "merchant_id in profile \"default\" must be a string"),
+            ("timeout", "[profiles.default]\nread_timeout = -1\n", "read_timeout in profile \"default\" must be a number of seconds"),
+            ("environment", "[profiles.default]\nenvironment = \"staging\"\n", "unknown environment: staging"),
+        ] {
+            let file = ConfigFile::new(name, text);
+            assert_eq!(setup_error(BraintreeBuilder::from_profile(&file.0, DEFAULT_PROFILE)), expected);
+        }
+
+        let malformed = ConfigFile::new("malformed", "[profiles.default\n");
+        assert!(setup_error(BraintreeBuilder::from_profile(&malformed.0, DEFAULT_PROFILE)).starts_with("couldn't parse"));
+    }
+
+    /// Every test that touches the `BRAINTREE_*` variables lives here, so
+    /// that they don't race with each other.
+    #[test]
+    fn reads_environment_variables() {
+        for name in ["BRAINTREE_PROFILE", "BRAINTREE_ENVIRONMENT", "BRAINTREE_MERCHANT_ID", "BRAINTREE_PUBLIC_KEY", "BRAINTREE_PRIVATE_KEY", "BRAINTREE_BASE_URL"] {
+            env::remove_var(name);
+        }
+
+        assert_eq!(default_profile().unwrap(), DEFAULT_PROFILE);
+        env::set_var("BRAINTREE_PROFILE", "eu");
+        assert_eq!(default_profile().unwrap(), "eu");
+        let file = ConfigFile::new("env", PROFILES);
+        let bt = crate::Braintree::from_config(&file.0).unwrap();
+        assert_eq!(bt.merchant_id(), Some("eu_merchant"));
+        env::remove_var("BRAINTREE_PROFILE");
+
+        assert_eq!(setup_error(BraintreeBuilder::from_env()), "environment variable BRAINTREE_MERCHANT_ID is not set");
+        env::set_var("BRAINTREE_MERCHANT_ID", "env_merchant");
+        env::set_var("BRAINTREE_PUBLIC_KEY", "env_public");
+        assert_eq!(setup_error(BraintreeBuilder::from_env()), "environment variable BRAINTREE_PRIVATE_KEY is not set");
+        env::set_var("BRAINTREE_PRIVATE_KEY", "env_private");
+        env::set_var("BRAINTREE_ENVIRONMENT", "Qa");
+        let bt = crate::Braintree::from_env().unwrap();
+        assert_eq!(bt.environment(), Environment::Qa);
+        assert_eq!(bt.merchant_id(), Some("env_merchant"));
+
+        env::set_var("BRAINTREE_ENVIRONMENT", "staging");
+        assert_eq!(setup_error(BraintreeBuilder::from_env()), "unknown environment: staging");
+
+        for name in ["BRAINTREE_ENVIRONMENT", "BRAINTREE_MERCHANT_ID", "BRAINTREE_PUBLIC_KEY", "BRAINTREE_PRIVATE_KEY"] {
+            env::remove_var(name);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! The ways of authenticating with Braintree.
+//!
+//! Most integrations use an `ApiKey`: a merchant ID with its public and
+//! private keys. Partners acting on behalf of other merchants use OAuth
+//! instead, authenticating as themselves with `ClientCredentials` to obtain
+//! an `AccessToken` for each merchant that connects to them.
+
+use base64::Engine;
+use zeroize::Zeroizing;
+
+use crate::error::{self, Error};
+use crate::secret::Secret;
+use crate::Environment;
+
+/// Authenticates requests to Braintree.
+pub trait Credentials: Send + Sync {
+    /// The environment these credentials belong to.
+    fn environment(&self) -> Environment;
+    /// The merchant that requests are made on behalf of, if any. Credentials
+    /// without one can only be used with the OAuth gateway.
+    fn merchant_id(&self) -> Option<&str>;
+    /// The value of the `Authorization` header, e.g. `Basic ...` or
+    /// `Bearer ...`.
+    fn authorization_header(&self) -> Secret;
+    /// The public and private key, used to verify webhook signatures.
+    fn key_pair(&self) -> Option<(&str, &str)> {
+        None
+    }
+    /// The OAuth client ID, used to build connect URLs.
+    fn client_id(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// A merchant's API keys, sent with Basic authentication.
+#[derive(Debug)]
+pub struct ApiKey {
+    env: Environment,
+    merchant_id: String,
+    public_key: String,
+    private_key: Secret,
+    auth_header: Secret,
+}
+
+impl ApiKey {
+    pub fn new<S: Into<String>>(env: Environment, merchant_id: S, public_key: S, private_key: S) -> ApiKey {
+        ApiKey::with_secret(env, merchant_id.into(), public_key.into(), Secret::credential(private_key))
+    }
+
+    pub(crate) fn with_secret(env: Environment, merchant_id: String, public_key: String, private_key: Secret) -> ApiKey {
+        ApiKey{
+            env,
+            merchant_id,
+            auth_header: basic(&public_key, private_key.expose()),
+            public_key,
+            private_key,
+        }
+    }
+}
+
+impl Credentials for ApiKey {
+    fn environment(&self) -> Environment { self.env }
+    fn merchant_id(&self) -> Option<&str> { Some(&self.merchant_id) }
+    fn authorization_header(&self) -> Secret { self.auth_header.clone() }
+    fn key_pair(&self) -> Option<(&str, &str)> { Some((&self.public_key, self.private_key.expose())) }
+}
+
+/// An OAuth application's client ID and secret, sent with Basic
+/// authentication. These are only accepted by the OAuth gateway, to exchange
+/// authorization codes and refresh tokens for access tokens.
+#[derive(Debug)]
+pub struct ClientCredentials {
+    env: Environment,
+    client_id: String,
+    client_secret: Secret,
+    auth_header: Secret,
+}
+
+impl ClientCredentials {
+    /// Create client credentials from the values shown in the Control Panel,
+    /// i.e. `client_id$<environment>$...` and `client_secret$<environment>$...`.
+    /// The environment is taken from the client ID.
+    pub fn new<S: Into<String>>(client_id: S, client_secret: S) -> error::Result<ClientCredentials> {
+        let (client_id, client_secret) = (client_id.into(), Secret::credential(client_secret));
+        let env = parse_environment(&client_id, "client_id")?;
+        if parse_environment(client_secret.expose(), "client_secret")? != env {
+            return Err(Error::Setup(String::from("the client ID and secret are for different environments")));
+        }
+        Ok(ClientCredentials{
+            env,
+            auth_header: basic(&client_id, client_secret.expose()),
+            client_id,
+            client_secret,
+        })
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn client_secret(&self) -> &str {
+        self.client_secret.expose()
+    }
+}
+
+impl Credentials for ClientCredentials {
+    fn environment(&self) -> Environment { self.env }
+    fn merchant_id(&self) -> Option<&str> { None }
+    fn authorization_header(&self) -> Secret { self.auth_header.clone() }
+    fn client_id(&self) -> Option<&str> { Some(&self.client_id) }
+}
+
+/// An OAuth access token, sent with Bearer authentication, which acts on
+/// behalf of the merchant that granted it.
+#[derive(Debug)]
+pub struct AccessToken {
+    env: Environment,
+    merchant_id: String,
+    token: Secret,
+}
+
+impl AccessToken {
+    /// Use an access token of the form
+    /// `access_token$<environment>$<merchant_id>$...`, as returned by
+    /// `OAuthGateway::create_token_from_code()`. The environment and merchant
+    /// are taken from the token itself.
+    pub fn new<S: Into<String>>(token: S) -> error::Result<AccessToken> {
+        let token = Secret::credential(token);
+        let env = parse_environment(token.expose(), "access_token")?;
+        let merchant_id = match token.expose().splitn(4, '$').collect::<Vec<_>>()[..] {
+            [_, _, merchant_id, _] if !merchant_id.is_empty() => String::from(merchant_id),
+            _ => return Err(Error::Setup(String::from("access token is missing its merchant id"))),
+        };
+        Ok(AccessToken{env, merchant_id, token})
+    }
+
+    pub fn token(&self) -> &str {
+        self.token.expose()
+    }
+}
+
+impl Credentials for AccessToken {
+    fn environment(&self) -> Environment { self.env }
+    fn merchant_id(&self) -> Option<&str> { Some(&self.merchant_id) }
+    fn authorization_header(&self) -> Secret { prefixed("Bearer ", self.token.expose()) }
+}
+
+/// Builds a Basic `Authorization` header. Every buffer that holds the
+/// password is sized up front, so that it isn't reallocated, and zeroed
+/// once it's no longer needed.
+fn basic(username: &str, password: &str) -> Secret {
+    let mut credentials = Zeroizing::new(String::with_capacity(username.len() + 1 + password.len()));
+    credentials.push_str(username);
+    credentials.push(':');
+    credentials.push_str(password);
+
+    let mut header = String::with_capacity("Basic ".len() + base64::encoded_len(credentials.len(), true).unwrap_or(0));
+    header.push_str("Basic ");
+    base64::engine::general_purpose::STANDARD.encode_string(credentials.as_bytes(), &mut header);
+    Secret::credential(header)
+}
+
+/// Builds a credential from a prefix and a secret, without leaving copies of
+/// the secret behind.
+fn prefixed(prefix: &str, secret: &str) -> Secret {
+    let mut value = String::with_capacity(prefix.len() + secret.len());
+    value.push_str(prefix);
+    value.push_str(secret);
+    Secret::credential(value)
+}
+
+/// Reads the environment out of an OAuth value of the form
+/// `<kind>$<environment>$...`, which must have something after the
+/// environment.
+fn parse_environment(value: &str, kind: &str) -> error::Result<Environment> {
+    let mut parts = value.splitn(3, '$');
+    if parts.next() != Some(kind) {
+        return Err(Error::Setup(format!("expected a value starting with {}$", kind)));
+    }
+    let env = parts.next()
+        .ok_or_else(|| Error::Setup(format!("{} is missing its environment", kind)))?
+        .parse()?;
+    match parts.next() {
+        Some(rest) if !rest.is_empty() => Ok(env),
+        _ => Err(Error::Setup(format!("{} is incomplete", kind))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessToken, ClientCredentials, Credentials};
+    use crate::error::Error;
+    use crate::Environment;
+
+    fn setup_error<T>(result: Result<T, Error>) -> String {
+        match result {
+            Err(Error::Setup(message)) => message,
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn parses_access_tokens() {
+        let token = AccessToken::new("access_token$production$merchant_123$abc$def").unwrap();
+        assert_eq!(token.environment(), Environment::Production);
+        assert_eq!(token.merchant_id(), Some("merchant_123"));
+        assert_eq!(token.authorization_header().expose(), "Bearer access_token$production$merchant_123$abc$def");
+
+        let token = AccessToken::new("access_token$Sandbox$merchant_123$abc").unwrap();
+        assert_eq!(token.environment(), Environment::Sandbox);
+
+        assert_eq!(setup_error(AccessToken::new("refresh_token$sandbox$merchant_123$abc")), "expected a value starting with access_token$");
+        assert_eq!(setup_error(AccessToken::new("access_token$staging$merchant_123$abc")), "unknown environment: staging");
+        assert_eq!(setup_error(AccessToken::new("access_token")), "access_token is missing its environment");
+        assert_eq!(setup_error(AccessToken::new("access_token$sandbox")), "access_token is incomplete");
+        assert_eq!(setup_error(AccessToken::new("access_token$sandbox$merchant_123")), "access token is missing its merchant id");
+        assert_eq!(setup_error(AccessToken::new("access_token$sandbox$$abc")), "access token is missing its merchant id");
+    }
+
+    #[test]
+    fn validates_client_credentials() {
+        let creds = ClientCredentials::new("client_id$sandbox$abc", "client_secret$sandbox$def").unwrap();
+        assert_eq!(creds.environment(), Environment::Sandbox);
+        assert_eq!(creds.merchant_id(), None);
+        assert_eq!(Credentials::client_id(&creds), Some("client_id$sandbox$abc"));
+
+        assert_eq!(setup_error(ClientCredentials::new("client_secret$sandbox$abc", "client_secret$sandbox$def")), "expected a value starting with client_id$");
+        assert_eq!(setup_error(ClientCredentials::new("client_id$sandbox$abc", "client_id$sandbox$def")), "expected a value starting with client_secret$");
+        assert_eq!(setup_error(ClientCredentials::new("client_id$sandbox$abc", "client_secret$production$def")), "the client ID and secret are for different environments");
+        assert_eq!(setup_error(ClientCredentials::new("client_id$sandbox$", "client_secret$sandbox$def")), "client_id is incomplete");
+        assert_eq!(setup_error(ClientCredentials::new("client_id$nowhere$abc", "client_secret$sandbox$def")), "unknown environment: nowhere");
+    }
+}
@@ -1,8 +1,8 @@
-use std::fmt::Write;
-use xml;
 
 use crate::address::Address as Address;
-use crate::ToXml;
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::{ToXml, Writer};
+use crate::secret::Secret;
 
 /// A record that includes credit card information.
 ///
@@ -13,44 +13,114 @@ use crate::ToXml;
 #[derive(Debug, Default)]
 pub struct CreditCard {
     pub cardholder_name: Option<String>,
-    pub cvv: Option<String>,
+    pub cvv: Option<Secret>,
     pub expiration_date: Option<String>,
     pub expiration_month: Option<String>,
     pub expiration_year: Option<String>,
-    pub number: Option<String>,
+    pub number: Option<Secret>,
     pub customer_id: Option<String>,
     pub token: Option<String>,
     pub billing_address: Option<Address>,
+    /// The first six digits of the card number. Only populated on responses.
+    pub bin: Option<String>,
+    /// The last four digits of the card number. Only populated on responses.
+    pub last_4: Option<String>,
+    /// The card brand. Only populated on responses.
+    pub card_type: Option<CardType>,
 }
 
 impl ToXml for CreditCard {
-    fn to_xml(&self, name: Option<&str>) -> String {
-        let name = xml::escape(&name.unwrap_or("credit-card"));
-        let mut s = String::new();
-        write!(s, "<{}>", name).unwrap();
-        write_xml!(s, "cardholder-name", self.cardholder_name);
-        write_xml!(s, "cvv", self.cvv);
-        write_xml!(s, "expiration-date", self.expiration_date);
-        write_xml!(s, "expiration-month", self.expiration_month);
-        write_xml!(s, "expiration-year", self.expiration_year);
-        write_xml!(s, "number", self.number);
-        write_xml!(s, "token", self.token);
-        if let Some(ref billing_address) = self.billing_address { write!(s, "{}", billing_address.to_xml(Some("billing-address"))).unwrap(); }
-        write!(s, "</{}>", name).unwrap();
-        s
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("cardholder-name", &self.cardholder_name);
+        w.field("cvv", &self.cvv);
+        w.field("expiration-date", &self.expiration_date);
+        w.field("expiration-month", &self.expiration_month);
+        w.field("expiration-year", &self.expiration_year);
+        w.field("number", &self.number);
+        w.field("token", &self.token);
+        w.optional_element("billing-address", &self.billing_address);
     }
 }
 
-impl From<&elementtree::Element> for CreditCard {
-    fn from(root: &elementtree::Element) -> CreditCard {
-        CreditCard{
-            cardholder_name: Some(String::from(root.find("cardholder-name").unwrap().text())),
-            expiration_month: Some(String::from(root.find("expiration-month").unwrap().text())),
-            expiration_year: Some(String::from(root.find("expiration-year").unwrap().text())),
-            token: Some(String::from(root.find("token").unwrap().text())),
-            customer_id: Some(String::from(root.find("customer-id").unwrap().text())),
-            billing_address: Some(Address::from(root.find("billing-address").unwrap())),
+impl FromXml for CreditCard {
+    fn from_xml(node: &Node) -> Result<CreditCard, DecodeError> {
+        Ok(CreditCard{
+            cardholder_name: node.child_opt("cardholder-name")?,
+            expiration_month: node.child_opt("expiration-month")?,
+            expiration_year: node.child_opt("expiration-year")?,
+            token: node.child_opt("token")?,
+            customer_id: node.child_opt("customer-id")?,
+            billing_address: node.child_opt("billing-address")?,
+            bin: node.child_opt("bin")?,
+            last_4: node.child_opt("last-4")?,
+            card_type: node.child_opt("card-type")?,
             ..Default::default()
+        })
+    }
+}
+
+/// The card brand reported by Braintree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CardType {
+    AmericanExpress,
+    CarteBlanche,
+    ChinaUnionPay,
+    DinersClub,
+    Discover,
+    Elo,
+    Jcb,
+    Laser,
+    Maestro,
+    MasterCard,
+    Solo,
+    Switch,
+    Visa,
+    /// A card type this version of the library doesn't know about, or
+    /// `"Unknown"` if Braintree couldn't identify the card.
+    Unknown(String),
+}
+
+impl From<String> for CardType {
+    fn from(s: String) -> CardType {
+        match s.as_ref() {
+            "American Express" => CardType::AmericanExpress,
+            "Carte Blanche" => CardType::CarteBlanche,
+            "China UnionPay" => CardType::ChinaUnionPay,
+            "Diners Club" => CardType::DinersClub,
+            "Discover" => CardType::Discover,
+            "Elo" => CardType::Elo,
+            "JCB" => CardType::Jcb,
+            "Laser" => CardType::Laser,
+            "Maestro" => CardType::Maestro,
+            "MasterCard" => CardType::MasterCard,
+            "Solo" => CardType::Solo,
+            "Switch" => CardType::Switch,
+            "Visa" => CardType::Visa,
+            _ => CardType::Unknown(s),
         }
     }
 }
+
+impl From<CardType> for String {
+    fn from(t: CardType) -> String {
+        match t {
+            CardType::AmericanExpress => String::from("American Express"),
+            CardType::CarteBlanche => String::from("Carte Blanche"),
+            CardType::ChinaUnionPay => String::from("China UnionPay"),
+            CardType::DinersClub => String::from("Diners Club"),
+            CardType::Discover => String::from("Discover"),
+            CardType::Elo => String::from("Elo"),
+            CardType::Jcb => String::from("JCB"),
+            CardType::Laser => String::from("Laser"),
+            CardType::Maestro => String::from("Maestro"),
+            CardType::MasterCard => String::from("MasterCard"),
+            CardType::Solo => String::from("Solo"),
+            CardType::Switch => String::from("Switch"),
+            CardType::Visa => String::from("Visa"),
+            CardType::Unknown(value) => value,
+        }
+    }
+}
+
+from_xml_via_string!(CardType);
+xml_value_via_string!(CardType);
@@ -0,0 +1,299 @@
+use chrono;
+use std::convert::From;
+
+use crate::address;
+use crate::amount::Amount;
+use crate::credit_card;
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::search;
+use crate::encode::{ToXml, Writer};
+
+/// The result of verifying a credit card, either explicitly through
+/// `bt.credit_card_verification().create()` or implicitly when a card is
+/// stored in the Vault with the `verify_card` option.
+#[derive(Debug)]
+pub struct CreditCardVerification {
+    pub id: String,
+    pub status: Status,
+    pub amount: Option<Amount>,
+    pub currency_iso_code: Option<String>,
+    pub merchant_account_id: Option<String>,
+    /// The AVS error response code, set when the AVS check could not be performed.
+    pub avs_error_response_code: Option<String>,
+    /// The AVS response code for the billing postal code.
+    pub avs_postal_code_response_code: Option<String>,
+    /// The AVS response code for the billing street address.
+    pub avs_street_address_response_code: Option<String>,
+    /// The CVV response code.
+    pub cvv_response_code: Option<String>,
+    pub processor_response_code: Option<String>,
+    pub processor_response_text: Option<String>,
+    pub processor_response_type: Option<String>,
+    pub network_response_code: Option<String>,
+    pub network_response_text: Option<String>,
+    /// Only set when `status` is `GatewayRejected`.
+    pub gateway_rejection_reason: Option<GatewayRejectionReason>,
+    pub risk_data: Option<RiskData>,
+    pub credit_card: Option<credit_card::CreditCard>,
+    pub billing: Option<address::Address>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl FromXml for CreditCardVerification {
+    fn from_xml(node: &Node) -> Result<CreditCardVerification, DecodeError> {
+        Ok(CreditCardVerification{
+            id: node.child("id")?,
+            status: node.child("status")?,
+            amount: node.child_opt("amount")?,
+            currency_iso_code: node.child_opt("currency-iso-code")?,
+            merchant_account_id: node.child_opt("merchant-account-id")?,
+            avs_error_response_code: node.child_opt("avs-error-response-code")?,
+            avs_postal_code_response_code: node.child_opt("avs-postal-code-response-code")?,
+            avs_street_address_response_code: node.child_opt("avs-street-address-response-code")?,
+            cvv_response_code: node.child_opt("cvv-response-code")?,
+            processor_response_code: node.child_opt("processor-response-code")?,
+            processor_response_text: node.child_opt("processor-response-text")?,
+            processor_response_type: node.child_opt("processor-response-type")?,
+            network_response_code: node.child_opt("network-response-code")?,
+            network_response_text: node.child_opt("network-response-text")?,
+            gateway_rejection_reason: node.child_opt("gateway-rejection-reason")?,
+            risk_data: node.child_opt("risk-data")?,
+            credit_card: node.child_opt("credit-card")?,
+            billing: node.child_opt("billing")?,
+            created_at: node.child_opt("created-at")?,
+        })
+    }
+}
+
+/// Risk data returned by Braintree's fraud tools for a verification.
+#[derive(Debug)]
+pub struct RiskData {
+    pub id: Option<String>,
+    /// The fraud decision, e.g. "Approve", "Review" or "Decline".
+    pub decision: Option<String>,
+    pub device_data_captured: Option<bool>,
+    pub fraud_service_provider: Option<String>,
+}
+
+impl FromXml for RiskData {
+    fn from_xml(node: &Node) -> Result<RiskData, DecodeError> {
+        Ok(RiskData{
+            id: node.child_opt("id")?,
+            decision: node.child_opt("decision")?,
+            device_data_captured: node.child_opt("device-data-captured")?,
+            fraud_service_provider: node.child_opt("fraud-service-provider")?,
+        })
+    }
+}
+
+/// A request to verify a credit card without storing it in the Vault.
+#[derive(Debug, Default)]
+pub(crate) struct Request {
+    pub credit_card: credit_card::CreditCard,
+    pub options: Option<Options>,
+}
+
+impl ToXml for Request {
+    fn write_xml(&self, w: &mut Writer) {
+        w.element("credit-card", &self.credit_card);
+        w.optional_element("options", &self.options);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// The merchant account to verify against. If not specified, your
+    /// account's default merchant account will be used.
+    pub merchant_account_id: Option<String>,
+    /// The amount to authorize. Defaults to a zero-dollar authorization on
+    /// processors that support it, and one dollar otherwise.
+    pub amount: Option<Amount>,
+}
+
+impl ToXml for Options {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("merchant-account-id", &self.merchant_account_id);
+        w.field("amount", &self.amount);
+    }
+}
+
+/// Criteria for searching verifications. Every criterion that is set must
+/// match; the default value matches every verification.
+#[derive(Debug, Default)]
+pub struct Search {
+    pub id: Option<search::Text>,
+    pub status: Vec<Status>,
+    pub created_at: Option<search::Range<chrono::DateTime<chrono::Utc>>>,
+    pub credit_card_card_type: Vec<credit_card::CardType>,
+    pub credit_card_cardholder_name: Option<search::Text>,
+    pub credit_card_expiration_date: Option<search::Text>,
+    /// Braintree only supports `StartsWith` (on the BIN) and `EndsWith` (on
+    /// the last four digits) for card numbers.
+    pub credit_card_number: Option<search::Text>,
+    pub billing_address_details_postal_code: Option<search::Text>,
+    pub customer_email: Option<search::Text>,
+    pub customer_id: Option<search::Text>,
+    pub payment_method_token: Option<search::Text>,
+}
+
+impl ToXml for Search {
+    fn write_xml(&self, w: &mut Writer) {
+        w.optional_element("id", &self.id);
+        w.values("status", &self.status);
+        w.optional_element("created-at", &self.created_at);
+        w.values("credit-card-card-type", &self.credit_card_card_type);
+        w.optional_element("credit-card-cardholder-name", &self.credit_card_cardholder_name);
+        w.optional_element("credit-card-expiration-date", &self.credit_card_expiration_date);
+        w.optional_element("credit-card-number", &self.credit_card_number);
+        w.optional_element("billing-address-details-postal-code", &self.billing_address_details_postal_code);
+        w.optional_element("customer-email", &self.customer_email);
+        w.optional_element("customer-id", &self.customer_id);
+        w.optional_element("payment-method-token", &self.payment_method_token);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Verified,
+    ProcessorDeclined,
+    GatewayRejected,
+    Failed,
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
+}
+
+impl From<String> for Status {
+    fn from(s: String) -> Status {
+        match s.as_ref() {
+            "verified" => Status::Verified,
+            "processor_declined" => Status::ProcessorDeclined,
+            "gateway_rejected" => Status::GatewayRejected,
+            "failed" => Status::Failed,
+            _ => Status::Unknown(s),
+        }
+    }
+}
+
+impl From<Status> for String {
+    fn from(s: Status) -> String {
+        match s {
+            Status::Verified => String::from("verified"),
+            Status::ProcessorDeclined => String::from("processor_declined"),
+            Status::GatewayRejected => String::from("gateway_rejected"),
+            Status::Failed => String::from("failed"),
+            Status::Unknown(value) => value,
+        }
+    }
+}
+
+from_xml_via_string!(Status);
+xml_value_via_string!(Status);
+
+/// The reason the gateway rejected a verification before it reached the
+/// processor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GatewayRejectionReason {
+    ApplicationIncomplete,
+    Avs,
+    AvsAndCvv,
+    Cvv,
+    Duplicate,
+    Fraud,
+    RiskThreshold,
+    ThreeDSecure,
+    TokenIssuance,
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
+}
+
+impl From<String> for GatewayRejectionReason {
+    fn from(s: String) -> GatewayRejectionReason {
+        match s.as_ref() {
+            "application_incomplete" => GatewayRejectionReason::ApplicationIncomplete,
+            "avs" => GatewayRejectionReason::Avs,
+            "avs_and_cvv" => GatewayRejectionReason::AvsAndCvv,
+            "cvv" => GatewayRejectionReason::Cvv,
+            "duplicate" => GatewayRejectionReason::Duplicate,
+            "fraud" => GatewayRejectionReason::Fraud,
+            "risk_threshold" => GatewayRejectionReason::RiskThreshold,
+            "three_d_secure" => GatewayRejectionReason::ThreeDSecure,
+            "token_issuance" => GatewayRejectionReason::TokenIssuance,
+            _ => GatewayRejectionReason::Unknown(s),
+        }
+    }
+}
+
+impl From<GatewayRejectionReason> for String {
+    fn from(r: GatewayRejectionReason) -> String {
+        match r {
+            GatewayRejectionReason::ApplicationIncomplete => String::from("application_incomplete"),
+            GatewayRejectionReason::Avs => String::from("avs"),
+            GatewayRejectionReason::AvsAndCvv => String::from("avs_and_cvv"),
+            GatewayRejectionReason::Cvv => String::from("cvv"),
+            GatewayRejectionReason::Duplicate => String::from("duplicate"),
+            GatewayRejectionReason::Fraud => String::from("fraud"),
+            GatewayRejectionReason::RiskThreshold => String::from("risk_threshold"),
+            GatewayRejectionReason::ThreeDSecure => String::from("three_d_secure"),
+            GatewayRejectionReason::TokenIssuance => String::from("token_issuance"),
+            GatewayRejectionReason::Unknown(value) => value,
+        }
+    }
+}
+
+from_xml_via_string!(GatewayRejectionReason);
+
+#[cfg(test)]
+mod tests {
+    use super::{CreditCardVerification, GatewayRejectionReason, Status};
+    use crate::credit_card::CardType;
+    use crate::decode::from_reader;
+
+    #[test]
+    fn decodes_verifications() {
+        let xml = r#"<verification>
+            <id>verification_id</id>
+            <status>processor_declined</status>
+            <amount>1.00</amount>
+            <currency-iso-code>USD</currency-iso-code>
+            <merchant-account-id>store_usd</merchant-account-id>
+            <avs-error-response-code nil="true"/>
+            <avs-postal-code-response-code>M</avs-postal-code-response-code>
+            <avs-street-address-response-code>N</avs-street-address-response-code>
+            <cvv-response-code>M</cvv-response-code>
+            <processor-response-code>2000</processor-response-code>
+            <processor-response-text>Do Not Honor</processor-response-text>
+            <processor-response-type>soft_declined</processor-response-type>
+            <gateway-rejection-reason nil="true"/>
+            <risk-data><id>risk_id</id><decision>Approve</decision><device-data-captured type="boolean">true</device-data-captured></risk-data>
+            <credit-card><bin>400011</bin><last-4>1115</last-4><card-type>Visa</card-type></credit-card>
+            <billing><postal-code>60622</postal-code></billing>
+            <created-at type="datetime">2024-01-02T03:04:05Z</created-at>
+        </verification>"#;
+        let verification: CreditCardVerification = from_reader(xml.as_bytes()).unwrap();
+        assert_eq!(verification.id, "verification_id");
+        assert_eq!(verification.status, Status::ProcessorDeclined);
+        assert_eq!(verification.amount, Some("1.00".parse().unwrap()));
+        assert_eq!(verification.merchant_account_id.as_deref(), Some("store_usd"));
+        assert_eq!(verification.avs_error_response_code, None);
+        assert_eq!(verification.avs_postal_code_response_code.as_deref(), Some("M"));
+        assert_eq!(verification.avs_street_address_response_code.as_deref(), Some("N"));
+        assert_eq!(verification.cvv_response_code.as_deref(), Some("M"));
+        assert_eq!(verification.processor_response_code.as_deref(), Some("2000"));
+        assert_eq!(verification.processor_response_text.as_deref(), Some("Do Not Honor"));
+        assert_eq!(verification.gateway_rejection_reason, None);
+        let risk_data = verification.risk_data.unwrap();
+        assert_eq!((risk_data.decision.as_deref(), risk_data.device_data_captured), (Some("Approve"), Some(true)));
+        assert_eq!(verification.credit_card.unwrap().card_type, Some(CardType::Visa));
+        assert!(verification.created_at.is_some());
+    }
+
+    #[test]
+    fn decodes_gateway_rejections() {
+        let xml = "<verification><id>verification_id</id><status>gateway_rejected</status>\
+                   <gateway-rejection-reason>cvv</gateway-rejection-reason></verification>";
+        let verification: CreditCardVerification = from_reader(xml.as_bytes()).unwrap();
+        assert_eq!(verification.status, Status::GatewayRejected);
+        assert_eq!(verification.gateway_rejection_reason, Some(GatewayRejectionReason::Cvv));
+        assert_eq!((verification.amount, verification.processor_response_code), (None, None));
+    }
+}
@@ -0,0 +1,377 @@
+//! Decoding of Braintree's XML responses into typed values.
+//!
+//! Braintree annotates its XML with `type` attributes (`integer`, `boolean`,
+//! `decimal`, `datetime`, `date`, `array` and `collection`) and marks missing
+//! values with `nil="true"`. Every resource model implements `FromXml` on top
+//! of a `Node`, which checks those attributes and keeps track of where in the
+//! document it is, so that a mismatch is reported with its exact path rather
+//! than as a panic.
+
+use chrono;
+use elementtree;
+use std;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::io::Read;
+
+use crate::amount::{self, Amount};
+
+/// A value that can be decoded from a Braintree XML element.
+pub trait FromXml: Sized {
+    fn from_xml(node: &Node) -> Result<Self, DecodeError>;
+}
+
+/// Parses a complete XML document and decodes its root element.
+pub fn from_reader<T: FromXml, R: Read>(r: R) -> Result<T, DecodeError> {
+    let root = elementtree::Element::from_reader(r).map_err(|err| DecodeError{
+        path: String::from("/"),
+        kind: DecodeErrorKind::Malformed(err.to_string()),
+    })?;
+    T::from_xml(&Node::root(&root))
+}
+
+/// An element in a response document, along with its path from the root.
+pub struct Node<'a> {
+    elem: &'a elementtree::Element,
+    path: String,
+}
+
+impl<'a> Node<'a> {
+    /// Wraps the root element of a document.
+    pub fn root(elem: &'a elementtree::Element) -> Node<'a> {
+        Node{elem, path: format!("/{}", elem.tag().name())}
+    }
+
+    /// The underlying element, for values that don't fit the typed helpers.
+    pub fn element(&self) -> &'a elementtree::Element {
+        self.elem
+    }
+
+    /// The path of this element from the root, e.g. `/transaction/amount`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Whether the element is marked with `nil="true"`.
+    pub fn is_nil(&self) -> bool {
+        self.elem.get_attr("nil") == Some("true")
+    }
+
+    /// The element's `type` attribute, if any.
+    pub fn type_attr(&self) -> Option<&'a str> {
+        self.elem.get_attr("type")
+    }
+
+    /// Looks up a direct child element.
+    pub fn find(&self, tag: &str) -> Option<Node<'a>> {
+        self.elem.children().find(|elem| elem.tag().name() == tag).map(|elem| Node{elem, path: format!("{}/{}", self.path, tag)})
+    }
+
+    /// Decodes a required child element. It is an error for the child to be
+    /// missing or `nil`.
+    pub fn child<T: FromXml>(&self, tag: &str) -> Result<T, DecodeError> {
+        match self.find(tag) {
+            Some(ref node) if node.is_nil() => Err(node.error(DecodeErrorKind::UnexpectedNil)),
+            Some(ref node) => T::from_xml(node),
+            None => Err(DecodeError{path: format!("{}/{}", self.path, tag), kind: DecodeErrorKind::Missing}),
+        }
+    }
+
+    /// Decodes an optional child element, returning `None` if it is missing
+    /// or `nil`.
+    pub fn child_opt<T: FromXml>(&self, tag: &str) -> Result<Option<T>, DecodeError> {
+        match self.find(tag) {
+            Some(ref node) if !node.is_nil() => T::from_xml(node).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Decodes a required child element with a custom function, for values
+    /// whose decoding depends on their context, e.g. an amount whose
+    /// precision depends on a sibling currency code.
+    pub fn child_with<T, F>(&self, tag: &str, f: F) -> Result<T, DecodeError>
+        where F: FnOnce(&Node<'a>) -> Result<T, DecodeError>
+    {
+        match self.find(tag) {
+            Some(ref node) if node.is_nil() => Err(node.error(DecodeErrorKind::UnexpectedNil)),
+            Some(ref node) => f(node),
+            None => Err(DecodeError{path: format!("{}/{}", self.path, tag), kind: DecodeErrorKind::Missing}),
+        }
+    }
+
+    /// Decodes every direct child element with the given tag. This is
+    /// useful for collections that mix records with paging metadata.
+    pub fn all<T: FromXml>(&self, tag: &str) -> Result<Vec<T>, DecodeError> {
+        self.children().filter(|node| node.elem.tag().name() == tag).map(|node| T::from_xml(&node)).collect()
+    }
+
+    /// Iterates over the direct child elements.
+    pub fn children(&self) -> impl Iterator<Item = Node<'a>> + '_ {
+        self.elem.children().enumerate().map(move |(i, elem)| Node{
+            elem,
+            path: format!("{}/{}[{}]", self.path, elem.tag().name(), i),
+        })
+    }
+
+    /// The element's text, after checking that its `type` attribute (if any)
+    /// is the expected one.
+    pub fn text_of_type(&self, expected: &'static str) -> Result<&'a str, DecodeError> {
+        match self.type_attr() {
+            Some(found) if found != expected => Err(self.error(DecodeErrorKind::TypeMismatch{expected, found: String::from(found)})),
+            _ => Ok(self.elem.text()),
+        }
+    }
+
+    /// The element's text, for values that aren't annotated with a type.
+    /// Arrays and collections are rejected.
+    pub fn text(&self) -> Result<&'a str, DecodeError> {
+        match self.type_attr() {
+            Some(found @ "array") | Some(found @ "collection") => Err(self.error(DecodeErrorKind::TypeMismatch{expected: "string", found: String::from(found)})),
+            _ => Ok(self.elem.text()),
+        }
+    }
+
+    /// Builds an error located at this element.
+    pub fn error(&self, kind: DecodeErrorKind) -> DecodeError {
+        DecodeError{path: self.path.clone(), kind}
+    }
+
+    /// Builds an error for text that couldn't be parsed as the expected type.
+    pub fn invalid(&self, expected: &'static str) -> DecodeError {
+        self.error(DecodeErrorKind::Invalid{expected, value: String::from(self.elem.text())})
+    }
+}
+
+/// An error decoding a response, along with the path of the element at fault.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeError {
+    pub path: String,
+    pub kind: DecodeErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeErrorKind {
+    /// The document was not well-formed XML.
+    Malformed(String),
+    /// A required element was missing.
+    Missing,
+    /// A required element was marked `nil`.
+    UnexpectedNil,
+    /// The element's `type` attribute didn't match the expected type.
+    TypeMismatch { expected: &'static str, found: String },
+    /// The element's text couldn't be parsed as the expected type.
+    Invalid { expected: &'static str, value: String },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.kind {
+            DecodeErrorKind::Malformed(ref err) => write!(f, "malformed XML: {}", err),
+            DecodeErrorKind::Missing => write!(f, "{}: missing element", self.path),
+            DecodeErrorKind::UnexpectedNil => write!(f, "{}: unexpected nil", self.path),
+            DecodeErrorKind::TypeMismatch{expected, ref found} => write!(f, "{}: expected type {}, found {}", self.path, expected, found),
+            DecodeErrorKind::Invalid{expected, ref value} => write!(f, "{}: expected {}, found {:?}", self.path, expected, value),
+        }
+    }
+}
+
+impl StdError for DecodeError {}
+
+impl FromXml for String {
+    fn from_xml(node: &Node) -> Result<String, DecodeError> {
+        node.text().map(String::from)
+    }
+}
+
+impl FromXml for bool {
+    fn from_xml(node: &Node) -> Result<bool, DecodeError> {
+        match node.text_of_type("boolean")? {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(node.invalid("boolean")),
+        }
+    }
+}
+
+macro_rules! from_xml_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FromXml for $t {
+                fn from_xml(node: &Node) -> Result<$t, DecodeError> {
+                    node.text_of_type("integer")?.parse().map_err(|_| node.invalid("integer"))
+                }
+            }
+        )*
+    }
+}
+
+from_xml_integer!(u8, u16, u32, u64, i32, i64, usize);
+
+impl FromXml for Amount {
+    fn from_xml(node: &Node) -> Result<Amount, DecodeError> {
+        amount::parse_response(node.text_of_type("decimal")?).map_err(|_| node.invalid("decimal"))
+    }
+}
+
+impl FromXml for chrono::DateTime<chrono::Utc> {
+    fn from_xml(node: &Node) -> Result<chrono::DateTime<chrono::Utc>, DecodeError> {
+        chrono::DateTime::parse_from_rfc3339(node.text_of_type("datetime")?)
+            .map(|datetime| datetime.with_timezone(&chrono::Utc))
+            .map_err(|_| node.invalid("datetime"))
+    }
+}
+
+impl FromXml for chrono::NaiveDate {
+    fn from_xml(node: &Node) -> Result<chrono::NaiveDate, DecodeError> {
+        chrono::NaiveDate::parse_from_str(node.text_of_type("date")?, crate::DATE_FORMAT).map_err(|_| node.invalid("date"))
+    }
+}
+
+impl<T: FromXml> FromXml for Box<T> {
+    fn from_xml(node: &Node) -> Result<Box<T>, DecodeError> {
+        T::from_xml(node).map(Box::new)
+    }
+}
+
+/// Decodes an `array` or `collection` element, with each child element
+/// becoming an item.
+impl<T: FromXml> FromXml for Vec<T> {
+    fn from_xml(node: &Node) -> Result<Vec<T>, DecodeError> {
+        match node.type_attr() {
+            None | Some("array") | Some("collection") => node.children().map(|child| T::from_xml(&child)).collect(),
+            Some(found) => Err(node.error(DecodeErrorKind::TypeMismatch{expected: "array", found: String::from(found)})),
+        }
+    }
+}
+
+/// Decodes a set of free-form fields, such as custom fields, keyed by tag.
+impl FromXml for HashMap<String, String> {
+    fn from_xml(node: &Node) -> Result<HashMap<String, String>, DecodeError> {
+        node.children().map(|child| Ok((String::from(child.element().tag().name()), String::from(child.text()?)))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elementtree;
+
+    use crate::{credit_card, credit_card_verification, line_item, merchant_account, transaction, webhook_notification};
+    use crate::encode::{self, ToXml, Writer};
+    use super::{from_reader, FromXml, Node};
+
+    /// Checks that a known value and an unknown one both survive a trip
+    /// through the decoder and the encoder unchanged.
+    fn check<T>(known: &str, expected: T)
+        where T: FromXml + Clone + PartialEq + std::fmt::Debug + From<String> + Into<String>
+    {
+        struct Value<T>(T);
+        impl<T: Clone + Into<String>> ToXml for Value<T> {
+            fn write_xml(&self, w: &mut Writer) {
+                w.value("value", &self.0.clone().into());
+            }
+        }
+
+        for (text, value) in [(known, expected), ("something_new", T::from(String::from("something_new")))] {
+            let parsed: T = from_reader(format!("<value>{}</value>", text).as_bytes()).unwrap();
+            assert_eq!(parsed, value);
+            assert_eq!(parsed.clone().into(), text);
+
+            let xml = encode::to_xml("root", &Value(parsed)).unwrap();
+            assert_eq!(xml, format!("<root><value>{}</value></root>", text));
+        }
+    }
+
+    #[test]
+    fn unknown_enum_values() {
+        check("sale", transaction::Type::Sale);
+        check("settled", transaction::Status::Settled);
+        check("held", transaction::EscrowStatus::Held);
+        check("Visa", credit_card::CardType::Visa);
+        check("verified", credit_card_verification::Status::Verified);
+        check("avs", credit_card_verification::GatewayRejectionReason::Avs);
+        check("debit", line_item::Kind::Debit);
+        check("active", merchant_account::Status::Active);
+        check("bank", merchant_account::FundingDestination::Bank);
+        check("check", webhook_notification::Kind::Check);
+
+        assert_eq!(transaction::Status::from(String::from("something_new")), transaction::Status::Unknown(String::from("something_new")));
+        assert_eq!(credit_card::CardType::from(String::from("Unknown")), credit_card::CardType::Unknown(String::from("Unknown")));
+    }
+
+    #[test]
+    fn unknown_enum_values_in_a_response() {
+        let xml = r#"<transaction>
+            <id>abc123</id>
+            <type>sale_with_a_twist</type>
+            <amount>10.00</amount>
+            <currency-iso-code>USD</currency-iso-code>
+            <status>settlement_on_the_moon</status>
+            <escrow-status>escrow_in_orbit</escrow-status>
+            <created-at type="datetime">2024-01-31T17:45:00Z</created-at>
+            <updated-at type="datetime">2024-01-31T17:45:00Z</updated-at>
+        </transaction>"#;
+        let transaction: transaction::Transaction = from_reader(xml.as_bytes()).unwrap();
+        assert_eq!(transaction.typ, transaction::Type::Unknown(String::from("sale_with_a_twist")));
+        assert_eq!(transaction.status, transaction::Status::Unknown(String::from("settlement_on_the_moon")));
+        assert_eq!(transaction.escrow_status, Some(transaction::EscrowStatus::Unknown(String::from("escrow_in_orbit"))));
+    }
+
+    #[test]
+    fn errors_report_their_path() {
+        let root = elementtree::Element::from_reader(r#"<transaction><created-at type="date">2024-01-31</created-at></transaction>"#.as_bytes()).unwrap();
+        let node = Node::root(&root);
+        let err = node.child::<chrono::DateTime<chrono::Utc>>("created-at").unwrap_err();
+        assert_eq!(err.to_string(), "/transaction/created-at: expected type datetime, found date");
+        let err = node.child::<String>("id").unwrap_err();
+        assert_eq!(err.to_string(), "/transaction/id: missing element");
+    }
+
+    fn parse(xml: &str) -> elementtree::Element {
+        elementtree::Element::from_reader(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn nil_values() {
+        let root = parse(r#"<record><order-id nil="true"/><amount type="decimal" nil="true"></amount><id>abc</id></record>"#);
+        let node = Node::root(&root);
+        assert_eq!(node.child_opt::<String>("order-id").unwrap(), None);
+        assert_eq!(node.child_opt::<crate::Amount>("amount").unwrap(), None);
+        assert_eq!(node.child_opt::<String>("missing").unwrap(), None);
+        assert_eq!(node.child_opt::<String>("id").unwrap().as_deref(), Some("abc"));
+        let err = node.child::<String>("order-id").unwrap_err();
+        assert_eq!(err.to_string(), "/record/order-id: unexpected nil");
+    }
+
+    #[test]
+    fn arrays_and_collections() {
+        let root = parse(r#"<record>
+            <ids type="array"><item>a</item><item>b</item></ids>
+            <empty type="array"/>
+            <counts type="collection"><count type="integer">1</count><count type="integer">2</count></counts>
+            <scalar type="integer">3</scalar>
+        </record>"#);
+        let node = Node::root(&root);
+        assert_eq!(node.child::<Vec<String>>("ids").unwrap(), vec!["a", "b"]);
+        assert_eq!(node.child::<Vec<String>>("empty").unwrap(), Vec::<String>::new());
+        assert_eq!(node.child::<Vec<u32>>("counts").unwrap(), vec![1, 2]);
+        let err = node.child::<Vec<u32>>("scalar").unwrap_err();
+        assert_eq!(err.to_string(), "/record/scalar: expected type array, found integer");
+        let err = node.child::<String>("ids").unwrap_err();
+        assert_eq!(err.to_string(), "/record/ids: expected type string, found array");
+    }
+
+    #[test]
+    fn wrong_type_values() {
+        let root = parse(r#"<record>
+            <count type="integer">many</count>
+            <flag type="boolean">yes</flag>
+            <price type="decimal">free</price>
+            <label type="boolean">true</label>
+        </record>"#);
+        let node = Node::root(&root);
+        assert_eq!(node.child::<u32>("count").unwrap_err().to_string(), "/record/count: expected integer, found \"many\"");
+        assert_eq!(node.child::<bool>("flag").unwrap_err().to_string(), "/record/flag: expected boolean, found \"yes\"");
+        assert_eq!(node.child::<crate::Amount>("price").unwrap_err().to_string(), "/record/price: expected decimal, found \"free\"");
+        assert_eq!(node.child::<u32>("label").unwrap_err().to_string(), "/record/label: expected type integer, found boolean");
+    }
+}
@@ -1,7 +1,5 @@
-use std::fmt::Write;
-use xml;
 
-use crate::ToXml;
+use crate::encode::{ToXml, Writer};
 
 /// A record that describes what your customers will see on their statement
 /// when they make a purchase through your application.
@@ -17,14 +15,9 @@ pub struct Descriptor {
 }
 
 impl ToXml for Descriptor {
-    fn to_xml(&self, name: Option<&str>) -> String {
-        let name = xml::escape(&name.unwrap_or("descriptor"));
-        let mut s = String::new();
-        write!(s, "<{}>", name).unwrap();
-        write_xml!(s, "name", self.name);
-        write_xml!(s, "phone", self.phone);
-        write_xml!(s, "url", self.url);
-        write!(s, "</{}>", name).unwrap();
-        s
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("name", &self.name);
+        w.field("phone", &self.phone);
+        w.field("url", &self.url);
     }
 }
@@ -0,0 +1,230 @@
+//! Renders requests instead of sending them.
+//!
+//! A handle in dry-run mode builds every request as usual, passes it through
+//! the guardrails and middleware, and then fails with `Error::DryRun`
+//! instead of sending it. The error holds the request as it would have gone
+//! out, which makes the XML produced for a given input easy to inspect or
+//! snapshot.
+//!
+//! **Every call on a dry-run handle returns `Err`**, even though nothing
+//! went wrong, so `?` passes it up like any other failure, and code that
+//! treats every error as a failed payment will do so here too. Match on
+//! `Error::DryRun` wherever a dry run is expected:
+//!
+//! ```rust
+//! # use braintree::{test_values, Braintree, Error};
+//! # use braintree::transaction;
+//! let bt = Braintree::builder()
+//!     .merchant_id("my_merchant_id")
+//!     .public_key("<public_key>")
+//!     .private_key("<private_key>")
+//!     .dry_run(true)
+//!     .build()
+//!     .unwrap();
+//!
+//! let result = bt.transaction().create(transaction::Request{
+//!     amount: "10.00".parse().unwrap(),
+//!     payment_method_nonce: Some(test_values::nonces::VALID.into()),
+//!     ..Default::default()
+//! });
+//! let request = match result {
+//!     Err(Error::DryRun(request)) => request,
+//!     _ => unreachable!(),
+//! };
+//! assert_eq!(request.method, "POST");
+//! assert_eq!(request.path, "/merchants/my_merchant_id/transactions");
+//! assert_eq!(request.header("Authorization"), Some(braintree::dry_run::REDACTED));
+//! assert!(request.body.unwrap().contains(r#"<amount type="decimal">10.00</amount>"#));
+//! ```
+//!
+//! Operations that send several requests, such as searches, stop at the
+//! first one. The `body` field holds the body as is, including any card
+//! numbers or nonces, so that it can be compared exactly; the `Debug` and
+//! `Display` output mask them, as trace logging does, so that a prepared
+//! request can be logged.
+
+use std;
+
+use crate::trace::redact;
+use crate::transport::Request;
+
+/// What the `Authorization` header's value is replaced with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A request that a handle in dry-run mode would have sent.
+#[derive(Clone, PartialEq)]
+pub struct PreparedRequest {
+    /// The HTTP method, e.g. `"POST"`.
+    pub method: String,
+    /// The path and query string, e.g. `"/merchants/<merchant_id>/transactions"`.
+    pub path: String,
+    /// Every header, in the order it would have been sent, with the
+    /// `Authorization` header's value replaced by `REDACTED`.
+    pub headers: Vec<(String, String)>,
+    /// The XML body, if the request has one, unmasked.
+    pub body: Option<String>,
+}
+
+impl PreparedRequest {
+    pub(crate) fn new(request: &Request) -> PreparedRequest {
+        let path = match request.url.query() {
+            Some(query) => format!("{}?{}", request.url.path(), query),
+            None => String::from(request.url.path()),
+        };
+        let headers = request.headers.iter()
+            .map(|header| {
+                let value = if header.name().eq_ignore_ascii_case("authorization") {
+                    String::from(REDACTED)
+                } else {
+                    header.value_string()
+                };
+                (String::from(header.name()), value)
+            })
+            .collect();
+        PreparedRequest{
+            method: request.method.to_string(),
+            path,
+            headers,
+            body: request.body.as_ref().map(|body| String::from_utf8_lossy(body).into_owned()),
+        }
+    }
+
+    /// The value of the named header, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Masks card data and credentials in the body.
+impl std::fmt::Debug for PreparedRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PreparedRequest")
+            .field("method", &self.method)
+            .field("path", &self.path)
+            .field("headers", &self.headers)
+            .field("body", &self.body.as_deref().map(redact))
+            .finish()
+    }
+}
+
+/// Renders the request as it would appear on the wire, with card data and
+/// credentials in the body masked.
+impl std::fmt::Display for PreparedRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{} {}", self.method, self.path)?;
+        for (name, value) in &self.headers {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        if let Some(ref body) = self.body {
+            write!(f, "\n{}", redact(body))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{PreparedRequest, REDACTED};
+    use crate::error::Error;
+    use crate::metrics::{self, Metrics};
+    use crate::middleware::{BoxError, Middleware};
+    use crate::transport::Request;
+    use crate::Braintree;
+
+    fn request(url: &str, authorization: &str) -> Request {
+        let mut headers = hyper::header::Headers::new();
+        headers.set_raw(String::from(authorization), vec![b"Basic c2VjcmV0".to_vec()]);
+        headers.set_raw("X-ApiVersion", vec![b"6".to_vec()]);
+        Request{method: hyper::method::Method::Put, url: url.parse().unwrap(), headers, body: None}
+    }
+
+    fn client() -> crate::builder::BraintreeBuilder {
+        Braintree::builder()
+            .merchant_id("my_merchant_id")
+            .public_key("my_public_key")
+            .private_key("my_private_key")
+            .dry_run(true)
+    }
+
+    #[test]
+    fn redacts_authorization_in_any_case() {
+        for name in ["Authorization", "authorization", "AUTHORIZATION"] {
+            let prepared = PreparedRequest::new(&request("https://example.com/transactions/abc/void", name));
+            assert_eq!(prepared.header("Authorization"), Some(REDACTED));
+            assert_eq!(prepared.header("x-apiversion"), Some("6"));
+            assert!(!prepared.to_string().contains("c2VjcmV0"));
+        }
+    }
+
+    #[test]
+    fn keeps_the_query_string() {
+        let prepared = PreparedRequest::new(&request("https://example.com/subscriptions/abc/make_past_due?days_past_due=3", "Authorization"));
+        assert_eq!(prepared.method, "PUT");
+        assert_eq!(prepared.path, "/subscriptions/abc/make_past_due?days_past_due=3");
+        assert_eq!(prepared.body, None);
+    }
+
+    struct RequestId;
+
+    impl Middleware for RequestId {
+        fn on_request(&self, request: &mut Request) -> Result<(), BoxError> {
+            request.headers.set_raw("X-Request-Id", vec![b"req-1".to_vec()]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn includes_middleware_headers() {
+        let bt = client().middleware(RequestId).build().unwrap();
+        match bt.transaction().find(String::from("abc")) {
+            Err(Error::DryRun(request)) => {
+                assert_eq!(request.method, "GET");
+                assert_eq!(request.path, "/merchants/my_merchant_id/transactions/abc");
+                assert_eq!(request.header("X-Request-Id"), Some("req-1"));
+                assert_eq!(request.header("Authorization"), Some(REDACTED));
+            },
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        requests: Arc<Mutex<Vec<Option<u16>>>>,
+        outcomes: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Metrics for Recorder {
+        fn request(&self, request: &metrics::Request) {
+            self.requests.lock().unwrap().push(request.status);
+        }
+
+        fn operation(&self, operation: &metrics::Operation) {
+            assert!(operation.transaction_status.is_none());
+            self.outcomes.lock().unwrap().push(operation.outcome());
+        }
+    }
+
+    #[test]
+    fn reports_dry_runs_as_their_own_outcome() {
+        let recorder = Recorder::default();
+        let bt = client().metrics(recorder.clone()).build().unwrap();
+        assert!(matches!(bt.transaction().void(String::from("abc")), Err(Error::DryRun(_))));
+        assert_eq!(*recorder.outcomes.lock().unwrap(), ["dry_run"]);
+        assert!(recorder.requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn masks_card_data_when_printed() {
+        let mut request = request("https://example.com/merchants/my_merchant_id/transactions", "Authorization");
+        let body = "<transaction><amount>10.00</amount><credit-card><number>4111111111111111</number><cvv>123</cvv></credit-card></transaction>";
+        request.body = Some(body.as_bytes().to_vec());
+        let prepared = PreparedRequest::new(&request);
+        assert_eq!(prepared.body.as_deref(), Some(body));
+        for output in [prepared.to_string(), format!("{:?}", prepared)] {
+            assert!(!output.contains("4111111111111111") && !output.contains("123<"), "{}", output);
+            assert!(output.contains("1111</number>") && output.contains("10.00"), "{}", output);
+        }
+    }
+}
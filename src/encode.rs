@@ -0,0 +1,542 @@
+//! Encoding of request bodies as Braintree XML.
+//!
+//! Request types implement `ToXml` by describing their fields to a `Writer`,
+//! rather than building strings by hand. The writer takes care of escaping,
+//! annotates values with the `type` attribute Braintree expects (`boolean`,
+//! `integer`, `decimal`, `date`, `datetime` and `array`), and checks every
+//! element name: names from the API schema must be lowercase and dashed,
+//! while custom field keys only need to be valid XML names.
+
+use chrono;
+use std;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use xml;
+
+use crate::amount::Amount;
+
+/// A value that is written as the content of an element.
+pub(crate) trait ToXml {
+    fn write_xml(&self, w: &mut Writer);
+}
+
+/// A value that is written as the text of a single element.
+pub(crate) trait XmlValue {
+    /// The `type` attribute Braintree expects for the value, if any.
+    const TYPE: Option<&'static str>;
+    fn to_text(&self) -> String;
+}
+
+/// Encodes a value as a complete document with the given root element.
+pub(crate) fn to_xml<T: ToXml + ?Sized>(name: &str, value: &T) -> Result<String, EncodeError> {
+    let mut w = Writer{buf: String::new(), error: None};
+    w.element(name, value);
+    match w.error {
+        Some(err) => Err(err),
+        None => Ok(w.buf),
+    }
+}
+
+/// Encodes a scalar as a complete document, e.g. `<token>...</token>`.
+pub(crate) fn value_to_xml<T: XmlValue + ?Sized>(name: &str, value: &T) -> Result<String, EncodeError> {
+    let mut w = Writer{buf: String::new(), error: None};
+    w.value(name, value);
+    match w.error {
+        Some(err) => Err(err),
+        None => Ok(w.buf),
+    }
+}
+
+/// Builds a document one element at a time. The first invalid name is
+/// recorded and reported once the document is finished, so that `ToXml`
+/// impls can describe their fields without checking each one.
+pub(crate) struct Writer {
+    buf: String,
+    error: Option<EncodeError>,
+}
+
+impl Writer {
+    /// Writes an element whose content is produced by `f`.
+    pub fn nested<F: FnOnce(&mut Writer)>(&mut self, name: &str, f: F) {
+        if self.check(name, is_schema_name, EncodeError::InvalidName) {
+            self.raw_element(name, None, f);
+        }
+    }
+
+    /// Writes a required nested value.
+    pub fn element<T: ToXml + ?Sized>(&mut self, name: &str, value: &T) {
+        self.nested(name, |w| value.write_xml(w));
+    }
+
+    /// Writes a nested value if it is set.
+    pub fn optional_element<T: ToXml>(&mut self, name: &str, value: &Option<T>) {
+        if let Some(ref value) = *value {
+            self.element(name, value);
+        }
+    }
+
+    /// Writes a required scalar value.
+    pub fn value<T: XmlValue + ?Sized>(&mut self, name: &str, value: &T) {
+        if self.check(name, is_schema_name, EncodeError::InvalidName) {
+            self.text_element(name, T::TYPE, &value.to_text());
+        }
+    }
+
+    /// Writes a scalar value if it is set.
+    pub fn field<T: XmlValue>(&mut self, name: &str, value: &Option<T>) {
+        if let Some(ref value) = *value {
+            self.value(name, value);
+        }
+    }
+
+    /// Writes a `type="array"` element with one `item` element per value.
+    /// Nothing is written if there are no items.
+    pub fn array<T: ToXml>(&mut self, name: &str, item: &str, values: &[T]) {
+        if values.is_empty() || !self.check(name, is_schema_name, EncodeError::InvalidName) {
+            return;
+        }
+        self.raw_element(name, Some("array"), |w| {
+            for value in values {
+                w.element(item, value);
+            }
+        });
+    }
+
+    /// Writes a `type="array"` element of scalar values, each wrapped in an
+    /// `<item>` element. Nothing is written if there are no values.
+    pub fn values<T: XmlValue>(&mut self, name: &str, values: &[T]) {
+        if values.is_empty() || !self.check(name, is_schema_name, EncodeError::InvalidName) {
+            return;
+        }
+        self.raw_element(name, Some("array"), |w| {
+            for value in values {
+                w.value("item", value);
+            }
+        });
+    }
+
+    /// Writes a set of custom fields, keyed by their names in the Control
+    /// Panel. Nothing is written if there are no fields. Keys are written in
+    /// sorted order so that the output is deterministic.
+    pub fn custom_fields(&mut self, name: &str, fields: &HashMap<String, String>) {
+        if fields.is_empty() || !self.check(name, is_schema_name, EncodeError::InvalidName) {
+            return;
+        }
+        let mut keys: Vec<&String> = fields.keys().collect();
+        keys.sort();
+        self.raw_element(name, None, |w| {
+            for key in keys {
+                if w.check(key, is_xml_name, EncodeError::InvalidCustomField) {
+                    w.text_element(key, None, &fields[key]);
+                }
+            }
+        });
+    }
+
+    fn check(&mut self, name: &str, valid: fn(&str) -> bool, error: fn(String) -> EncodeError) -> bool {
+        if valid(name) {
+            return true;
+        }
+        if self.error.is_none() {
+            self.error = Some(error(String::from(name)));
+        }
+        false
+    }
+
+    fn raw_element<F: FnOnce(&mut Writer)>(&mut self, name: &str, typ: Option<&str>, f: F) {
+        self.start(name, typ);
+        f(self);
+        self.buf.push_str("</");
+        self.buf.push_str(name);
+        self.buf.push('>');
+    }
+
+    fn text_element(&mut self, name: &str, typ: Option<&str>, text: &str) {
+        self.raw_element(name, typ, |w| w.buf.push_str(&xml::escape(text)));
+    }
+
+    fn start(&mut self, name: &str, typ: Option<&str>) {
+        self.buf.push('<');
+        self.buf.push_str(name);
+        if let Some(typ) = typ {
+            self.buf.push_str(" type=\"");
+            self.buf.push_str(typ);
+            self.buf.push('"');
+        }
+        self.buf.push('>');
+    }
+}
+
+/// Whether `name` is a Braintree element name, e.g. `payment-method-nonce`.
+fn is_schema_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && !name.ends_with('-')
+        && !name.contains("--")
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Whether `name` can be used as an XML tag name. Only ASCII names are
+/// accepted, and names starting with `xml` are reserved.
+fn is_xml_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && !name.to_ascii_lowercase().starts_with("xml")
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// An error encoding a request body.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EncodeError {
+    /// An element name wasn't a lowercase, dashed name. This indicates a bug
+    /// in the library rather than in the request.
+    InvalidName(String),
+    /// A custom field key can't be used as an XML tag name.
+    InvalidCustomField(String),
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            EncodeError::InvalidName(ref name) => write!(f, "invalid element name: {:?}", name),
+            EncodeError::InvalidCustomField(ref key) => write!(f, "invalid custom field key: {:?}", key),
+        }
+    }
+}
+
+impl StdError for EncodeError {}
+
+impl XmlValue for String {
+    const TYPE: Option<&'static str> = None;
+    fn to_text(&self) -> String { self.clone() }
+}
+
+impl XmlValue for str {
+    const TYPE: Option<&'static str> = None;
+    fn to_text(&self) -> String { String::from(self) }
+}
+
+impl XmlValue for bool {
+    const TYPE: Option<&'static str> = Some("boolean");
+    fn to_text(&self) -> String { self.to_string() }
+}
+
+macro_rules! xml_value_integer {
+    ($($t:ty),*) => {
+        $(
+            impl XmlValue for $t {
+                const TYPE: Option<&'static str> = Some("integer");
+                fn to_text(&self) -> String { self.to_string() }
+            }
+        )*
+    }
+}
+
+xml_value_integer!(u8, u16, u32, u64, i32, i64, usize);
+
+impl XmlValue for Amount {
+    const TYPE: Option<&'static str> = Some("decimal");
+    fn to_text(&self) -> String { self.to_string() }
+}
+
+impl XmlValue for chrono::DateTime<chrono::Utc> {
+    const TYPE: Option<&'static str> = Some("datetime");
+    fn to_text(&self) -> String { self.to_rfc3339_opts(chrono::SecondsFormat::Secs, true) }
+}
+
+impl XmlValue for chrono::NaiveDate {
+    const TYPE: Option<&'static str> = Some("date");
+    fn to_text(&self) -> String { self.format(crate::DATE_FORMAT).to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono;
+    use elementtree;
+    use std::collections::HashMap;
+
+    use crate::amount::Amount;
+    use crate::decode::{FromXml, Node};
+    use crate::secret::Secret;
+    use crate::{address, client_token, credit_card, credit_card_verification, customer, descriptor, line_item,
+                merchant_account, oauth, search, settlement_batch_summary, subscription, transaction};
+    use super::{to_xml, value_to_xml, EncodeError, ToXml};
+
+    /// Encodes a request and parses it back, so that the tests below can
+    /// check it with the same decoder used for responses.
+    fn round_trip<T: ToXml>(name: &str, value: &T) -> elementtree::Element {
+        let xml = to_xml(name, value).unwrap();
+        let root = elementtree::Element::from_reader(xml.as_bytes()).unwrap();
+        assert_eq!(root.tag().name(), name);
+        root
+    }
+
+    fn at<'a>(root: &'a elementtree::Element, path: &[&str]) -> Node<'a> {
+        path.iter().fold(Node::root(root), |node, tag| node.find(tag).unwrap_or_else(|| panic!("{}/{} missing", node.path(), tag)))
+    }
+
+    fn get<T: FromXml>(root: &elementtree::Element, path: &[&str]) -> T {
+        T::from_xml(&at(root, path)).unwrap()
+    }
+
+    fn card() -> credit_card::CreditCard {
+        credit_card::CreditCard{
+            cardholder_name: Some(String::from("Jane <Doe> & Co")),
+            number: Some(Secret::from("4111111111111111")),
+            expiration_date: Some(String::from("10/30")),
+            billing_address: Some(address::Address{postal_code: Some(String::from("60622")), ..Default::default()}),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn transaction_request() {
+        let mut custom_fields = HashMap::new();
+        custom_fields.insert(String::from("store_me"), String::from("a < b"));
+        let req = transaction::Request{
+            amount: "10.50".parse().unwrap(),
+            order_id: Some(String::from("order-1")),
+            billing: Some(address::Address{first_name: Some(String::from("Jane")), ..Default::default()}),
+            credit_card: Some(card()),
+            custom_fields,
+            customer: Some(customer::Customer{email: Some(String::from("jane@example.com")), ..Default::default()}),
+            descriptor: Some(descriptor::Descriptor{name: Some(String::from("ACME*WIDGET")), ..Default::default()}),
+            line_items: vec![line_item::LineItem{
+                quantity: Some("2".parse().unwrap()),
+                name: Some(String::from("Widget")),
+                kind: Some(line_item::Kind::Debit),
+                unit_amount: Some("5.25".parse().unwrap()),
+                total_amount: Some("10.50".parse().unwrap()),
+                ..Default::default()
+            }],
+            merchant_account_id: Some(String::from("seller")),
+            options: Some(transaction::Options{submit_for_settlement: Some(true), ..Default::default()}),
+            recurring: Some(false),
+            service_fee_amount: Some("1.00".parse().unwrap()),
+            shipping_amount: Some("2.00".parse().unwrap()),
+            ..Default::default()
+        };
+        let root = round_trip("transaction", &req);
+        assert_eq!(get::<String>(&root, &["type"]), "sale");
+        assert_eq!(get::<Amount>(&root, &["amount"]), req.amount);
+        assert_eq!(get::<String>(&root, &["order-id"]), "order-1");
+        assert_eq!(get::<String>(&root, &["billing", "first-name"]), "Jane");
+        assert_eq!(get::<String>(&root, &["credit-card", "cardholder-name"]), "Jane <Doe> & Co");
+        assert_eq!(get::<String>(&root, &["credit-card", "billing-address", "postal-code"]), "60622");
+        assert_eq!(get::<HashMap<String, String>>(&root, &["custom-fields"]), req.custom_fields);
+        assert_eq!(get::<String>(&root, &["customer", "email"]), "jane@example.com");
+        assert_eq!(get::<String>(&root, &["descriptor", "name"]), "ACME*WIDGET");
+        let line_items: Vec<line_item::LineItem> = get(&root, &["line-items"]);
+        assert_eq!(line_items.len(), 1);
+        assert_eq!(line_items[0].name.as_ref().unwrap(), "Widget");
+        assert_eq!(line_items[0].total_amount, req.line_items[0].total_amount);
+        assert_eq!(get::<String>(&root, &["merchant-account-id"]), "seller");
+        assert!(get::<bool>(&root, &["options", "submit-for-settlement"]));
+        assert!(!get::<bool>(&root, &["recurring"]));
+        assert_eq!(get::<Amount>(&root, &["service-fee-amount"]), req.service_fee_amount.unwrap());
+        assert_eq!(get::<Amount>(&root, &["shipping-amount"]), req.shipping_amount.unwrap());
+        assert!(root.find("tax-amount").is_none());
+    }
+
+    #[test]
+    fn refund_request() {
+        let root = round_trip("transaction", &transaction::RefundRequest{amount: Some("3.00".parse().unwrap())});
+        assert_eq!(get::<Amount>(&root, &["amount"]), "3.00".parse().unwrap());
+        let root = round_trip("transaction", &transaction::RefundRequest{amount: None});
+        assert_eq!(root.child_count(), 0);
+    }
+
+    #[test]
+    fn client_token_request() {
+        let req = client_token::Request{
+            customer_id: Some(String::from("customer-1")),
+            options: Some(client_token::Options{verify_card: Some(true), ..Default::default()}),
+            ..Default::default()
+        };
+        let root = round_trip("client-token", &req);
+        assert_eq!(get::<u8>(&root, &["version"]), 2);
+        assert_eq!(get::<String>(&root, &["customer-id"]), "customer-1");
+        assert!(get::<bool>(&root, &["options", "verify-card"]));
+        assert!(at(&root, &["options"]).find("make-default").is_none());
+    }
+
+    #[test]
+    fn customer_request() {
+        let mut custom_fields = HashMap::new();
+        custom_fields.insert(String::from("loyalty_tier"), String::from("gold"));
+        let req = customer::Customer{
+            first_name: Some(String::from("Jane")),
+            payment_method_nonce: Some(Secret::from("fake-valid-nonce")),
+            credit_card: Some(card()),
+            custom_fields: Some(custom_fields),
+            ..Default::default()
+        };
+        let root = round_trip("customer", &req);
+        assert_eq!(get::<String>(&root, &["first-name"]), "Jane");
+        assert_eq!(get::<String>(&root, &["payment-method-nonce"]), "fake-valid-nonce");
+        assert_eq!(get::<String>(&root, &["credit-card", "number"]), "4111111111111111");
+        assert_eq!(get::<String>(&root, &["custom-fields", "loyalty_tier"]), "gold");
+    }
+
+    #[test]
+    fn credit_card_verification_request() {
+        let req = credit_card_verification::Request{
+            credit_card: card(),
+            options: Some(credit_card_verification::Options{
+                merchant_account_id: Some(String::from("seller")),
+                amount: Some("1.00".parse().unwrap()),
+            }),
+        };
+        let root = round_trip("verification", &req);
+        assert_eq!(get::<String>(&root, &["credit-card", "expiration-date"]), "10/30");
+        assert_eq!(get::<String>(&root, &["options", "merchant-account-id"]), "seller");
+        assert_eq!(get::<Amount>(&root, &["options", "amount"]), "1.00".parse().unwrap());
+    }
+
+    #[test]
+    fn credit_card_verification_search() {
+        let created_at = chrono::DateTime::parse_from_rfc3339("2024-01-31T17:45:00Z").unwrap().with_timezone(&chrono::Utc);
+        let req = credit_card_verification::Search{
+            id: Some(search::Text::StartsWith(String::from("abc"))),
+            status: vec![credit_card_verification::Status::Verified, credit_card_verification::Status::Failed],
+            created_at: Some(search::Range{min: Some(created_at), max: None}),
+            credit_card_card_type: vec![credit_card::CardType::Visa],
+            customer_email: Some(search::Text::IsNot(String::from("jane@example.com"))),
+            ..Default::default()
+        };
+        let root = round_trip("search", &req);
+        assert_eq!(get::<String>(&root, &["id", "starts-with"]), "abc");
+        assert_eq!(get::<Vec<String>>(&root, &["status"]), vec!["verified", "failed"]);
+        assert_eq!(get::<chrono::DateTime<chrono::Utc>>(&root, &["created-at", "min"]), created_at);
+        assert!(at(&root, &["created-at"]).find("max").is_none());
+        assert_eq!(get::<Vec<String>>(&root, &["credit-card-card-type"]), vec!["Visa"]);
+        assert_eq!(get::<String>(&root, &["customer-email", "is-not"]), "jane@example.com");
+        assert!(root.find("credit-card-number").is_none());
+    }
+
+    #[test]
+    fn transaction_search() {
+        let req = transaction::Search{
+            order_id: Some(search::Text::Is(String::from("order-1"))),
+            status: vec![transaction::Status::Settled],
+            amount: Some(search::Range{min: Some("10.00".parse().unwrap()), max: Some("20.00".parse().unwrap())}),
+            merchant_account_id: vec![String::from("seller")],
+            ..Default::default()
+        };
+        let root = round_trip("search", &req);
+        assert_eq!(get::<String>(&root, &["order-id", "is"]), "order-1");
+        assert_eq!(get::<Vec<String>>(&root, &["status"]), vec!["settled"]);
+        assert_eq!(get::<Amount>(&root, &["amount", "max"]), "20.00".parse().unwrap());
+        assert_eq!(get::<Vec<String>>(&root, &["merchant-account-id"]), vec!["seller"]);
+        assert!(root.find("type").is_none());
+    }
+
+    #[test]
+    fn search_ids() {
+        let ids = vec![String::from("a"), String::from("b")];
+        let root = round_trip("search", &search::Ids(&ids));
+        assert_eq!(get::<Vec<String>>(&root, &["ids"]), ids);
+    }
+
+    #[test]
+    fn merchant_account_request() {
+        let req = merchant_account::Request{
+            id: Some(String::from("seller")),
+            master_merchant_account_id: Some(String::from("marketplace")),
+            tos_accepted: Some(true),
+            individual: Some(merchant_account::Individual{
+                first_name: Some(String::from("Jane")),
+                date_of_birth: Some(chrono::NaiveDate::from_ymd_opt(1980, 1, 31).unwrap()),
+                ssn: Some(Secret::from("123-45-6789")),
+                address: Some(merchant_account::Address{locality: Some(String::from("Chicago")), ..Default::default()}),
+                ..Default::default()
+            }),
+            business: Some(merchant_account::Business{legal_name: Some(String::from("Jane's Widgets")), ..Default::default()}),
+            funding: Some(merchant_account::Funding{
+                destination: Some(merchant_account::FundingDestination::Bank),
+                account_number: Some(Secret::from("1123581321")),
+                routing_number: Some(Secret::from("071101307")),
+                ..Default::default()
+            }),
+        };
+        let root = round_trip("merchant-account", &req);
+        assert_eq!(get::<String>(&root, &["master-merchant-account-id"]), "marketplace");
+        assert!(get::<bool>(&root, &["tos-accepted"]));
+        assert_eq!(get::<chrono::NaiveDate>(&root, &["individual", "date-of-birth"]), req.individual.as_ref().unwrap().date_of_birth.unwrap());
+        assert_eq!(get::<String>(&root, &["individual", "address", "locality"]), "Chicago");
+        assert_eq!(get::<String>(&root, &["business", "legal-name"]), "Jane's Widgets");
+        assert_eq!(get::<merchant_account::FundingDestination>(&root, &["funding", "destination"]), merchant_account::FundingDestination::Bank);
+        assert_eq!(get::<String>(&root, &["individual", "ssn"]), "123-45-6789");
+        assert_eq!(get::<String>(&root, &["funding", "account-number"]), "1123581321");
+        assert_eq!(get::<String>(&root, &["funding", "routing-number"]), "071101307");
+        let debug = format!("{:?}", req);
+        assert!(!debug.contains("123-45-6789") && !debug.contains("1123581321") && !debug.contains("071101307"), "{}", debug);
+    }
+
+    #[test]
+    fn merchant_account_currency_request() {
+        let req = merchant_account::CurrencyRequest{currency: String::from("EUR"), id: None};
+        let root = round_trip("merchant-account", &req);
+        assert_eq!(get::<String>(&root, &["currency"]), "EUR");
+        assert!(root.find("id").is_none());
+    }
+
+    #[test]
+    fn settlement_batch_summary_request() {
+        let req = settlement_batch_summary::Request{
+            settlement_date: chrono::NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+            group_by_custom_field: Some(String::from("store_me")),
+        };
+        let root = round_trip("settlement-batch-summary", &req);
+        assert_eq!(get::<chrono::NaiveDate>(&root, &["settlement-date"]), req.settlement_date);
+        assert_eq!(get::<String>(&root, &["group-by-custom-field"]), "store_me");
+    }
+
+    #[test]
+    fn subscription_request() {
+        let req = subscription::Request{
+            plan_id: Some(String::from("monthly")),
+            payment_method_token: Some(String::from("token")),
+            price: Some("9.99".parse().unwrap()),
+            ..Default::default()
+        };
+        let root = round_trip("subscription", &req);
+        assert_eq!(get::<String>(&root, &["plan-id"]), "monthly");
+        assert_eq!(get::<Amount>(&root, &["price"]), req.price.unwrap());
+        assert!(get::<bool>(&root, &["options", "start-immediately"]));
+
+        let first_billing_date = chrono::NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let root = round_trip("subscription", &subscription::Request{first_billing_date: Some(first_billing_date), ..req});
+        assert_eq!(get::<chrono::NaiveDate>(&root, &["first-billing-date"]), first_billing_date);
+        assert!(root.find("options").is_none());
+    }
+
+    #[test]
+    fn oauth_token_requests() {
+        let root = round_trip("credentials", &oauth::TokenRequest::Code{code: "code-1", scope: Some("read_write")});
+        assert_eq!(get::<String>(&root, &["grant-type"]), "authorization_code");
+        assert_eq!(get::<String>(&root, &["code"]), "code-1");
+        assert_eq!(get::<String>(&root, &["scope"]), "read_write");
+
+        let root = round_trip("credentials", &oauth::TokenRequest::RefreshToken{refresh_token: "refresh-1", scope: None});
+        assert_eq!(get::<String>(&root, &["grant-type"]), "refresh_token");
+        assert_eq!(get::<String>(&root, &["refresh-token"]), "refresh-1");
+        assert!(root.find("scope").is_none());
+
+        assert_eq!(value_to_xml("token", "a&b").unwrap(), "<token>a&amp;b</token>");
+    }
+
+    #[test]
+    fn rejects_invalid_custom_field_keys() {
+        for key in &["", "has space", "1st", "<tag>", "xml-field"] {
+            let mut custom_fields = HashMap::new();
+            custom_fields.insert(String::from(*key), String::from("value"));
+            let req = transaction::Request{custom_fields, ..Default::default()};
+            assert_eq!(to_xml("transaction", &req), Err(EncodeError::InvalidCustomField(String::from(*key))));
+        }
+    }
+
+    #[test]
+    fn rejects_undashed_names() {
+        assert_eq!(to_xml("payment_method_nonce", &transaction::RefundRequest::default()), Err(EncodeError::InvalidName(String::from("payment_method_nonce"))));
+        assert_eq!(to_xml("Transaction", &transaction::RefundRequest::default()), Err(EncodeError::InvalidName(String::from("Transaction"))));
+    }
+}
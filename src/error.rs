@@ -1,4 +1,11 @@
 use elementtree;
+use crate::amount;
+use crate::dry_run::PreparedRequest;
+use crate::decode::{DecodeError, DecodeErrorKind, Node};
+use crate::encode::EncodeError;
+use crate::guardrails::Violation;
+use crate::middleware::BoxError;
+use crate::transaction::Transaction;
 use hyper;
 use std;
 use std::error::Error as StdError;
@@ -21,23 +28,63 @@ pub enum Error {
     /// A test operation, such as forcing a transaction into a settlement
     /// status, was attempted in a production environment.
     TestOperationInProduction,
-    ///
-    /// Error in setting up api
-    Setup
+    /// An amount was malformed, negative or more precise than its currency
+    /// allows.
+    InvalidAmount(amount::ParseAmountError),
+    /// A webhook notification's signature did not match its payload, or the
+    /// payload could not be decoded.
+    InvalidSignature,
+    /// A response from Braintree didn't match the expected structure, e.g. a
+    /// required element was missing or a value had the wrong type.
+    Decode(DecodeError),
+    /// A request couldn't be encoded, e.g. because a custom field key isn't
+    /// a valid XML tag name.
+    Encode(EncodeError),
+    /// The client couldn't be set up, e.g. because a required setting was
+    /// missing or the TLS client couldn't be created, or its credentials
+    /// can't be used for the request, e.g. OAuth client credentials for a
+    /// merchant's transactions.
+    Setup(String),
+    /// A middleware rejected the request or its response.
+    Middleware(BoxError),
+    /// The request broke one of the handle's guardrails, and wasn't sent.
+    Guardrail(Violation),
+    /// The handle is in dry-run mode, and this request was rendered instead
+    /// of being sent. This isn't a failure: every call on a dry-run handle
+    /// returns it. Operations that send several requests, such as searches,
+    /// stop after the first one.
+    DryRun(PreparedRequest),
 }
 
 impl std::fmt::Display for Error {
-        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-            f.write_str(&self.to_string())
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Error::Api(ref err) => f.write_str(&err.message),
+            Error::Http(ref err) => write!(f, "{}", err),
+            Error::TestOperationInProduction => f.write_str("test operation attempted in production"),
+            Error::InvalidAmount(ref err) => write!(f, "invalid amount: {}", err),
+            Error::InvalidSignature => f.write_str("webhook signature could not be verified"),
+            Error::Decode(ref err) => write!(f, "unexpected response: {}", err),
+            Error::Encode(ref err) => write!(f, "invalid request: {}", err),
+            Error::Setup(ref message) => write!(f, "error setting up the client: {}", message),
+            Error::Middleware(ref err) => write!(f, "rejected by middleware: {}", err),
+            Error::Guardrail(ref violation) => write!(f, "blocked by guardrail: {}", violation),
+            Error::DryRun(ref request) => write!(f, "dry run: {} {} not sent", request.method, request.path),
         }
+    }
 }
 
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        if let Error::Http(ref err) = *self {
-            return Some(err)
+        match *self {
+            Error::Http(ref err) => Some(err),
+            Error::InvalidAmount(ref err) => Some(err),
+            Error::Decode(ref err) => Some(err),
+            Error::Encode(ref err) => Some(err),
+            Error::Middleware(ref err) => Some(err.as_ref()),
+            Error::Guardrail(ref violation) => Some(violation),
+            _ => None,
         }
-        None
     }
 }
 
@@ -47,13 +94,40 @@ impl From<hyper::Error> for Error {
     }
 }
 
+impl From<amount::ParseAmountError> for Error {
+    fn from(error: amount::ParseAmountError) -> Error {
+        Error::InvalidAmount(error)
+    }
+}
+
+impl From<Violation> for Error {
+    fn from(violation: Violation) -> Error {
+        Error::Guardrail(violation)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Error {
+        Error::Decode(error)
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(error: EncodeError) -> Error {
+        Error::Encode(error)
+    }
+}
+
 impl std::convert::From<Box<dyn std::io::Read>> for Error {
     fn from(xml: Box<dyn std::io::Read>) -> Error {
-        let root = elementtree::Element::from_reader(xml).unwrap();
-        Error::Api(ApiErrorResponse{
-            message: String::from(root.find("message").unwrap().text()),
-            raw: root,
-        })
+        let root = match elementtree::Element::from_reader(xml) {
+            Ok(root) => root,
+            Err(err) => return Error::Decode(DecodeError{path: String::from("/"), kind: DecodeErrorKind::Malformed(err.to_string())}),
+        };
+        match Node::root(&root).child("message") {
+            Ok(message) => Error::Api(ApiErrorResponse{message, raw: root}),
+            Err(err) => Error::Decode(err),
+        }
     }
 }
 
@@ -64,3 +138,11 @@ pub struct ApiErrorResponse {
     /// The parsed response body returned by the API.
     pub raw: elementtree::Element,
 }
+
+impl ApiErrorResponse {
+    /// The transaction that failed, e.g. one that was declined by the
+    /// processor or rejected by the gateway, if the response includes one.
+    pub fn transaction(&self) -> Option<Transaction> {
+        Node::root(&self.raw).child_opt("transaction").ok()?
+    }
+}
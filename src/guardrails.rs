@@ -0,0 +1,339 @@
+//! Checks that refuse risky requests before they're sent.
+//!
+//! Guardrails are off by default. Once set, every request is checked against
+//! them, and a request that breaks one fails with `Error::Guardrail` without
+//! reaching Braintree:
+//!
+//! ```rust
+//! # use braintree::{Braintree, Environment, Error};
+//! # use braintree::guardrails::{Guardrails, Violation};
+//! # use braintree::transaction;
+//! let bt = Braintree::builder()
+//!     .environment(Environment::Production)
+//!     .merchant_id("<merchant_id>")
+//!     .public_key("<public_key>")
+//!     .private_key("<private_key>")
+//!     .guardrails(Guardrails{
+//!         max_amount: Some("500.00".parse().unwrap()),
+//!         merchant_account_ids: Some(vec![String::from("store_usd")]),
+//!         ..Default::default()
+//!     })
+//!     .build()
+//!     .unwrap();
+//!
+//! let result = bt.transaction().create(transaction::Request{
+//!     amount: "1000.00".parse().unwrap(),
+//!     ..Default::default()
+//! });
+//! match result {
+//!     Err(Error::Guardrail(Violation::AmountTooLarge{..})) => {},
+//!     _ => unreachable!(),
+//! }
+//! ```
+
+use std;
+use std::error::Error as StdError;
+
+use crate::amount::Amount;
+use crate::credit_card::CreditCard;
+use crate::secret::Secret;
+use crate::test_values;
+use crate::Environment;
+
+/// The limits to enforce. Every limit is off unless set, except for
+/// `reject_test_values`.
+#[derive(Clone, Debug)]
+pub struct Guardrails {
+    /// The largest amount a transaction, refund, verification or
+    /// subscription may be for.
+    pub max_amount: Option<Amount>,
+    /// The merchant accounts that requests may name. Requests that don't
+    /// name one use the default merchant account, and are allowed. As a
+    /// merchant account settles in a single currency, this also pins the
+    /// currency of transactions.
+    pub merchant_account_ids: Option<Vec<String>>,
+    /// The ISO 4217 codes, e.g. `"USD"`, of the currencies that merchant
+    /// accounts may be created for and transactions may be made in. As a
+    /// transaction's currency is that of its merchant account, creating one
+    /// first looks the merchant account up, which costs an extra request.
+    pub currencies: Option<Vec<String>>,
+    /// Refuse to send the sandbox's test card numbers and fake nonces, such
+    /// as those in `test_values`, to production. On by default.
+    pub reject_test_values: bool,
+    /// Refuse every request that changes something. Lookups, searches,
+    /// settlement batch summaries and client tokens are still allowed. The
+    /// check is made after middleware has run, so a `POST` whose URL was
+    /// rewritten by middleware is refused too.
+    pub read_only: bool,
+}
+
+impl Default for Guardrails {
+    fn default() -> Guardrails {
+        Guardrails{
+            max_amount: None,
+            merchant_account_ids: None,
+            currencies: None,
+            reject_test_values: true,
+            read_only: false,
+        }
+    }
+}
+
+/// The guardrail that a request broke.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// A request that changes something was attempted in read-only mode.
+    /// Holds the request's method and path template, e.g. `"POST"` and
+    /// `"transactions"`.
+    ReadOnly{method: String, path: String},
+    /// An amount was larger than `max_amount`.
+    AmountTooLarge{amount: Amount, max: Amount},
+    /// A merchant account that isn't in `merchant_account_ids` was named.
+    MerchantAccountNotAllowed(String),
+    /// A currency that isn't in `currencies` was named.
+    CurrencyNotAllowed(String),
+    /// The currency of a transaction's merchant account couldn't be found,
+    /// so it couldn't be checked against `currencies`. Holds the merchant
+    /// account ID, or `None` for the default merchant account.
+    UnknownCurrency(Option<String>),
+    /// A sandbox test card number or nonce was about to be sent to
+    /// production.
+    TestValueInProduction,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Violation::ReadOnly{ref method, ref path} => write!(f, "{} {} is not allowed in read-only mode", method, path),
+            Violation::AmountTooLarge{ref amount, ref max} => write!(f, "amount {} is larger than the maximum of {}", amount, max),
+            Violation::MerchantAccountNotAllowed(ref id) => write!(f, "merchant account {:?} is not allowed", id),
+            Violation::CurrencyNotAllowed(ref currency) => write!(f, "currency {:?} is not allowed", currency),
+            Violation::UnknownCurrency(Some(ref id)) => write!(f, "currency of merchant account {:?} is unknown", id),
+            Violation::UnknownCurrency(None) => f.write_str("currency of the default merchant account is unknown"),
+            Violation::TestValueInProduction => f.write_str("test card number or nonce sent to production"),
+        }
+    }
+}
+
+impl StdError for Violation {}
+
+/// The request paths that only read, although they're `POST`s.
+const READS: &[&str] = &[
+    "client_token",
+    "settlement_batch_summary",
+    "{resource}/advanced_search_ids",
+    "{resource}/advanced_search",
+];
+
+impl Guardrails {
+    pub(crate) fn request(&self, method: &hyper::method::Method, template: &str) -> Result<(), Violation> {
+        if !self.read_only || *method == hyper::method::Method::Get || READS.contains(&template) {
+            return Ok(());
+        }
+        Err(Violation::ReadOnly{method: method.to_string(), path: String::from(template)})
+    }
+
+    pub(crate) fn amount(&self, amount: Option<&Amount>) -> Result<(), Violation> {
+        match (amount, self.max_amount) {
+            (Some(amount), Some(max)) if *amount > max => Err(Violation::AmountTooLarge{amount: *amount, max}),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn merchant_account(&self, id: Option<&String>) -> Result<(), Violation> {
+        match (id, &self.merchant_account_ids) {
+            (Some(id), Some(allowed)) if !allowed.contains(id) => Err(Violation::MerchantAccountNotAllowed(id.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn currency(&self, currency: &str) -> Result<(), Violation> {
+        match self.currencies {
+            Some(ref allowed) if !allowed.iter().any(|c| c.eq_ignore_ascii_case(currency)) => Err(Violation::CurrencyNotAllowed(String::from(currency))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the card number and nonce of a payment method.
+    pub(crate) fn payment_method(&self, env: Environment, card: Option<&CreditCard>, nonce: Option<&Secret>) -> Result<(), Violation> {
+        if !self.reject_test_values || env != Environment::Production {
+            return Ok(());
+        }
+        let number = card.and_then(|card| card.number.as_ref())
+            .map(|number| zeroize::Zeroizing::new(number.expose().chars().filter(char::is_ascii_digit).collect::<String>()));
+        if number.is_some_and(|number| test_values::card_numbers::ALL.contains(&number.as_str()))
+            || nonce.is_some_and(|nonce| nonce.expose().starts_with("fake-"))
+        {
+            return Err(Violation::TestValueInProduction);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::method::Method;
+
+    use super::*;
+    use crate::test_values::{card_numbers, nonces};
+
+    #[test]
+    fn read_only_allows_reads() {
+        let guardrails = Guardrails{read_only: true, ..Default::default()};
+        assert!(guardrails.request(&Method::Get, "transactions/{id}").is_ok());
+        assert!(guardrails.request(&Method::Post, "{resource}/advanced_search_ids").is_ok());
+        assert!(guardrails.request(&Method::Post, "{resource}/advanced_search").is_ok());
+        assert!(guardrails.request(&Method::Post, "client_token").is_ok());
+        assert!(guardrails.request(&Method::Post, "settlement_batch_summary").is_ok());
+        assert_eq!(guardrails.request(&Method::Post, "transactions"),
+                   Err(Violation::ReadOnly{method: String::from("POST"), path: String::from("transactions")}));
+        assert!(guardrails.request(&Method::Put, "transactions/{id}/void").is_err());
+        assert!(Guardrails::default().request(&Method::Put, "transactions/{id}/void").is_ok());
+    }
+
+    #[test]
+    fn allows_listed_merchant_accounts() {
+        let guardrails = Guardrails{merchant_account_ids: Some(vec![String::from("store_usd")]), ..Default::default()};
+        assert!(guardrails.merchant_account(None).is_ok());
+        assert!(guardrails.merchant_account(Some(&String::from("store_usd"))).is_ok());
+        assert_eq!(guardrails.merchant_account(Some(&String::from("store_eur"))),
+                   Err(Violation::MerchantAccountNotAllowed(String::from("store_eur"))));
+        assert!(Guardrails::default().merchant_account(Some(&String::from("store_eur"))).is_ok());
+    }
+
+    #[test]
+    fn allows_listed_currencies_ignoring_case() {
+        let guardrails = Guardrails{currencies: Some(vec![String::from("USD")]), ..Default::default()};
+        assert!(guardrails.currency("USD").is_ok());
+        assert!(guardrails.currency("usd").is_ok());
+        assert_eq!(guardrails.currency("eur"), Err(Violation::CurrencyNotAllowed(String::from("eur"))));
+        assert!(Guardrails::default().currency("EUR").is_ok());
+    }
+
+    #[test]
+    fn rejects_test_values_in_production() {
+        let guardrails = Guardrails::default();
+        let card = CreditCard{number: Some(Secret::from(card_numbers::VISA)), ..Default::default()};
+        let nonce = Secret::from(nonces::VALID);
+        let real_card = CreditCard{number: Some(Secret::from("4242424242424241")), ..Default::default()};
+        let real_nonce = Secret::from("tokencc_bf_abc123");
+
+        assert_eq!(guardrails.payment_method(Environment::Production, Some(&card), None), Err(Violation::TestValueInProduction));
+        assert_eq!(guardrails.payment_method(Environment::Production, None, Some(&nonce)), Err(Violation::TestValueInProduction));
+        assert!(guardrails.payment_method(Environment::Production, Some(&real_card), Some(&real_nonce)).is_ok());
+        for number in ["4111 1111 1111 1111", "4111-1111-1111-1111", " 4111111111111111 "] {
+            let card = CreditCard{number: Some(Secret::from(number)), ..Default::default()};
+            assert_eq!(guardrails.payment_method(Environment::Production, Some(&card), None), Err(Violation::TestValueInProduction));
+        }
+        assert!(guardrails.payment_method(Environment::Sandbox, Some(&card), Some(&nonce)).is_ok());
+
+        let guardrails = Guardrails{reject_test_values: false, ..Default::default()};
+        assert!(guardrails.payment_method(Environment::Production, Some(&card), Some(&nonce)).is_ok());
+    }
+
+    #[test]
+    fn compares_amounts_across_exponents() {
+        let max: Amount = "100.00".parse().unwrap();
+        let guardrails = Guardrails{max_amount: Some(max), ..Default::default()};
+        assert!(guardrails.amount(Some(&"100.00".parse().unwrap())).is_ok());
+        assert!(guardrails.amount(Some(&Amount::parse_for_currency("100", "JPY").unwrap())).is_ok());
+        assert!(guardrails.amount(Some(&Amount::parse_for_currency("101", "JPY").unwrap())).is_err());
+        assert!(guardrails.amount(Some(&Amount::parse_for_currency("100.001", "BHD").unwrap())).is_err());
+    }
+
+    /// Rewrites the URL or method of every request.
+    struct Rewrite(Option<Method>);
+
+    impl crate::middleware::Middleware for Rewrite {
+        fn on_request(&self, request: &mut crate::transport::Request) -> Result<(), crate::middleware::BoxError> {
+            match self.0 {
+                Some(ref method) => request.method = method.clone(),
+                None => request.url.set_path("/merchants/my_merchant_id/transactions"),
+            }
+            Ok(())
+        }
+    }
+
+    fn client(guardrails: Guardrails) -> crate::builder::BraintreeBuilder {
+        crate::Braintree::builder()
+            .merchant_id("my_merchant_id")
+            .public_key("my_public_key")
+            .private_key("my_private_key")
+            .guardrails(guardrails)
+    }
+
+    #[test]
+    fn read_only_checks_requests_after_middleware() {
+        use crate::error::Error;
+
+        let read_only = || Guardrails{read_only: true, ..Default::default()};
+        let bt = client(read_only()).dry_run(true).build().unwrap();
+        assert!(matches!(bt.transaction().search(Default::default()), Err(Error::DryRun(_))));
+
+        let bt = client(read_only()).dry_run(true).middleware(Rewrite(None)).build().unwrap();
+        match bt.transaction().search(Default::default()) {
+            Err(Error::Guardrail(Violation::ReadOnly{method, path})) => {
+                assert_eq!((method.as_str(), path.as_str()), ("POST", "/merchants/my_merchant_id/transactions"));
+            },
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        let bt = client(read_only()).dry_run(true).middleware(Rewrite(Some(Method::Delete))).build().unwrap();
+        match bt.transaction().find(String::from("abc")) {
+            Err(Error::Guardrail(Violation::ReadOnly{method, path})) => {
+                assert_eq!((method.as_str(), path.as_str()), ("DELETE", "transactions/{id}"));
+            },
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn checks_the_currency_of_transactions() {
+        use hyper::status::StatusCode;
+
+        use crate::error::Error;
+        use crate::transaction;
+        use crate::transport::Stub;
+
+        let stub = Stub::new(|request| {
+            let account = |id: &str, currency: &str, default: bool| format!(
+                "<merchant-account><id>{}</id><status>active</status><currency-iso-code>{}</currency-iso-code>\
+                 <default type=\"boolean\">{}</default></merchant-account>", id, currency, default);
+            match (request.method.as_ref(), request.url.path(), request.url.query()) {
+                ("GET", "/merchants/my_merchant_id/merchant_accounts", Some("page=1")) => (StatusCode::Ok, format!(
+                    "<merchant-accounts><current-page-number>1</current-page-number><page-size>50</page-size>\
+                     <total-items>2</total-items>{}{}</merchant-accounts>",
+                    account("store_eur", "EUR", false), account("store_usd", "USD", true))),
+                ("GET", "/merchants/my_merchant_id/merchant_accounts/store_eur", _) => (StatusCode::Ok, account("store_eur", "EUR", false)),
+                ("GET", "/merchants/my_merchant_id/merchant_accounts/store_new", _) => {
+                    (StatusCode::Ok, String::from("<merchant-account><id>store_new</id><status>pending</status></merchant-account>"))
+                },
+                _ => (StatusCode::NotFound, String::new()),
+            }
+        });
+        let sale = |merchant_account_id: Option<&str>| transaction::Request{
+            amount: "10.00".parse().unwrap(),
+            merchant_account_id: merchant_account_id.map(String::from),
+            ..Default::default()
+        };
+        let guardrails = Guardrails{currencies: Some(vec![String::from("usd")]), ..Default::default()};
+        let bt = client(guardrails).transport(stub.clone()).build().unwrap();
+
+        match bt.transaction().create(sale(Some("store_eur"))) {
+            Err(Error::Guardrail(Violation::CurrencyNotAllowed(currency))) => assert_eq!(currency, "EUR"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match bt.transaction().create(sale(Some("store_new"))) {
+            Err(Error::Guardrail(Violation::UnknownCurrency(id))) => assert_eq!(id.as_deref(), Some("store_new")),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert!(!matches!(bt.transaction().create(sale(None)), Err(Error::Guardrail(_))));
+        assert_eq!(stub.requests().last().unwrap().url.path(), "/merchants/my_merchant_id/transactions");
+        match bt.transaction().create(sale(Some("missing"))) {
+            Err(Error::Guardrail(_)) => panic!("a failed lookup should be reported as is"),
+            Err(_) => {},
+            Ok(_) => panic!("created a transaction in an unknown currency"),
+        }
+        assert_eq!(stub.requests().iter().filter(|request| request.method == Method::Post).count(), 1);
+    }
+}
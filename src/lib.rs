@@ -24,8 +24,8 @@
 //! ```rust
 //! extern crate braintree;
 //!
-//! use braintree::{Braintree, CreditCard, Environment};
-//! use braintree::transaction;
+//! use braintree::{Braintree, CreditCard, Environment, Secret};
+//! use braintree::{test_values, transaction};
 //! use std::error::Error;
 //!
 //! fn main() {
@@ -39,10 +39,10 @@
 //!
 //!     // Attempt to charge the provided credit card $10.
 //!     let result = bt.transaction().create(transaction::Request{
-//!         amount: String::from("10.00"),
+//!         amount: "10.00".parse().unwrap(),
 //!         credit_card: Some(CreditCard{
-//!             number: Some(String::from("4111111111111111")),
-//!             expiration_date: Some(String::from("10/20")),
+//!             number: Some(Secret::from(test_values::card_numbers::VISA)),
+//!             expiration_date: Some(test_values::expiration_date()),
 //!             ..Default::default()
 //!         }),
 //!         options: Some(transaction::Options{
@@ -70,201 +70,523 @@
 //! This crate is very much in a pre-alpha state, and as such the design of its
 //! API is subject to change. You have been forewarned!
 
+#![allow(clippy::result_large_err)]
+
+pub extern crate chrono;
 extern crate elementtree;
+extern crate fastrand;
 #[macro_use] extern crate hyper;
 extern crate libflate;
+extern crate native_tls;
+extern crate toml;
 extern crate xml;
+extern crate zeroize;
 
-macro_rules! write_xml {
-    ($s:expr, $elem:expr, $value:expr) => {
-        if let Some(ref value) = $value {
-            write!($s, "<{}>{}</{}>", $elem, &xml::escape(&value.to_string()), $elem).unwrap();
+macro_rules! from_xml_via_string {
+    ($t:ty) => {
+        impl crate::decode::FromXml for $t {
+            fn from_xml(node: &crate::decode::Node) -> Result<$t, crate::decode::DecodeError> {
+                Ok(<$t>::from(String::from(node.text()?)))
+            }
         }
     }
 }
 
-macro_rules! write_xml_type {
-    ($s:expr, $elem:expr, $typ:expr, $value:expr) => {
-        if let Some(ref value) = $value {
-            write!($s, "<{} type=\"{}\">{}</{}>", $elem, $typ, &xml::escape(&value.to_string()), $elem).unwrap();
+macro_rules! xml_value_via_string {
+    ($t:ty) => {
+        impl crate::encode::XmlValue for $t {
+            const TYPE: Option<&'static str> = None;
+            fn to_text(&self) -> String { String::from(self.clone()) }
         }
     }
 }
 
+/// Builds a `Path` from a template such as `"transactions/{id}/void"`, whose
+/// placeholders are filled in by name.
+macro_rules! path {
+    ($template:literal $(, $name:ident = $arg:expr)*) => {
+        crate::Path{template: String::from($template), path: format!($template $(, $name = $arg)*)}
+    }
+}
 
 header! { (XApiVersion, "X-ApiVersion") => [u8] }
 
 use std::io::Read;
 pub mod address;
+pub mod amount;
+pub mod builder;
+pub mod cassette;
 pub mod client_token;
+pub mod config;
+pub mod credentials;
 pub mod credit_card;
+pub mod credit_card_verification;
 pub mod descriptor;
+pub mod dry_run;
 pub mod customer;
+pub mod decode;
+pub mod encode;
+pub mod guardrails;
 pub mod subscription;
 pub mod error;
+pub mod line_item;
+pub mod merchant_account;
+pub mod metrics;
+pub mod middleware;
+pub mod oauth;
+pub mod registry;
+pub mod retry;
+pub mod search;
+pub mod secret;
+pub mod settlement_batch_summary;
+pub mod test_values;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod trace;
 pub mod transaction;
+pub mod transport;
+pub mod webhook_notification;
 
 pub use address::Address as Address;
+pub use amount::Amount as Amount;
+pub use builder::BraintreeBuilder as BraintreeBuilder;
+pub use credentials::{AccessToken, ApiKey, ClientCredentials, Credentials};
 pub use credit_card::CreditCard as CreditCard;
+pub use credit_card_verification::CreditCardVerification as CreditCardVerification;
 pub use descriptor::Descriptor as Descriptor;
 pub use customer::Customer as Customer;
+pub use line_item::LineItem as LineItem;
+pub use merchant_account::MerchantAccount as MerchantAccount;
+pub use registry::BraintreeRegistry as BraintreeRegistry;
+pub use secret::Secret as Secret;
+pub use settlement_batch_summary::SettlementBatchSummary as SettlementBatchSummary;
 pub use subscription::Subscription as Subscription;
 pub use error::Error as Error;
+pub use webhook_notification::WebhookNotification as WebhookNotification;
 
 pub struct Braintree {
     creds: Box<dyn Credentials>,
-    client: hyper::Client,
-    merchant_url: hyper::Url,
+    transport: std::sync::Arc<dyn transport::Transport>,
+    base_url: hyper::Url,
+    merchant_url: Option<hyper::Url>,
     user_agent: String,
+    api_version: u8,
+    retry: retry::RetryPolicy,
+    metrics: Option<std::sync::Arc<dyn metrics::Metrics>>,
+    middleware: Vec<std::sync::Arc<dyn middleware::Middleware>>,
+    guardrails: Option<guardrails::Guardrails>,
+    dry_run: bool,
 }
 
 impl Braintree {
+    /// Create a handle with the default settings. This panics if the TLS
+    /// client can't be set up; use `builder()` to handle that error or to
+    /// customize the handle.
     pub fn new<S>(env: Environment, merchant_id: S, public_key: S, private_key: S) -> Braintree
         where S: Into<String>
     {
-        let ssl = hyper_native_tls::NativeTlsClient::new().unwrap();
-        let connector = hyper::net::HttpsConnector::new(ssl);
-
-        let merchant_id = merchant_id.into();
-        let public_key = public_key.into();
-        let private_key = private_key.into();
-        // Calculate some things in advance.
-        let merchant_url = hyper::Url::parse(&format!("{}/merchants/{}/", env.base_url(), merchant_id)).unwrap();
-        Braintree{
-            creds: Box::new(ApiKey{
-                       env: env,
-                       merchant_id: merchant_id,
-                       auth_header: hyper::header::Basic{username: public_key.clone(), password: Some(private_key.clone())},
-                       public_key: public_key,
-                       private_key: private_key,
-                   }),
-            client: hyper::Client::with_connector(connector),
-            merchant_url: merchant_url,
-            user_agent: format!("Braintree Rust {}", env!("CARGO_PKG_VERSION")),
-        }
+        Braintree::builder()
+            .environment(env)
+            .merchant_id(merchant_id)
+            .public_key(public_key)
+            .private_key(private_key)
+            .build()
+            .unwrap()
+    }
+
+    /// Configure a handle with custom timeouts, proxy, TLS or transport
+    /// settings.
+    pub fn builder() -> BraintreeBuilder {
+        BraintreeBuilder::new()
+    }
+
+    /// Create a handle from the `BRAINTREE_*` environment variables. See the
+    /// `config` module for the variables that are read.
+    pub fn from_env() -> error::Result<Braintree> {
+        BraintreeBuilder::from_env()?.build()
+    }
+
+    /// Create a handle from a TOML configuration file, using the profile
+    /// named by `BRAINTREE_PROFILE`, or `default`. Use
+    /// `BraintreeBuilder::from_profile()` to choose the profile explicitly.
+    pub fn from_config<P: AsRef<std::path::Path>>(path: P) -> error::Result<Braintree> {
+        BraintreeBuilder::from_profile(path, &config::default_profile()?)?.build()
+    }
+
+    /// The environment this handle sends requests to.
+    pub fn environment(&self) -> Environment {
+        self.creds.environment()
     }
 
-    pub fn client_token(&self) -> ClientTokenGateway {
+    /// The merchant ID this handle sends requests for, if its credentials
+    /// are tied to a merchant.
+    pub fn merchant_id(&self) -> Option<&str> {
+        self.creds.merchant_id()
+    }
+
+    /// Set how failed requests are retried. By default, reads are attempted
+    /// up to three times.
+    pub fn set_retry_policy(&mut self, policy: retry::RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// Refuse requests that break these guardrails, or lift them with
+    /// `None`. See the `guardrails` module.
+    pub fn set_guardrails(&mut self, guardrails: Option<guardrails::Guardrails>) {
+        self.guardrails = guardrails;
+    }
+
+    /// Render requests instead of sending them. Every call then returns
+    /// `Err(Error::DryRun(..))`; see the `dry_run` module.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn client_token(&self) -> ClientTokenGateway<'_> {
         ClientTokenGateway(self)
     }
 
-    pub fn customer(&self) -> CustomerGateway {
+    pub fn credit_card_verification(&self) -> CreditCardVerificationGateway<'_> {
+        CreditCardVerificationGateway(self)
+    }
+
+    pub fn customer(&self) -> CustomerGateway<'_> {
         CustomerGateway(self)
     }
 
-    pub fn transaction(&self) -> TransactionGateway {
+    pub fn transaction(&self) -> TransactionGateway<'_> {
         TransactionGateway(self)
     }
 
-    pub fn subscription(&self) -> SubscriptionGateway {
+    pub fn merchant_account(&self) -> MerchantAccountGateway<'_> {
+        MerchantAccountGateway(self)
+    }
+
+    pub fn oauth(&self) -> OAuthGateway<'_> {
+        OAuthGateway(self)
+    }
+
+    pub fn settlement_batch_summary(&self) -> SettlementBatchSummaryGateway<'_> {
+        SettlementBatchSummaryGateway(self)
+    }
+
+    pub fn subscription(&self) -> SubscriptionGateway<'_> {
         SubscriptionGateway(self)
     }
 
-    pub fn testing(&self) -> TestingGateway {
+    pub fn testing(&self) -> TestingGateway<'_> {
         TestingGateway(self)
     }
 
-    fn execute(&self, method: hyper::method::Method, path: &str, body: Option<&[u8]>) -> hyper::error::Result<hyper::client::response::Response> {
+    pub fn webhook_notification(&self) -> WebhookNotificationGateway<'_> {
+        WebhookNotificationGateway(self)
+    }
+
+    /// Sends a request, retrying according to the retry policy. `GET`s are
+    /// assumed to be safe to repeat; everything else is not.
+    fn execute<P: Into<Path>>(&self, method: hyper::method::Method, path: P, body: Option<&[u8]>) -> error::Result<transport::Response> {
+        let safety = match method {
+            hyper::method::Method::Get => retry::Safety::Idempotent,
+            _ => retry::Safety::Unsafe,
+        };
+        self.execute_with(safety, method, path, body)
+    }
+
+    fn execute_with<P: Into<Path>>(&self, safety: retry::Safety, method: hyper::method::Method, path: P, body: Option<&[u8]>) -> error::Result<transport::Response> {
+        let path = path.into();
+        let url = match self.merchant_url {
+            Some(ref merchant_url) => merchant_url.join(&path.path).map_err(hyper::Error::from)?,
+            None => return Err(Error::Setup(String::from("these credentials aren't tied to a merchant, and can only be used with the OAuth gateway"))),
+        };
+        self.execute_url(safety, method, &url, &path.template, body)
+    }
+
+    /// Sends a request to a URL outside of the merchant's path, e.g. for
+    /// OAuth.
+    fn execute_url(&self, safety: retry::Safety, method: hyper::method::Method, url: &hyper::Url, template: &str, body: Option<&[u8]>) -> error::Result<transport::Response> {
+        let span = trace::RequestSpan::start(&method, template, self.merchant_id(), body);
+        let started = std::time::Instant::now();
+        let mut attempt = 1;
+        loop {
+            let result = self.send(method.clone(), url, template, body);
+            let retry = attempt < self.retry.max_attempts && match result {
+                Ok(ref response) => retry::should_retry_status(response.status, safety),
+                Err(Error::Http(ref err)) => retry::should_retry_error(err, safety),
+                Err(_) => false,
+            };
+            if !retry {
+                span.finish(&result, attempt - 1);
+                let sent = !matches!(result, Err(Error::DryRun(_)) | Err(Error::Guardrail(_)));
+                if let Some(metrics) = self.metrics.as_ref().filter(|_| sent) {
+                    metrics.request(&metrics::Request{
+                        method: method.as_ref(),
+                        path: template,
+                        status: result.as_ref().ok().map(|response| response.status.to_u16()),
+                        latency: started.elapsed(),
+                        retries: attempt - 1,
+                    });
+                }
+                return result;
+            }
+            span.retrying(attempt);
+            std::thread::sleep(self.retry.backoff(attempt));
+            attempt += 1;
+        }
+    }
+
+    /// Sends a single request through the middleware chain.
+    fn send(&self, method: hyper::method::Method, url: &hyper::Url, template: &str, body: Option<&[u8]>) -> error::Result<transport::Response> {
         use hyper::header::{self, Quality, QualityItem};
         use hyper::mime::{Mime, TopLevel, SubLevel};
 
-        let url = self.merchant_url.join(&path).unwrap();
+        let mut headers = header::Headers::new();
+        headers.set(header::ContentType(Mime(TopLevel::Application, SubLevel::Xml, vec![])));
+        headers.set(header::Accept(vec![QualityItem::new(Mime(TopLevel::Application, SubLevel::Xml, vec![]), Quality(1000))]));
+        headers.set(header::AcceptEncoding(vec![QualityItem::new(header::Encoding::Gzip, Quality(1000))]));
+        headers.set(header::UserAgent(self.user_agent.clone()));
+        headers.set_raw("Authorization", vec![self.creds.authorization_header().expose().as_bytes().to_vec()]);
+        headers.set(XApiVersion(self.api_version));
 
-        let mut req = self.client.request(method, url)
-            .header(header::ContentType(Mime(TopLevel::Application, SubLevel::Xml, vec![])))
-            .header(header::Accept(vec![QualityItem::new(Mime(TopLevel::Application, SubLevel::Xml, vec![]), Quality(1000))]))
-            .header(header::AcceptEncoding(vec![QualityItem::new(header::Encoding::Gzip, Quality(1000))]))
-            .header(header::UserAgent(self.user_agent.clone()))
-            .header(header::Authorization(self.creds.authorization_header()))
-            .header(XApiVersion(4));
+        let mut request = transport::Request{
+            method,
+            url: url.clone(),
+            headers,
+            body: body.map(<[u8]>::to_vec),
+        };
+        for middleware in &self.middleware {
+            middleware.on_request(&mut request).map_err(Error::Middleware)?;
+        }
+        // Middleware may have rewritten the request, in which case the
+        // template no longer describes it.
+        let template = if request.url == *url { template } else { request.url.path() };
+        self.guard(|g| g.request(&request.method, template))?;
+        if self.dry_run {
+            return Err(Error::DryRun(dry_run::PreparedRequest::new(&request)));
+        }
+        if self.middleware.is_empty() {
+            return Ok(self.transport.send(request)?);
+        }
 
-        if let Some(data) = body {
-            req = req.body(hyper::client::Body::BufBody(data, data.len()));
+        let response = self.transport.send(request.clone())?;
+        let (status, mut headers) = (response.status, response.headers.clone());
+        let mut body = Vec::new();
+        self.decompress(response)?.read_to_end(&mut body).map_err(hyper::Error::from)?;
+        headers.remove::<hyper::header::ContentEncoding>();
+
+        let mut response = middleware::Response{status, headers, body};
+        for middleware in self.middleware.iter().rev() {
+            middleware.on_response(&request, &mut response).map_err(Error::Middleware)?;
         }
+        Ok(transport::Response{
+            status: response.status,
+            headers: response.headers,
+            body: Box::new(std::io::Cursor::new(response.body)),
+        })
+    }
 
-        req.send()
+    /// Returns a reader for the response body, logging it if tracing is
+    /// enabled.
+    fn response_reader(&self, response: transport::Response) -> hyper::error::Result<Box<dyn Read>> {
+        trace::response_body(self.decompress(response)?)
     }
 
     /// Returns a reader that will correctly decode the response body's data based on its Content-Encoding header.
-    fn response_reader(&self, response: hyper::client::response::Response) -> hyper::error::Result<Box<dyn Read>> {
-        // TODO: This is written this way in order to appease the borrow checker, but there's probably a better way to do this.
-        let headers = response.headers.clone();
-        let content_encoding = headers.get::<hyper::header::ContentEncoding>();
-        let mut r: Box<dyn Read> = Box::new(response);
-        // ???: Use Content-Length somehow to provide a hint to the consumer?
-        if let Some(content_encoding) = content_encoding {
-            match content_encoding[0] {
-                hyper::header::Encoding::Gzip => {
-                    r = Box::new(libflate::gzip::Decoder::new(r)?);
-                },
-                _ => panic!("unsupported content encoding: {}", content_encoding[0]),
+    fn decompress(&self, response: transport::Response) -> hyper::error::Result<Box<dyn Read>> {
+        Ok(response.decoded_body()?)
+    }
+
+    /// Runs an advanced search against `resource`, e.g. `transactions`. The
+    /// ids of every match are fetched first, then the records themselves one
+    /// page at a time. Searches don't modify anything, so they are retried
+    /// like reads.
+    fn search<S: encode::ToXml, T: decode::FromXml>(&self, resource: &'static str, criteria: &S) -> error::Result<Vec<T>> {
+        let body = encode::to_xml("search", criteria)?;
+        let response = self.execute_with(retry::Safety::Idempotent, hyper::method::Method::Post, path!("{resource}/advanced_search_ids", resource = resource), Some(body.as_bytes()))?;
+        let results = match response.status {
+            hyper::status::StatusCode::Ok => self.decode::<search::SearchResults>(response)?,
+            _ => return Err(Error::from(self.response_reader(response)?)),
+        };
+
+        let mut records = Vec::with_capacity(results.ids.len());
+        for ids in results.ids.chunks(results.page_size.max(1)) {
+            let body = encode::to_xml("search", &search::Ids(ids))?;
+            let response = self.execute_with(retry::Safety::Idempotent, hyper::method::Method::Post, path!("{resource}/advanced_search", resource = resource), Some(body.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Ok => records.extend(self.decode::<search::Collection<T>>(response)?.0),
+                _ => return Err(Error::from(self.response_reader(response)?)),
             }
         }
-        Ok(r)
+        Ok(records)
+    }
+
+    /// Checks a request against the guardrails, if any are set.
+    fn guard<F>(&self, check: F) -> error::Result<()>
+        where F: FnOnce(&guardrails::Guardrails) -> Result<(), guardrails::Violation>
+    {
+        match self.guardrails {
+            Some(ref guardrails) => Ok(check(guardrails)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs a gateway operation, reporting its outcome to the metrics hook.
+    fn observe<T, F>(&self, operation: &'static str, f: F) -> error::Result<T>
+        where T: metrics::Outcome, F: FnOnce() -> error::Result<T>
+    {
+        let metrics = match self.metrics {
+            Some(ref metrics) => metrics,
+            None => return f(),
+        };
+        let started = std::time::Instant::now();
+        let result = f();
+        metrics.operation(&metrics::Operation::new(operation, started.elapsed(), &result));
+        result
+    }
+
+    /// Decodes a successful response body into the expected resource.
+    fn decode<T: decode::FromXml>(&self, response: transport::Response) -> error::Result<T> {
+        Ok(decode::from_reader(self.response_reader(response)?)?)
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Environment {
     Sandbox,
     Production,
+    /// A gateway running locally, at `http://localhost:3000` unless
+    /// overridden with `BraintreeBuilder::base_url()`.
+    Development,
+    /// Braintree's QA gateway.
+    Qa,
 }
 
 impl Environment {
-    fn base_url(&self) -> &str {
+    fn base_url(&self) -> &'static str {
         match *self {
             Environment::Sandbox => "https://sandbox.braintreegateway.com",
             Environment::Production => "https://www.braintreegateway.com",
+            Environment::Development => "http://localhost:3000",
+            Environment::Qa => "https://gateway.qa.braintreepayments.com",
         }
     }
 
-    #[allow(dead_code)]
-    pub fn from_str(input: &str) -> Result<Environment, Error> {
-        match input {
-            "Sandbox"     => Ok(Environment::Sandbox),
-            "Production"  => Ok(Environment::Production),
-            _             => Err(Error::Setup),
+    fn name(&self) -> &'static str {
+        match *self {
+            Environment::Sandbox => "sandbox",
+            Environment::Production => "production",
+            Environment::Development => "development",
+            Environment::Qa => "qa",
         }
     }
-}
 
-trait Credentials : Send {
-    fn environment(&self) -> Environment;
-    fn merchant_id(&self) -> &str;
-    fn authorization_header(&self) -> hyper::header::Basic;
+    /// Parses an environment name. Kept for callers from before
+    /// `Environment` implemented `FromStr`, which this forwards to.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &str) -> Result<Environment, Error> {
+        <Environment as std::str::FromStr>::from_str(input)
+    }
 }
 
-#[allow(dead_code)]
-struct ApiKey {
-    env: Environment,
-    merchant_id: String,
-    public_key: String,
-    private_key: String,
-    auth_header: hyper::header::Basic,
+/// Parses an environment name, ignoring case, e.g. `"sandbox"` or
+/// `"Production"`.
+impl std::str::FromStr for Environment {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Environment, Error> {
+        [Environment::Sandbox, Environment::Production, Environment::Development, Environment::Qa].iter()
+            .find(|env| env.name().eq_ignore_ascii_case(input))
+            .cloned()
+            .ok_or_else(|| Error::Setup(format!("unknown environment: {}", input)))
+    }
 }
 
-impl Credentials for ApiKey {
-    fn environment(&self) -> Environment { self.env }
-    fn merchant_id(&self) -> &str { &self.merchant_id }
-    fn authorization_header(&self) -> hyper::header::Basic { self.auth_header.clone() }
+impl std::fmt::Display for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
+
 pub struct ClientTokenGateway<'a>(&'a Braintree);
 
 impl<'a> ClientTokenGateway<'a> {
     /// Generate a client token. The simplest usage is:
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use braintree::{Braintree, Environment};
+    /// # let bt = Braintree::new(Environment::Sandbox, "<merchant_id>", "<public_key>", "<private_key>");
     /// let client_token = bt.client_token().generate(Default::default());
     /// ```
     ///
     /// Further customization can be done by manually specifying your own `client_token::Request` value.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.client_token.generate", skip_all))]
     pub fn generate(&self, req: client_token::Request) -> error::Result<client_token::ClientToken> {
-        let response = self.0.execute(hyper::method::Method::Post, "client_token", Some(req.to_xml(None).as_bytes()))?;
-        match response.status {
-            hyper::status::StatusCode::Created => Ok(client_token::ClientToken::from(self.0.response_reader(response)?)),
-            _ => Err(Error::from(self.0.response_reader(response)?)),
-        }
+        self.0.observe("client_token.generate", || {
+            self.0.guard(|g| g.merchant_account(req.merchant_account_id.as_ref()))?;
+            let response = self.0.execute(hyper::method::Method::Post, "client_token", Some(encode::to_xml("client-token", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Created => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+}
+
+pub struct CreditCardVerificationGateway<'a>(&'a Braintree);
+
+impl<'a> CreditCardVerificationGateway<'a> {
+    /// Verify a credit card without storing it in the Vault. This runs a
+    /// zero- or low-dollar authorization against the card, along with any AVS
+    /// and CVV checks configured for your account:
+    ///
+    /// ```rust,no_run
+    /// # use braintree::{test_values, Braintree, Environment};
+    /// # let bt = Braintree::new(Environment::Sandbox, "<merchant_id>", "<public_key>", "<private_key>");
+    /// let verification = bt.credit_card_verification().create(test_values::card(test_values::card_numbers::VISA), None);
+    /// ```
+    ///
+    /// Note that a verification that was declined is still returned as
+    /// `Ok`; check its `status` to see whether the card passed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.credit_card_verification.create", skip_all))]
+    pub fn create(&self, credit_card: CreditCard, options: Option<credit_card_verification::Options>) -> error::Result<CreditCardVerification> {
+        self.0.observe("credit_card_verification.create", || {
+            let env = self.0.environment();
+            self.0.guard(|g| {
+                g.payment_method(env, Some(&credit_card), None)?;
+                if let Some(ref options) = options {
+                    g.amount(options.amount.as_ref())?;
+                    g.merchant_account(options.merchant_account_id.as_ref())?;
+                }
+                Ok(())
+            })?;
+            let req = credit_card_verification::Request{credit_card, options};
+            let response = self.0.execute(hyper::method::Method::Post, "verifications", Some(encode::to_xml("verification", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Created => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Retrieve details for a verification.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.credit_card_verification.find", skip_all))]
+    pub fn find(&self, verification_id: String) -> error::Result<CreditCardVerification> {
+        self.0.observe("credit_card_verification.find", || {
+            let response = self.0.execute(hyper::method::Method::Get, path!("verifications/{id}", id = verification_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Search for verifications matching the given criteria. All matching
+    /// records are fetched eagerly, one page at a time.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.credit_card_verification.search", skip_all))]
+    pub fn search(&self, criteria: credit_card_verification::Search) -> error::Result<Vec<CreditCardVerification>> {
+        self.0.observe("credit_card_verification.search", || {
+            self.0.search("verifications", &criteria)
+        })
     }
 }
 
@@ -273,17 +595,222 @@ pub struct CustomerGateway<'a>(&'a Braintree);
 impl<'a> CustomerGateway<'a> {
     /// Generate a customer. The simplest usage is:
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use braintree::{Braintree, Environment};
+    /// # let bt = Braintree::new(Environment::Sandbox, "<merchant_id>", "<public_key>", "<private_key>");
     /// let customer = bt.customer().generate(Default::default());
     /// ```
     ///
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.customer.generate", skip_all))]
     pub fn generate(&self, req: Customer) -> error::Result<customer::Customer> {
-        let response = self.0.execute(hyper::method::Method::Post, "customers", Some(req.to_xml(None).as_bytes()))?;
+        self.0.observe("customer.generate", || {
+            let env = self.0.environment();
+            self.0.guard(|g| g.payment_method(env, req.credit_card.as_ref(), req.payment_method_nonce.as_ref()))?;
+            let response = self.0.execute(hyper::method::Method::Post, "customers", Some(encode::to_xml("customer", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Created => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+}
+
+pub struct MerchantAccountGateway<'a>(&'a Braintree);
+
+impl<'a> MerchantAccountGateway<'a> {
+    /// Onboard a new sub-merchant account for a marketplace seller. The
+    /// account starts out as `Pending`; you'll receive a
+    /// `SubMerchantAccountApproved` or `SubMerchantAccountDeclined` webhook
+    /// once Braintree has reviewed it.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.merchant_account.create", skip_all))]
+    pub fn create(&self, req: merchant_account::Request) -> error::Result<MerchantAccount> {
+        self.0.observe("merchant_account.create", || {
+            let response = self.0.execute(hyper::method::Method::Post, "merchant_accounts/create_via_api", Some(encode::to_xml("merchant-account", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Created => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Update the details of an existing sub-merchant account.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.merchant_account.update", skip_all))]
+    pub fn update(&self, merchant_account_id: String, req: merchant_account::Request) -> error::Result<MerchantAccount> {
+        self.0.observe("merchant_account.update", || {
+            let response = self.0.execute(hyper::method::Method::Put, path!("merchant_accounts/{id}/update_via_api", id = merchant_account_id), Some(encode::to_xml("merchant-account", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Retrieve details for a merchant account.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.merchant_account.find", skip_all))]
+    pub fn find(&self, merchant_account_id: String) -> error::Result<MerchantAccount> {
+        self.0.observe("merchant_account.find", || {
+            let response = self.0.execute(hyper::method::Method::Get, path!("merchant_accounts/{id}", id = merchant_account_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Retrieve a page of the merchant accounts on your gateway. Pages are
+    /// numbered from 1; use `has_next()` on the result to check whether
+    /// there's another page to fetch.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.merchant_account.all", skip_all))]
+    pub fn all(&self, page: u32) -> error::Result<merchant_account::Page> {
+        self.0.observe("merchant_account.all", || {
+            let response = self.0.execute(hyper::method::Method::Get, path!("merchant_accounts?page={page}", page = page), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// The currency of a merchant account, or of the default merchant
+    /// account if `merchant_account_id` is `None`.
+    fn currency(&self, merchant_account_id: Option<&String>) -> error::Result<Option<String>> {
+        if let Some(id) = merchant_account_id {
+            return Ok(self.find(id.clone())?.currency_iso_code);
+        }
+        let mut page = 1;
+        loop {
+            let accounts = self.all(page)?;
+            if let Some(account) = accounts.merchant_accounts.iter().find(|account| account.default == Some(true)) {
+                return Ok(account.currency_iso_code.clone());
+            }
+            if !accounts.has_next() {
+                return Ok(None);
+            }
+            page += 1;
+        }
+    }
+
+    /// Create a merchant account that settles in a different currency,
+    /// given as an ISO 4217 code such as `"EUR"`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.merchant_account.create_for_currency", skip_all))]
+    pub fn create_for_currency(&self, currency: String, merchant_account_id: Option<String>) -> error::Result<MerchantAccount> {
+        self.0.observe("merchant_account.create_for_currency", || {
+            self.0.guard(|g| g.currency(&currency))?;
+            let req = merchant_account::CurrencyRequest{currency, id: merchant_account_id};
+            let response = self.0.execute(hyper::method::Method::Post, "merchant_accounts/create_for_currency", Some(encode::to_xml("merchant-account", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Created|hyper::status::StatusCode::Ok => self.0.decode::<merchant_account::CurrencyResponse>(response).map(|r| r.0),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+}
+
+pub struct OAuthGateway<'a>(&'a Braintree);
+
+impl<'a> OAuthGateway<'a> {
+    /// Exchange the authorization code passed to your redirect URI for an
+    /// access token. The handle must be set up with `ClientCredentials`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.oauth.create_token_from_code", skip_all))]
+    pub fn create_token_from_code(&self, code: &str, scope: Option<&str>) -> error::Result<oauth::Token> {
+        self.0.observe("oauth.create_token_from_code", || {
+            self.create_token(&oauth::TokenRequest::Code{code, scope})
+        })
+    }
+
+    /// Obtain a new access token with a refresh token. The handle must be set
+    /// up with `ClientCredentials`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.oauth.create_token_from_refresh_token", skip_all))]
+    pub fn create_token_from_refresh_token(&self, refresh_token: &str, scope: Option<&str>) -> error::Result<oauth::Token> {
+        self.0.observe("oauth.create_token_from_refresh_token", || {
+            self.create_token(&oauth::TokenRequest::RefreshToken{refresh_token, scope})
+        })
+    }
+
+    fn create_token(&self, req: &oauth::TokenRequest) -> error::Result<oauth::Token> {
+        let url = self.url("oauth/access_tokens")?;
+        let response = self.0.execute_url(retry::Safety::Unsafe, hyper::method::Method::Post, &url, "oauth/access_tokens", Some(encode::to_xml("credentials", req)?.as_bytes()))?;
         match response.status {
-            hyper::status::StatusCode::Created => Ok(customer::Customer::from(self.0.response_reader(response)?)),
+            hyper::status::StatusCode::Ok | hyper::status::StatusCode::Created => self.0.decode(response),
             _ => Err(Error::from(self.0.response_reader(response)?)),
         }
     }
+
+    /// Revoke an access token, so that it can no longer be used. Returns
+    /// whether Braintree reported success. Revoking is safe to repeat, so
+    /// it's retried like a read.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.oauth.revoke_access_token", skip_all))]
+    pub fn revoke_access_token(&self, access_token: &str) -> error::Result<bool> {
+        self.0.observe("oauth.revoke_access_token", || {
+            let url = self.url("oauth/revoke_access_token")?;
+            let response = self.0.execute_url(retry::Safety::Idempotent, hyper::method::Method::Post, &url, "oauth/revoke_access_token", Some(encode::value_to_xml("token", access_token)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Ok | hyper::status::StatusCode::Created => Ok(self.0.decode::<oauth::RevokeResult>(response)?.success),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Build the URL to send a merchant to so that they can connect to your
+    /// application:
+    ///
+    /// ```rust,no_run
+    /// # use braintree::{Braintree, ClientCredentials};
+    /// # use braintree::oauth;
+    /// let bt = Braintree::builder()
+    ///     .credentials(ClientCredentials::new("<client_id>", "<client_secret>").unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// let url = bt.oauth().connect_url(&oauth::ConnectUrl{
+    ///     redirect_uri: String::from("https://example.com/braintree/callback"),
+    ///     scope: String::from("read_write"),
+    ///     state: Some(String::from("<csrf_token>")),
+    ///     ..Default::default()
+    /// });
+    /// ```
+    ///
+    /// The handle must be set up with `ClientCredentials`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.oauth.connect_url", skip_all))]
+    pub fn connect_url(&self, req: &oauth::ConnectUrl) -> error::Result<String> {
+        let client_id = self.0.creds.client_id()
+            .ok_or_else(|| Error::Setup(String::from("connect URLs require client credentials")))?;
+        let mut url = self.url("oauth/connect")?;
+        url.query_pairs_mut().extend_pairs(req.query_pairs(client_id));
+        Ok(url.into_string())
+    }
+
+    /// OAuth endpoints live outside of any merchant's path.
+    fn url(&self, path: &str) -> hyper::error::Result<hyper::Url> {
+        Ok(self.0.base_url.join(path)?)
+    }
+}
+
+pub struct SettlementBatchSummaryGateway<'a>(&'a Braintree);
+
+impl<'a> SettlementBatchSummaryGateway<'a> {
+    /// Generate a summary of the transactions settled on the given date:
+    ///
+    /// ```rust,no_run
+    /// # use braintree::{Braintree, Environment};
+    /// # use braintree::chrono::NaiveDate;
+    /// # let bt = Braintree::new(Environment::Sandbox, "<merchant_id>", "<public_key>", "<private_key>");
+    /// let date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+    /// let summary = bt.settlement_batch_summary().generate(date, None);
+    /// ```
+    ///
+    /// Passing `group_by_custom_field` further breaks down each record by the
+    /// value of that custom field.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.settlement_batch_summary.generate", skip_all))]
+    pub fn generate(&self, settlement_date: chrono::NaiveDate, group_by_custom_field: Option<String>) -> error::Result<SettlementBatchSummary> {
+        self.0.observe("settlement_batch_summary.generate", || {
+            let req = settlement_batch_summary::Request{settlement_date, group_by_custom_field};
+            let response = self.0.execute(hyper::method::Method::Post, "settlement_batch_summary", Some(encode::to_xml("settlement-batch-summary", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
 }
 
 pub struct SubscriptionGateway<'a>(&'a Braintree);
@@ -291,17 +818,23 @@ pub struct SubscriptionGateway<'a>(&'a Braintree);
 impl<'a> SubscriptionGateway<'a> {
     /// Generate a subscription. The simplest usage is:
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use braintree::{Braintree, Environment};
+    /// # let bt = Braintree::new(Environment::Sandbox, "<merchant_id>", "<public_key>", "<private_key>");
     /// let subscription = bt.subscription().create(Default::default());
     /// ```
     ///
     /// Further customization can be done by manually specifying your own `client_token::Request` value.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.subscription.create", skip_all))]
     pub fn create(&self, req: subscription::Request) -> error::Result<subscription::Subscription> {
-        let response = self.0.execute(hyper::method::Method::Post, "subscriptions", Some(req.to_xml(None).as_bytes()))?;
-        match response.status {
-            hyper::status::StatusCode::Created => Ok(subscription::Subscription::from(self.0.response_reader(response)?)),
-            _ => Err(Error::from(self.0.response_reader(response)?)),
-        }
+        self.0.observe("subscription.create", || {
+            self.0.guard(|g| g.amount(req.price.as_ref()))?;
+            let response = self.0.execute(hyper::method::Method::Post, "subscriptions", Some(encode::to_xml("subscription", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Created => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
     }
 }
 
@@ -322,77 +855,344 @@ impl<'a> TransactionGateway<'a> {
     /// For more information, check out Braintree's documentation on the
     /// [transaction
     /// lifecycle](https://articles.braintreepayments.com/support/get-started/transaction-life-cycle).
+    ///
+    /// Creating a transaction is never retried automatically. If the
+    /// `recover_by_order_id` retry option is set and the request has an
+    /// `order_id`, a failure that leaves the outcome unknown (a dropped
+    /// connection or a server error) is followed by a search for a
+    /// transaction with that order id and the same amount, created since the
+    /// call started. It's returned if exactly one is found; otherwise, the
+    /// original error is returned.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.create", skip_all))]
     pub fn create(&self, transaction: transaction::Request) -> error::Result<transaction::Transaction> {
-        let response = self.0.execute(hyper::method::Method::Post, "transactions", Some(transaction.to_xml(None).as_bytes()))?;
-        match response.status {
-            hyper::status::StatusCode::Created => Ok(transaction::Transaction::from(self.0.response_reader(response)?)),
-            _ => Err(Error::from(self.0.response_reader(response)?)),
+        self.0.observe("transaction.create", || {
+            let env = self.0.environment();
+            self.0.guard(|g| {
+                g.amount(Some(&transaction.amount))?;
+                g.merchant_account(transaction.merchant_account_id.as_ref())?;
+                g.payment_method(env, transaction.credit_card.as_ref(), transaction.payment_method_nonce.as_ref())?;
+                match transaction.customer {
+                    Some(ref customer) => g.payment_method(env, customer.credit_card.as_ref(), customer.payment_method_nonce.as_ref()),
+                    None => Ok(()),
+                }
+            })?;
+            if !self.0.dry_run && self.0.guardrails.as_ref().is_some_and(|g| g.currencies.is_some()) {
+                let merchant_account_id = transaction.merchant_account_id.as_ref();
+                match self.0.merchant_account().currency(merchant_account_id)? {
+                    Some(currency) => self.0.guard(|g| g.currency(&currency))?,
+                    None => return Err(Error::from(guardrails::Violation::UnknownCurrency(merchant_account_id.cloned()))),
+                }
+            }
+            let started = chrono::Utc::now();
+            let result = self.0.execute(hyper::method::Method::Post, "transactions", Some(encode::to_xml("transaction", &transaction)?.as_bytes()));
+            let order_id = match transaction.order_id {
+                Some(ref order_id) if self.0.retry.recover_by_order_id => Some(order_id),
+                _ => None,
+            };
+            match result {
+                Ok(response) => match response.status {
+                    hyper::status::StatusCode::Created => self.0.decode(response),
+                    status => {
+                        let err = Error::from(self.0.response_reader(response)?);
+                        match order_id {
+                            Some(order_id) if status.is_server_error() => self.recover(order_id, &transaction.amount, started, err),
+                            _ => Err(err),
+                        }
+                    },
+                },
+                Err(err) => match (order_id, &err) {
+                    (Some(order_id), Error::Http(ref http)) if retry::is_ambiguous(http) => self.recover(order_id, &transaction.amount, started, err),
+                    _ => Err(err),
+                },
+            }
+        })
+    }
+
+    /// Look for the transaction created by an ambiguous failed `create()`
+    /// that started at `started`. Transactions created earlier, allowing for
+    /// Braintree's clock being a few minutes behind ours, or for another
+    /// amount, were created by some other call that reused the order id.
+    fn recover(&self, order_id: &str, amount: &Amount, started: chrono::DateTime<chrono::Utc>, err: Error) -> error::Result<transaction::Transaction> {
+        let since = started - chrono::Duration::minutes(5);
+        let criteria = transaction::Search{order_id: Some(search::Text::Is(String::from(order_id))), ..Default::default()};
+        let mut transactions = match self.search(criteria) {
+            Ok(transactions) => transactions,
+            Err(_) => return Err(err),
+        };
+        transactions.retain(|transaction| transaction.amount == *amount && transaction.created_at >= since);
+        match transactions.len() {
+            1 => Ok(transactions.remove(0)),
+            _ => Err(err),
         }
     }
 
+    /// Search for transactions matching the given criteria. All matching
+    /// records are fetched eagerly, one page at a time.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.search", skip_all))]
+    pub fn search(&self, criteria: transaction::Search) -> error::Result<Vec<transaction::Transaction>> {
+        self.0.observe("transaction.search", || {
+            self.0.search("transactions", &criteria)
+        })
+    }
+
     /// Submit an authorized transaction for settlement.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.submit_for_settlement", skip_all))]
     pub fn submit_for_settlement(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
-        let response = self.0.execute(hyper::method::Method::Put, &format!("transactions/{}/submit_for_settlement", transaction_id), None)?;
-        match response.status {
-            hyper::status::StatusCode::Ok => Ok(transaction::Transaction::from(self.0.response_reader(response)?)),
-            _ => Err(Error::from(self.0.response_reader(response)?)),
-        }
+        self.0.observe("transaction.submit_for_settlement", || {
+            let response = self.0.execute(hyper::method::Method::Put, path!("transactions/{id}/submit_for_settlement", id = transaction_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
     }
 
     /// If a transaction has yet to be captured (i.e. it should be in a state
     /// of `Authorized` or `SubmittedForSettlement`), you can cancel it by
     /// calling void.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.void", skip_all))]
     pub fn void(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
-        let response = self.0.execute(hyper::method::Method::Put, &format!("transactions/{}/void", transaction_id), None)?;
-        match response.status {
-            hyper::status::StatusCode::Ok => Ok(transaction::Transaction::from(self.0.response_reader(response)?)),
-            _ => Err(Error::from(self.0.response_reader(response)?)),
-        }
+        self.0.observe("transaction.void", || {
+            let response = self.0.execute(hyper::method::Method::Put, path!("transactions/{id}/void", id = transaction_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
     }
 
     /// When a transaction has been settled, you can refund it, which creates a
     /// new credit transaction. You must pass a settled or settling
     /// `transaction_id` in order to execute a valid refund.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.refund", skip_all))]
     pub fn refund(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
-        // TODO: add an optional amount to refund the transaction partially
-        let response = self.0.execute(hyper::method::Method::Post, &format!("transactions/{}/refund", transaction_id), None)?;
+        self.0.observe("transaction.refund", || {
+            self.send_refund(transaction_id, None)
+        })
+    }
+
+    /// Refund part of a settled or settling transaction. A transaction can be
+    /// partially refunded several times, up to its full amount.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.refund_partial", skip_all))]
+    pub fn refund_partial(&self, transaction_id: String, amount: Amount) -> error::Result<transaction::Transaction> {
+        self.0.observe("transaction.refund_partial", || {
+            self.0.guard(|g| g.amount(Some(&amount)))?;
+            self.send_refund(transaction_id, Some(amount))
+        })
+    }
+
+    fn send_refund(&self, transaction_id: String, amount: Option<Amount>) -> error::Result<transaction::Transaction> {
+        let req = transaction::RefundRequest{amount};
+        let response = self.0.execute(hyper::method::Method::Post, path!("transactions/{id}/refund", id = transaction_id), Some(encode::to_xml("transaction", &req)?.as_bytes()))?;
         match response.status {
-            hyper::status::StatusCode::Created|hyper::status::StatusCode::Ok => Ok(transaction::Transaction::from(self.0.response_reader(response)?)),
+            hyper::status::StatusCode::Created|hyper::status::StatusCode::Ok => self.0.decode(response),
             _ => Err(Error::from(self.0.response_reader(response)?)),
         }
     }
 
+    /// Hold the funds of a sub-merchant transaction in escrow. This is the
+    /// after-the-fact equivalent of creating the transaction with the
+    /// `hold_in_escrow` option, and only works before it has settled.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.hold_in_escrow", skip_all))]
+    pub fn hold_in_escrow(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
+        self.0.observe("transaction.hold_in_escrow", || {
+            let response = self.0.execute(hyper::method::Method::Put, path!("transactions/{id}/hold_in_escrow", id = transaction_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Release a transaction's funds from escrow, so that they are disbursed
+    /// to the sub-merchant. The transaction must have an escrow status of
+    /// `Held`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.release_from_escrow", skip_all))]
+    pub fn release_from_escrow(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
+        self.0.observe("transaction.release_from_escrow", || {
+            let response = self.0.execute(hyper::method::Method::Put, path!("transactions/{id}/release_from_escrow", id = transaction_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Cancel a pending release from escrow, returning the funds to the
+    /// `Held` state. The transaction must have an escrow status of
+    /// `ReleasePending`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.cancel_release", skip_all))]
+    pub fn cancel_release(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
+        self.0.observe("transaction.cancel_release", || {
+            let response = self.0.execute(hyper::method::Method::Put, path!("transactions/{id}/cancel_release", id = transaction_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Retrieve the line items that were sent with a transaction.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.line_items", skip_all))]
+    pub fn line_items(&self, transaction_id: String) -> error::Result<Vec<LineItem>> {
+        self.0.observe("transaction.line_items", || {
+            let response = self.0.execute(hyper::method::Method::Get, path!("transactions/{id}/line_items", id = transaction_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
     /// Retrieve details for a transaction.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.transaction.find", skip_all))]
     pub fn find(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
-        let response = self.0.execute(hyper::method::Method::Get, &format!("transactions/{}", transaction_id), None)?;
-        match response.status {
-            hyper::status::StatusCode::Ok => Ok(transaction::Transaction::from(self.0.response_reader(response)?)),
-            _ => Err(Error::from(self.0.response_reader(response)?)),
-        }
+        self.0.observe("transaction.find", || {
+            let response = self.0.execute(hyper::method::Method::Get, path!("transactions/{id}", id = transaction_id), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
     }
 }
 
+/// Operations that drive sandbox records into states that would otherwise
+/// take days to reach. Every one of them fails with
+/// `Error::TestOperationInProduction` in the production environment, before
+/// any request is sent.
 pub struct TestingGateway<'a>(&'a Braintree);
 
 impl<'a> TestingGateway<'a> {
-    fn set_status(&self, transaction_id: String, status: String) -> error::Result<transaction::Transaction> {
-        if self.0.creds.environment() == Environment::Production {
-            return Err(Error::TestOperationInProduction);
+    fn check_environment(&self) -> error::Result<()> {
+        match self.0.creds.environment() {
+            Environment::Production => Err(Error::TestOperationInProduction),
+            _ => Ok(()),
         }
-        let response = self.0.execute(hyper::method::Method::Put, &format!("transactions/{}/{}", transaction_id, status), None)?;
+    }
+
+    fn set_status(&self, path: Path) -> error::Result<transaction::Transaction> {
+        self.check_environment()?;
+        let response = self.0.execute(hyper::method::Method::Put, path, None)?;
         match response.status {
-            hyper::status::StatusCode::Ok => Ok(transaction::Transaction::from(self.0.response_reader(response)?)),
+            hyper::status::StatusCode::Ok => self.0.decode(response),
             _ => Err(Error::from(self.0.response_reader(response)?)),
         }
     }
 
     /// Force a transaction into a settled state. Note that this is intended
     /// for testing, and will only work in the Sandbox environment.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.testing.settle", skip_all))]
     pub fn settle(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
-        self.set_status(transaction_id, String::from("settle"))
+        self.0.observe("testing.settle", || {
+            self.set_status(path!("transactions/{id}/settle", id = transaction_id))
+        })
+    }
+
+    /// Force a transaction into the `SettlementConfirmed` state.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.testing.settlement_confirm", skip_all))]
+    pub fn settlement_confirm(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
+        self.0.observe("testing.settlement_confirm", || {
+            self.set_status(path!("transactions/{id}/settlement_confirm", id = transaction_id))
+        })
+    }
+
+    /// Force a transaction into the `SettlementDeclined` state, as if the
+    /// processor had refused to settle it.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.testing.settlement_decline", skip_all))]
+    pub fn settlement_decline(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
+        self.0.observe("testing.settlement_decline", || {
+            self.set_status(path!("transactions/{id}/settlement_decline", id = transaction_id))
+        })
+    }
+
+    /// Force a transaction into the `SettlementPending` state.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.testing.settlement_pending", skip_all))]
+    pub fn settlement_pending(&self, transaction_id: String) -> error::Result<transaction::Transaction> {
+        self.0.observe("testing.settlement_pending", || {
+            self.set_status(path!("transactions/{id}/settlement_pending", id = transaction_id))
+        })
+    }
+
+    /// Force a transaction into the `SettlementPending` state, with `reason`
+    /// as the processor's settlement response text.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.testing.settlement_pending_with_reason", skip_all))]
+    pub fn settlement_pending_with_reason(&self, transaction_id: String, reason: &str) -> error::Result<transaction::Transaction> {
+        self.0.observe("testing.settlement_pending_with_reason", || {
+            self.check_environment()?;
+            let req = transaction::SettlementStatusRequest{processor_settlement_response_text: Some(String::from(reason))};
+            let response = self.0.execute(hyper::method::Method::Put, path!("transactions/{id}/settlement_pending", id = transaction_id), Some(encode::to_xml("transaction", &req)?.as_bytes()))?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+
+    /// Create a sale that the sandbox will open a chargeback dispute for
+    /// shortly after it settles, which is the only way to simulate one. The
+    /// dispute is reported by a `DisputeOpened` webhook notification.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.testing.create_disputed_transaction", skip_all))]
+    pub fn create_disputed_transaction(&self, amount: Amount) -> error::Result<transaction::Transaction> {
+        self.0.observe("testing.create_disputed_transaction", || {
+            self.check_environment()?;
+            self.0.transaction().create(transaction::Request{
+                amount,
+                credit_card: Some(test_values::card(test_values::card_numbers::DISPUTE)),
+                options: Some(transaction::Options{
+                    submit_for_settlement: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
     }
+
+    /// Move a subscription's billing back in time so that it's
+    /// `days_past_due` days past due, as if its last charges had failed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.testing.make_past_due", skip_all))]
+    pub fn make_past_due(&self, subscription_id: String, days_past_due: u32) -> error::Result<subscription::Subscription> {
+        self.0.observe("testing.make_past_due", || {
+            self.check_environment()?;
+            let response = self.0.execute(hyper::method::Method::Put, path!("subscriptions/{id}/make_past_due?days_past_due={days}", id = subscription_id, days = days_past_due), None)?;
+            match response.status {
+                hyper::status::StatusCode::Ok => self.0.decode(response),
+                _ => Err(Error::from(self.0.response_reader(response)?)),
+            }
+        })
+    }
+}
+
+pub struct WebhookNotificationGateway<'a>(&'a Braintree);
+
+impl<'a> WebhookNotificationGateway<'a> {
+    /// Verify and parse a webhook notification from the `bt_signature` and
+    /// `bt_payload` parameters that Braintree posts to your endpoint. Returns
+    /// `Error::InvalidSignature` if the notification wasn't signed with your
+    /// API keys.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "braintree.webhook_notification.parse", skip_all))]
+    pub fn parse(&self, signature: &str, payload: &str) -> error::Result<WebhookNotification> {
+        let (public_key, private_key) = self.0.creds.key_pair().ok_or(Error::InvalidSignature)?;
+        if !webhook_notification::verify(public_key, private_key, signature, payload) {
+            return Err(Error::InvalidSignature);
+        }
+        let root = webhook_notification::decode(payload).ok_or(Error::InvalidSignature)?;
+        Ok(decode::FromXml::from_xml(&decode::Node::root(&root))?)
+    }
+}
+
+/// A request path, relative to the merchant's URL, along with the template it
+/// was built from. Only the template, which has no IDs in it, is logged.
+struct Path {
+    template: String,
+    path: String,
 }
 
-trait ToXml {
-    fn to_xml(&self, name: Option<&str>) -> String;
+impl From<&'static str> for Path {
+    fn from(path: &'static str) -> Path {
+        Path{template: String::from(path), path: String::from(path)}
+    }
 }
+
+/// The format Braintree uses for `type="date"` values.
+const DATE_FORMAT: &str = "%Y-%m-%d";
@@ -0,0 +1,97 @@
+use std::convert::From;
+
+use crate::amount::Amount;
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::{ToXml, Writer};
+
+/// A line item on a transaction, used to send Level 3 data to the processor.
+///
+/// `quantity`, `name`, `kind`, `unit_amount` and `total_amount` are
+/// required; the rest are optional but may improve interchange rates.
+#[derive(Debug, Default)]
+pub struct LineItem {
+    pub quantity: Option<String>,
+    pub name: Option<String>,
+    pub kind: Option<Kind>,
+    pub unit_amount: Option<Amount>,
+    pub total_amount: Option<Amount>,
+    pub description: Option<String>,
+    pub product_code: Option<String>,
+    pub commodity_code: Option<String>,
+    pub unit_of_measure: Option<String>,
+    pub unit_tax_amount: Option<Amount>,
+    pub discount_amount: Option<Amount>,
+    pub tax_amount: Option<Amount>,
+    pub url: Option<String>,
+}
+
+impl ToXml for LineItem {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("quantity", &self.quantity);
+        w.field("name", &self.name);
+        w.field("kind", &self.kind);
+        w.field("unit-amount", &self.unit_amount);
+        w.field("total-amount", &self.total_amount);
+        w.field("description", &self.description);
+        w.field("product-code", &self.product_code);
+        w.field("commodity-code", &self.commodity_code);
+        w.field("unit-of-measure", &self.unit_of_measure);
+        w.field("unit-tax-amount", &self.unit_tax_amount);
+        w.field("discount-amount", &self.discount_amount);
+        w.field("tax-amount", &self.tax_amount);
+        w.field("url", &self.url);
+    }
+}
+
+impl FromXml for LineItem {
+    fn from_xml(node: &Node) -> Result<LineItem, DecodeError> {
+        Ok(LineItem{
+            quantity: node.child_opt("quantity")?,
+            name: node.child_opt("name")?,
+            kind: node.child_opt("kind")?,
+            unit_amount: node.child_opt("unit-amount")?,
+            total_amount: node.child_opt("total-amount")?,
+            description: node.child_opt("description")?,
+            product_code: node.child_opt("product-code")?,
+            commodity_code: node.child_opt("commodity-code")?,
+            unit_of_measure: node.child_opt("unit-of-measure")?,
+            unit_tax_amount: node.child_opt("unit-tax-amount")?,
+            discount_amount: node.child_opt("discount-amount")?,
+            tax_amount: node.child_opt("tax-amount")?,
+            url: node.child_opt("url")?,
+        })
+    }
+}
+
+/// Whether a line item adds to (`Debit`) or subtracts from (`Credit`) the
+/// transaction total.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Kind {
+    Debit,
+    Credit,
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
+}
+
+impl From<String> for Kind {
+    fn from(s: String) -> Kind {
+        match s.as_ref() {
+            "debit" => Kind::Debit,
+            "credit" => Kind::Credit,
+            _ => Kind::Unknown(s),
+        }
+    }
+}
+
+impl From<Kind> for String {
+    fn from(k: Kind) -> String {
+        match k {
+            Kind::Debit => String::from("debit"),
+            Kind::Credit => String::from("credit"),
+            Kind::Unknown(value) => value,
+        }
+    }
+}
+
+from_xml_via_string!(Kind);
+xml_value_via_string!(Kind);
@@ -0,0 +1,356 @@
+use chrono;
+use std::convert::From;
+
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::{ToXml, Writer};
+use crate::secret::Secret;
+
+/// A merchant account. For marketplaces, this is either the master merchant
+/// account or a sub-merchant account onboarded on behalf of a seller.
+#[derive(Debug)]
+pub struct MerchantAccount {
+    pub id: String,
+    pub status: Status,
+    pub currency_iso_code: Option<String>,
+    /// Whether this is the default merchant account for the gateway.
+    pub default: Option<bool>,
+    /// The master merchant account that a sub-merchant account belongs to.
+    pub master_merchant_account: Option<Box<MerchantAccount>>,
+    pub individual: Option<IndividualDetails>,
+    pub business: Option<BusinessDetails>,
+    pub funding: Option<FundingDetails>,
+}
+
+impl FromXml for MerchantAccount {
+    fn from_xml(node: &Node) -> Result<MerchantAccount, DecodeError> {
+        Ok(MerchantAccount{
+            id: node.child("id")?,
+            status: node.child("status")?,
+            currency_iso_code: node.child_opt("currency-iso-code")?,
+            default: node.child_opt("default")?,
+            master_merchant_account: node.child_opt("master-merchant-account")?,
+            individual: node.child_opt("individual")?,
+            business: node.child_opt("business")?,
+            funding: node.child_opt("funding")?,
+        })
+    }
+}
+
+/// The response to `create_for_currency`, which wraps the new account in a
+/// `<response>` element.
+pub(crate) struct CurrencyResponse(pub MerchantAccount);
+
+impl FromXml for CurrencyResponse {
+    fn from_xml(node: &Node) -> Result<CurrencyResponse, DecodeError> {
+        Ok(CurrencyResponse(node.child("merchant-account")?))
+    }
+}
+
+/// A single page of merchant accounts, as returned by `all()`.
+#[derive(Debug)]
+pub struct Page {
+    pub current_page_number: u32,
+    pub page_size: u32,
+    pub total_items: u32,
+    pub merchant_accounts: Vec<MerchantAccount>,
+}
+
+impl Page {
+    /// Whether there are more pages after this one.
+    pub fn has_next(&self) -> bool {
+        self.current_page_number * self.page_size < self.total_items
+    }
+}
+
+impl FromXml for Page {
+    fn from_xml(node: &Node) -> Result<Page, DecodeError> {
+        Ok(Page{
+            current_page_number: node.child("current-page-number")?,
+            page_size: node.child("page-size")?,
+            total_items: node.child("total-items")?,
+            merchant_accounts: node.all("merchant-account")?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Pending,
+    Active,
+    Suspended,
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
+}
+
+impl From<String> for Status {
+    fn from(s: String) -> Status {
+        match s.as_ref() {
+            "pending" => Status::Pending,
+            "active" => Status::Active,
+            "suspended" => Status::Suspended,
+            _ => Status::Unknown(s),
+        }
+    }
+}
+
+impl From<Status> for String {
+    fn from(s: Status) -> String {
+        match s {
+            Status::Pending => String::from("pending"),
+            Status::Active => String::from("active"),
+            Status::Suspended => String::from("suspended"),
+            Status::Unknown(value) => value,
+        }
+    }
+}
+
+from_xml_via_string!(Status);
+
+/// Where a sub-merchant's funds are disbursed to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FundingDestination {
+    Bank,
+    Email,
+    MobilePhone,
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
+}
+
+impl From<String> for FundingDestination {
+    fn from(s: String) -> FundingDestination {
+        match s.as_ref() {
+            "bank" => FundingDestination::Bank,
+            "email" => FundingDestination::Email,
+            "mobile_phone" => FundingDestination::MobilePhone,
+            _ => FundingDestination::Unknown(s),
+        }
+    }
+}
+
+impl From<FundingDestination> for String {
+    fn from(d: FundingDestination) -> String {
+        match d {
+            FundingDestination::Bank => String::from("bank"),
+            FundingDestination::Email => String::from("email"),
+            FundingDestination::MobilePhone => String::from("mobile_phone"),
+            FundingDestination::Unknown(value) => value,
+        }
+    }
+}
+
+from_xml_via_string!(FundingDestination);
+xml_value_via_string!(FundingDestination);
+
+/// A request to create or update a sub-merchant account.
+///
+/// When creating an account, `master_merchant_account_id`, `individual`,
+/// `funding` and `tos_accepted` are required; `business` is only needed if
+/// the seller is a registered business.
+#[derive(Debug, Default)]
+pub struct Request {
+    /// The id to give the new account. If omitted, Braintree generates one.
+    pub id: Option<String>,
+    pub master_merchant_account_id: Option<String>,
+    /// Whether the seller has accepted Braintree's terms of service. Only
+    /// used when creating an account.
+    pub tos_accepted: Option<bool>,
+    pub individual: Option<Individual>,
+    pub business: Option<Business>,
+    pub funding: Option<Funding>,
+}
+
+impl ToXml for Request {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("id", &self.id);
+        w.field("master-merchant-account-id", &self.master_merchant_account_id);
+        w.field("tos-accepted", &self.tos_accepted);
+        w.optional_element("individual", &self.individual);
+        w.optional_element("business", &self.business);
+        w.optional_element("funding", &self.funding);
+    }
+}
+
+/// A request to create a merchant account for another presentment currency.
+#[derive(Debug, Default)]
+pub(crate) struct CurrencyRequest {
+    pub currency: String,
+    pub id: Option<String>,
+}
+
+impl ToXml for CurrencyRequest {
+    fn write_xml(&self, w: &mut Writer) {
+        w.value("currency", &self.currency);
+        w.field("id", &self.id);
+    }
+}
+
+/// The seller's personal details.
+#[derive(Debug, Default)]
+pub struct Individual {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub date_of_birth: Option<chrono::NaiveDate>,
+    pub ssn: Option<Secret>,
+    pub address: Option<Address>,
+}
+
+impl ToXml for Individual {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("first-name", &self.first_name);
+        w.field("last-name", &self.last_name);
+        w.field("email", &self.email);
+        w.field("phone", &self.phone);
+        w.field("date-of-birth", &self.date_of_birth);
+        w.field("ssn", &self.ssn);
+        w.optional_element("address", &self.address);
+    }
+}
+
+/// The seller's business details.
+#[derive(Debug, Default)]
+pub struct Business {
+    pub legal_name: Option<String>,
+    pub dba_name: Option<String>,
+    pub tax_id: Option<String>,
+    pub address: Option<Address>,
+}
+
+impl ToXml for Business {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("legal-name", &self.legal_name);
+        w.field("dba-name", &self.dba_name);
+        w.field("tax-id", &self.tax_id);
+        w.optional_element("address", &self.address);
+    }
+}
+
+/// How the seller will receive their funds.
+#[derive(Debug, Default)]
+pub struct Funding {
+    /// The descriptor that will appear on the seller's bank statement.
+    pub descriptor: Option<String>,
+    pub destination: Option<FundingDestination>,
+    /// Required when `destination` is `Email`.
+    pub email: Option<String>,
+    /// Required when `destination` is `MobilePhone`.
+    pub mobile_phone: Option<String>,
+    /// Required when `destination` is `Bank`.
+    pub account_number: Option<Secret>,
+    /// Required when `destination` is `Bank`.
+    pub routing_number: Option<Secret>,
+}
+
+impl ToXml for Funding {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("descriptor", &self.descriptor);
+        w.field("destination", &self.destination);
+        w.field("email", &self.email);
+        w.field("mobile-phone", &self.mobile_phone);
+        w.field("account-number", &self.account_number);
+        w.field("routing-number", &self.routing_number);
+    }
+}
+
+/// A seller's street address. This has a different shape than the
+/// `Address` used for customers and transactions.
+#[derive(Debug, Default)]
+pub struct Address {
+    pub street_address: Option<String>,
+    pub locality: Option<String>,
+    pub region: Option<String>,
+    pub postal_code: Option<String>,
+}
+
+impl ToXml for Address {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("street-address", &self.street_address);
+        w.field("locality", &self.locality);
+        w.field("region", &self.region);
+        w.field("postal-code", &self.postal_code);
+    }
+}
+
+impl FromXml for Address {
+    fn from_xml(node: &Node) -> Result<Address, DecodeError> {
+        Ok(Address{
+            street_address: node.child_opt("street-address")?,
+            locality: node.child_opt("locality")?,
+            region: node.child_opt("region")?,
+            postal_code: node.child_opt("postal-code")?,
+        })
+    }
+}
+
+/// The individual details returned for a merchant account. Sensitive values
+/// like the SSN are only returned in truncated form.
+#[derive(Debug)]
+pub struct IndividualDetails {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub date_of_birth: Option<chrono::NaiveDate>,
+    pub ssn_last_4: Option<String>,
+    pub address: Option<Address>,
+}
+
+impl FromXml for IndividualDetails {
+    fn from_xml(node: &Node) -> Result<IndividualDetails, DecodeError> {
+        Ok(IndividualDetails{
+            first_name: node.child_opt("first-name")?,
+            last_name: node.child_opt("last-name")?,
+            email: node.child_opt("email")?,
+            phone: node.child_opt("phone")?,
+            date_of_birth: node.child_opt("date-of-birth")?,
+            ssn_last_4: node.child_opt("ssn-last-4")?,
+            address: node.child_opt("address")?,
+        })
+    }
+}
+
+/// The business details returned for a merchant account.
+#[derive(Debug)]
+pub struct BusinessDetails {
+    pub legal_name: Option<String>,
+    pub dba_name: Option<String>,
+    pub tax_id: Option<String>,
+    pub address: Option<Address>,
+}
+
+impl FromXml for BusinessDetails {
+    fn from_xml(node: &Node) -> Result<BusinessDetails, DecodeError> {
+        Ok(BusinessDetails{
+            legal_name: node.child_opt("legal-name")?,
+            dba_name: node.child_opt("dba-name")?,
+            tax_id: node.child_opt("tax-id")?,
+            address: node.child_opt("address")?,
+        })
+    }
+}
+
+/// The funding details returned for a merchant account. Account numbers are
+/// only returned in truncated form.
+#[derive(Debug)]
+pub struct FundingDetails {
+    pub descriptor: Option<String>,
+    pub destination: Option<FundingDestination>,
+    pub email: Option<String>,
+    pub mobile_phone: Option<String>,
+    pub account_number_last_4: Option<String>,
+    pub routing_number: Option<Secret>,
+}
+
+impl FromXml for FundingDetails {
+    fn from_xml(node: &Node) -> Result<FundingDetails, DecodeError> {
+        Ok(FundingDetails{
+            descriptor: node.child_opt("descriptor")?,
+            destination: node.child_opt("destination")?,
+            email: node.child_opt("email")?,
+            mobile_phone: node.child_opt("mobile-phone")?,
+            account_number_last_4: node.child_opt("account-number-last-4")?,
+            routing_number: node.child_opt("routing-number")?,
+        })
+    }
+}
@@ -0,0 +1,287 @@
+//! Hooks for collecting metrics about API calls.
+//!
+//! A `Metrics` implementation, set with `BraintreeBuilder::metrics()`, is told
+//! about every HTTP request and every gateway operation, e.g. so that a
+//! dashboard can spot a rise in declines or server errors. With the `metrics`
+//! feature, `GlobalRecorder` reports them through the
+//! [`metrics`](https://docs.rs/metrics) crate.
+
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::transaction::{self, Transaction};
+
+/// Receives measurements of API calls. Both methods do nothing by default.
+pub trait Metrics: Send + Sync {
+    /// Called once per HTTP request, after any retries. Requests that are
+    /// refused by a guardrail, or that a handle in dry-run mode renders
+    /// instead of sending, aren't reported.
+    fn request(&self, _request: &Request) {}
+    /// Called once per gateway operation, e.g. `transaction.create`, which
+    /// may have sent several requests.
+    fn operation(&self, _operation: &Operation) {}
+}
+
+/// A completed HTTP request.
+#[derive(Debug)]
+pub struct Request<'a> {
+    pub method: &'a str,
+    /// The path template, e.g. `transactions/{id}/void`.
+    pub path: &'a str,
+    /// The response's status code, unless the request failed without one.
+    pub status: Option<u16>,
+    pub latency: Duration,
+    pub retries: u32,
+}
+
+/// A completed gateway operation.
+#[derive(Debug)]
+pub struct Operation<'a> {
+    /// The gateway and method, e.g. `transaction.create`.
+    pub name: &'static str,
+    pub latency: Duration,
+    pub error: Option<&'a Error>,
+    /// The resulting transaction's status, for operations on transactions.
+    /// Declined transactions are reported here too, even though they're
+    /// returned as errors.
+    pub transaction_status: Option<transaction::Status>,
+    pub processor_response_code: Option<String>,
+}
+
+impl<'a> Operation<'a> {
+    pub(crate) fn new<T: Outcome>(name: &'static str, latency: Duration, result: &'a Result<T, Error>) -> Operation<'a> {
+        let transaction = match *result {
+            Ok(ref value) => value.transaction().map(|transaction| (transaction.status.clone(), transaction.processor_response_code.clone())),
+            Err(Error::Api(ref response)) => response.transaction().map(|transaction| (transaction.status, transaction.processor_response_code)),
+            Err(_) => None,
+        };
+        let (transaction_status, processor_response_code) = match transaction {
+            Some((status, code)) => (Some(status), code),
+            None => (None, None),
+        };
+        Operation{
+            name,
+            latency,
+            error: result.as_ref().err(),
+            transaction_status,
+            processor_response_code,
+        }
+    }
+
+    /// `ok`, or the name of the `Error` variant, e.g. `api` or `http`.
+    pub fn outcome(&self) -> &'static str {
+        match self.error {
+            None => "ok",
+            Some(Error::Api(_)) => "api",
+            Some(Error::Http(_)) => "http",
+            Some(Error::TestOperationInProduction) => "test_operation_in_production",
+            Some(Error::InvalidAmount(_)) => "invalid_amount",
+            Some(Error::InvalidSignature) => "invalid_signature",
+            Some(Error::Decode(_)) => "decode",
+            Some(Error::Encode(_)) => "encode",
+            Some(Error::Setup(_)) => "setup",
+            Some(Error::Middleware(_)) => "middleware",
+            Some(Error::Guardrail(_)) => "guardrail",
+            Some(Error::DryRun(_)) => "dry_run",
+        }
+    }
+}
+
+/// The result of an operation, which may carry a transaction.
+pub(crate) trait Outcome {
+    fn transaction(&self) -> Option<&Transaction> {
+        None
+    }
+}
+
+impl Outcome for Transaction {
+    fn transaction(&self) -> Option<&Transaction> {
+        Some(self)
+    }
+}
+
+macro_rules! outcome {
+    ($($t:ty),*) => { $(impl Outcome for $t {})* }
+}
+
+outcome!(
+    bool,
+    Vec<Transaction>,
+    Vec<crate::CreditCardVerification>,
+    Vec<crate::LineItem>,
+    crate::client_token::ClientToken,
+    crate::CreditCardVerification,
+    crate::Customer,
+    crate::MerchantAccount,
+    crate::merchant_account::Page,
+    crate::oauth::Token,
+    crate::SettlementBatchSummary,
+    crate::Subscription
+);
+
+/// Reports to the [`metrics`](https://docs.rs/metrics) crate's global
+/// recorder:
+///
+/// * `braintree_requests_total` and `braintree_request_duration_seconds`,
+///   labeled by `method`, `path` and `status`;
+/// * `braintree_operations_total` and `braintree_operation_duration_seconds`,
+///   labeled by `operation` and `outcome`, and for transactions by
+///   `transaction_status` and `processor_response_code`.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlobalRecorder;
+
+#[cfg(feature = "metrics")]
+impl Metrics for GlobalRecorder {
+    fn request(&self, request: &Request) {
+        let status = request.status.map(|status| status.to_string()).unwrap_or_else(|| String::from("none"));
+        let labels = [
+            ("method", request.method.to_string()),
+            ("path", request.path.to_string()),
+            ("status", status),
+        ];
+        ::metrics::counter!("braintree_requests_total", &labels).increment(1);
+        ::metrics::histogram!("braintree_request_duration_seconds", &labels).record(request.latency.as_secs_f64());
+    }
+
+    fn operation(&self, operation: &Operation) {
+        let mut labels = vec![
+            ("operation", operation.name.to_string()),
+            ("outcome", operation.outcome().to_string()),
+        ];
+        ::metrics::histogram!("braintree_operation_duration_seconds", &labels).record(operation.latency.as_secs_f64());
+        if let Some(ref status) = operation.transaction_status {
+            labels.push(("transaction_status", String::from(status.clone())));
+        }
+        if let Some(ref code) = operation.processor_response_code {
+            labels.push(("processor_response_code", code.clone()));
+        }
+        ::metrics::counter!("braintree_operations_total", &labels).increment(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use hyper::status::StatusCode;
+
+    use super::{Metrics, Operation, Request};
+    use crate::retry::RetryPolicy;
+    use crate::transaction::{self, Status};
+    use crate::transport::{self, Stub, Transport};
+    use crate::Braintree;
+
+    #[derive(Debug, PartialEq)]
+    struct Recorded {
+        name: &'static str,
+        outcome: &'static str,
+        transaction_status: Option<Status>,
+        processor_response_code: Option<String>,
+    }
+
+    /// A request's method, path template, status and retry count.
+    type RecordedRequest = (String, String, Option<u16>, u32);
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        requests: Arc<Mutex<Vec<RecordedRequest>>>,
+        operations: Arc<Mutex<Vec<Recorded>>>,
+    }
+
+    impl Metrics for Recorder {
+        fn request(&self, request: &Request) {
+            self.requests.lock().unwrap().push((request.method.to_string(), request.path.to_string(), request.status, request.retries));
+        }
+
+        fn operation(&self, operation: &Operation) {
+            self.operations.lock().unwrap().push(Recorded{
+                name: operation.name,
+                outcome: operation.outcome(),
+                transaction_status: operation.transaction_status.clone(),
+                processor_response_code: operation.processor_response_code.clone(),
+            });
+        }
+    }
+
+    fn transaction(status: &str, code: &str) -> String {
+        format!(
+            "<transaction><id>abc</id><type>sale</type><amount>2001.00</amount><currency-iso-code>USD</currency-iso-code>\
+             <status>{}</status><processor-response-code>{}</processor-response-code>\
+             <created-at type=\"datetime\">2024-01-02T03:04:05Z</created-at><updated-at type=\"datetime\">2024-01-02T03:04:05Z</updated-at></transaction>",
+            status, code,
+        )
+    }
+
+    fn client<T: Transport + 'static>(transport: T, recorder: &Recorder) -> Braintree {
+        Braintree::builder()
+            .merchant_id("my_merchant_id")
+            .public_key("my_public_key")
+            .private_key("my_private_key")
+            .transport(transport)
+            .retry_policy(RetryPolicy::never())
+            .metrics(recorder.clone())
+            .build()
+            .unwrap()
+    }
+
+    fn sale() -> transaction::Request {
+        transaction::Request{amount: "2001.00".parse().unwrap(), ..Default::default()}
+    }
+
+    #[test]
+    fn records_successful_operations() {
+        let recorder = Recorder::default();
+        let bt = client(Stub::new(|_| (StatusCode::Created, transaction("authorized", "1000"))), &recorder);
+        bt.transaction().create(sale()).unwrap();
+
+        assert_eq!(*recorder.operations.lock().unwrap(), [Recorded{
+            name: "transaction.create",
+            outcome: "ok",
+            transaction_status: Some(Status::Authorized),
+            processor_response_code: Some(String::from("1000")),
+        }]);
+        assert_eq!(*recorder.requests.lock().unwrap(), [(String::from("POST"), String::from("transactions"), Some(201), 0)]);
+    }
+
+    #[test]
+    fn records_the_transaction_of_declines() {
+        let recorder = Recorder::default();
+        let bt = client(Stub::new(|_| {
+            let body = format!("<api-error-response><message>Insufficient Funds</message>{}</api-error-response>", transaction("processor_declined", "2001"));
+            (StatusCode::UnprocessableEntity, body)
+        }), &recorder);
+        assert!(bt.transaction().create(sale()).is_err());
+
+        assert_eq!(*recorder.operations.lock().unwrap(), [Recorded{
+            name: "transaction.create",
+            outcome: "api",
+            transaction_status: Some(Status::ProcessorDeclined),
+            processor_response_code: Some(String::from("2001")),
+        }]);
+        assert_eq!(recorder.requests.lock().unwrap()[0].2, Some(422));
+    }
+
+    struct Refused;
+
+    impl Transport for Refused {
+        fn send(&self, _request: transport::Request) -> hyper::Result<transport::Response> {
+            Err(hyper::Error::Io(std::io::Error::from(std::io::ErrorKind::ConnectionRefused)))
+        }
+    }
+
+    #[test]
+    fn records_transport_errors() {
+        let recorder = Recorder::default();
+        let bt = client(Refused, &recorder);
+        assert!(bt.transaction().find(String::from("abc")).is_err());
+
+        assert_eq!(*recorder.operations.lock().unwrap(), [Recorded{
+            name: "transaction.find",
+            outcome: "http",
+            transaction_status: None,
+            processor_response_code: None,
+        }]);
+        assert_eq!(*recorder.requests.lock().unwrap(), [(String::from("GET"), String::from("transactions/{id}"), None, 0)]);
+    }
+}
@@ -0,0 +1,159 @@
+//! Hooks that run around every request, e.g. to add headers, audit-log
+//! mutating calls or block operations.
+//!
+//! Middleware is added with `BraintreeBuilder::middleware()`. Each request is
+//! passed to every middleware's `on_request()` in the order they were added,
+//! after which the response is passed to `on_response()` in reverse order.
+//! Retried requests go through the chain again on every attempt.
+
+use hyper;
+use std::error::Error as StdError;
+
+use crate::transport;
+
+/// The error a middleware returns to abort a request. It's surfaced to the
+/// caller as `Error::Middleware`.
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+pub trait Middleware: Send + Sync {
+    /// Inspect or modify a request before it's sent, including its method,
+    /// URL, headers and body. Returning an error aborts the request without
+    /// sending it.
+    fn on_request(&self, _request: &mut transport::Request) -> Result<(), BoxError> {
+        Ok(())
+    }
+
+    /// Inspect or modify a response before the gateway decodes it.
+    /// Returning an error fails the call.
+    fn on_response(&self, _request: &transport::Request, _response: &mut Response) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+/// A response, with its body already decompressed.
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: hyper::status::StatusCode,
+    pub headers: hyper::header::Headers,
+    pub body: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use hyper::status::StatusCode;
+
+    use super::{BoxError, Middleware, Response};
+    use crate::builder::BraintreeBuilder;
+    use crate::error::Error;
+    use crate::retry::RetryPolicy;
+    use crate::transport::{Request, Stub};
+    use crate::Braintree;
+
+    fn builder(stub: &Stub) -> BraintreeBuilder {
+        Braintree::builder()
+            .merchant_id("my_merchant_id")
+            .public_key("my_public_key")
+            .private_key("my_private_key")
+            .retry_policy(RetryPolicy::never())
+            .transport(stub.clone())
+    }
+
+    fn found() -> Stub {
+        Stub::new(|_| (StatusCode::Ok, String::from(
+            "<transaction><id>abc</id><type>sale</type><amount>10.00</amount><currency-iso-code>USD</currency-iso-code>\
+             <status>authorized</status><created-at type=\"datetime\">2024-01-02T03:04:05Z</created-at>\
+             <updated-at type=\"datetime\">2024-01-02T03:04:05Z</updated-at></transaction>",
+        )))
+    }
+
+    /// Records when each hook runs, and tags requests with its name.
+    struct Log(&'static str, Arc<Mutex<Vec<String>>>);
+
+    impl Middleware for Log {
+        fn on_request(&self, request: &mut Request) -> Result<(), BoxError> {
+            self.1.lock().unwrap().push(format!("{} request", self.0));
+            request.headers.append_raw("X-Seen-By", self.0.as_bytes().to_vec());
+            Ok(())
+        }
+
+        fn on_response(&self, _request: &Request, _response: &mut Response) -> Result<(), BoxError> {
+            self.1.lock().unwrap().push(format!("{} response", self.0));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn runs_requests_in_order_and_responses_in_reverse() {
+        let stub = Stub::new(|_| (StatusCode::NotFound, String::new()));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let bt = builder(&stub).middleware(Log("outer", log.clone())).middleware(Log("inner", log.clone())).build().unwrap();
+        assert!(bt.transaction().find(String::from("abc")).is_err());
+
+        assert_eq!(*log.lock().unwrap(), ["outer request", "inner request", "inner response", "outer response"]);
+        let seen_by = stub.requests()[0].headers.get_raw("X-Seen-By").unwrap().to_vec();
+        assert_eq!(seen_by, [b"outer".to_vec(), b"inner".to_vec()]);
+    }
+
+    /// Replaces every response with a server error.
+    struct ServerError;
+
+    impl Middleware for ServerError {
+        fn on_response(&self, _request: &Request, response: &mut Response) -> Result<(), BoxError> {
+            response.status = StatusCode::InternalServerError;
+            response.body = b"<api-error-response><message>Rewritten</message></api-error-response>".to_vec();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn responses_can_be_rewritten() {
+        let bt = builder(&found()).build().unwrap();
+        assert_eq!(bt.transaction().find(String::from("abc")).unwrap().id, "abc");
+
+        let bt = builder(&found()).middleware(ServerError).build().unwrap();
+        match bt.transaction().find(String::from("abc")) {
+            Err(Error::Api(response)) => assert_eq!(response.message, "Rewritten"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    /// Fails the request or the response.
+    struct Abort {
+        on_request: bool,
+    }
+
+    impl Middleware for Abort {
+        fn on_request(&self, _request: &mut Request) -> Result<(), BoxError> {
+            match self.on_request {
+                true => Err(BoxError::from("blocked")),
+                false => Ok(()),
+            }
+        }
+
+        fn on_response(&self, _request: &Request, _response: &mut Response) -> Result<(), BoxError> {
+            Err(BoxError::from("rejected"))
+        }
+    }
+
+    #[test]
+    fn errors_abort_the_call() {
+        let stub = found();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let bt = builder(&stub).middleware(Abort{on_request: true}).middleware(Log("after", log.clone())).build().unwrap();
+        match bt.transaction().find(String::from("abc")) {
+            Err(Error::Middleware(err)) => assert_eq!(err.to_string(), "blocked"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert!(stub.requests().is_empty());
+        assert!(log.lock().unwrap().is_empty());
+
+        let bt = builder(&stub).middleware(Abort{on_request: false}).build().unwrap();
+        match bt.transaction().find(String::from("abc")) {
+            Err(Error::Middleware(err)) => assert_eq!(err.to_string(), "rejected"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(stub.requests().len(), 1);
+    }
+}
@@ -0,0 +1,219 @@
+//! Requests and responses for Braintree's OAuth flow, used by partners to act
+//! on behalf of the merchants that connect to them.
+
+use chrono;
+
+use crate::credentials::AccessToken;
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::{ToXml, Writer};
+use crate::error;
+use crate::secret::Secret;
+
+/// The parameters of a Braintree Auth connect URL, to which a merchant is
+/// sent to grant your application access to their account. After they
+/// approve, they're redirected to `redirect_uri` with a `code` that can be
+/// exchanged for an access token.
+#[derive(Debug, Default)]
+pub struct ConnectUrl {
+    /// Where to send the merchant once they've granted access.
+    pub redirect_uri: String,
+    /// A comma-separated list of the permissions requested, e.g.
+    /// `read_write`.
+    pub scope: String,
+    /// An opaque value that's passed back to `redirect_uri`, which should be
+    /// checked to protect against cross-site request forgery.
+    pub state: Option<String>,
+    /// Either `login` or `signup`.
+    pub landing_page: Option<String>,
+    /// Only allow merchants with an existing account to connect.
+    pub login_only: Option<bool>,
+    /// Payment methods to enable for the merchant, e.g. `credit_card` or
+    /// `paypal`.
+    pub payment_methods: Vec<String>,
+    /// Prefills the sign up form with details about the merchant.
+    pub user: Option<User>,
+    /// Prefills the sign up form with details about the merchant's business.
+    pub business: Option<Business>,
+}
+
+#[derive(Debug, Default)]
+pub struct User {
+    pub email: Option<String>,
+    pub country: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub phone: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Business {
+    pub name: Option<String>,
+    pub registered_as: Option<String>,
+    pub industry: Option<String>,
+    pub description: Option<String>,
+    pub street_address: Option<String>,
+    pub locality: Option<String>,
+    pub region: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+    pub website: Option<String>,
+}
+
+impl ConnectUrl {
+    /// The query parameters, in the order they're sent.
+    pub(crate) fn query_pairs(&self, client_id: &str) -> Vec<(String, String)> {
+        let mut pairs = vec![
+            (String::from("client_id"), String::from(client_id)),
+            (String::from("redirect_uri"), self.redirect_uri.clone()),
+            (String::from("scope"), self.scope.clone()),
+        ];
+        let mut push = |key: &str, value: &Option<String>| {
+            if let Some(ref value) = *value {
+                pairs.push((String::from(key), value.clone()));
+            }
+        };
+        push("state", &self.state);
+        push("landing_page", &self.landing_page);
+        push("login_only", &self.login_only.map(|login_only| login_only.to_string()));
+        if let Some(ref user) = self.user {
+            push("user[email]", &user.email);
+            push("user[country]", &user.country);
+            push("user[first_name]", &user.first_name);
+            push("user[last_name]", &user.last_name);
+            push("user[phone]", &user.phone);
+        }
+        if let Some(ref business) = self.business {
+            push("business[name]", &business.name);
+            push("business[registered_as]", &business.registered_as);
+            push("business[industry]", &business.industry);
+            push("business[description]", &business.description);
+            push("business[street_address]", &business.street_address);
+            push("business[locality]", &business.locality);
+            push("business[region]", &business.region);
+            push("business[postal_code]", &business.postal_code);
+            push("business[country]", &business.country);
+            push("business[website]", &business.website);
+        }
+        for payment_method in &self.payment_methods {
+            pairs.push((String::from("payment_methods[]"), payment_method.clone()));
+        }
+        pairs
+    }
+}
+
+/// A request for an access token, either from an authorization code or a
+/// refresh token.
+#[derive(Debug)]
+pub(crate) enum TokenRequest<'a> {
+    Code{code: &'a str, scope: Option<&'a str>},
+    RefreshToken{refresh_token: &'a str, scope: Option<&'a str>},
+}
+
+impl<'a> ToXml for TokenRequest<'a> {
+    fn write_xml(&self, w: &mut Writer) {
+        match *self {
+            TokenRequest::Code{code, scope} => {
+                w.value("grant-type", "authorization_code");
+                w.value("code", code);
+                w.field("scope", &scope.map(String::from));
+            },
+            TokenRequest::RefreshToken{refresh_token, scope} => {
+                w.value("grant-type", "refresh_token");
+                w.value("refresh-token", refresh_token);
+                w.field("scope", &scope.map(String::from));
+            },
+        }
+    }
+}
+
+/// The tokens granted by a merchant.
+#[derive(Debug)]
+pub struct Token {
+    pub access_token: Secret,
+    pub refresh_token: Option<Secret>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub token_type: Option<String>,
+    pub scope: Option<String>,
+}
+
+impl Token {
+    /// Credentials for acting on behalf of the merchant, to pass to
+    /// `BraintreeBuilder::credentials()`.
+    pub fn credentials(&self) -> error::Result<AccessToken> {
+        AccessToken::new(self.access_token.expose())
+    }
+}
+
+impl FromXml for Token {
+    fn from_xml(node: &Node) -> Result<Token, DecodeError> {
+        Ok(Token{
+            access_token: node.child::<Secret>("access-token").map(|token| Secret::credential(token.expose()))?,
+            refresh_token: node.child_opt::<Secret>("refresh-token")?.map(|token| Secret::credential(token.expose())),
+            expires_at: node.child_opt("expires-at")?,
+            token_type: node.child_opt("token-type")?,
+            scope: node.child_opt("scope")?,
+        })
+    }
+}
+
+/// The outcome of revoking an access token.
+pub(crate) struct RevokeResult {
+    pub success: bool,
+}
+
+impl FromXml for RevokeResult {
+    fn from_xml(node: &Node) -> Result<RevokeResult, DecodeError> {
+        Ok(RevokeResult{
+            success: node.child("success")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Business, ConnectUrl, User};
+    use crate::error::Error;
+    use crate::{Braintree, ClientCredentials};
+
+    #[test]
+    fn encodes_connect_urls() {
+        let bt = Braintree::builder()
+            .credentials(ClientCredentials::new("client_id$sandbox$abc", "client_secret$sandbox$def").unwrap())
+            .build()
+            .unwrap();
+        let url = bt.oauth().connect_url(&ConnectUrl{
+            redirect_uri: String::from("https://example.com/callback?from=braintree"),
+            scope: String::from("read_write,shared_vault_transactions"),
+            state: Some(String::from("a b&c=d")),
+            login_only: Some(true),
+            payment_methods: vec![String::from("credit_card"), String::from("paypal")],
+            user: Some(User{email: Some(String::from("owner+shop@example.com")), ..Default::default()}),
+            business: Some(Business{name: Some(String::from("Bob's Café")), ..Default::default()}),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(url, concat!(
+            "https://sandbox.braintreegateway.com/oauth/connect",
+            "?client_id=client_id%24sandbox%24abc",
+            "&redirect_uri=https%3A%2F%2Fexample.com%2Fcallback%3Ffrom%3Dbraintree",
+            "&scope=read_write%2Cshared_vault_transactions",
+            "&state=a+b%26c%3Dd",
+            "&login_only=true",
+            "&user%5Bemail%5D=owner%2Bshop%40example.com",
+            "&business%5Bname%5D=Bob%27s+Caf%C3%A9",
+            "&payment_methods%5B%5D=credit_card",
+            "&payment_methods%5B%5D=paypal",
+        ));
+    }
+
+    #[test]
+    fn client_credentials_are_only_for_oauth() {
+        let bt = Braintree::builder()
+            .credentials(ClientCredentials::new("client_id$sandbox$abc", "client_secret$sandbox$def").unwrap())
+            .build()
+            .unwrap();
+        assert!(matches!(bt.transaction().find(String::from("abc")), Err(Error::Setup(_))));
+
+        let bt = Braintree::new(crate::Environment::Sandbox, "merchant_id", "public_key", "private_key");
+        assert!(matches!(bt.oauth().connect_url(&Default::default()), Err(Error::Setup(_))));
+    }
+}
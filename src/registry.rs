@@ -0,0 +1,115 @@
+//! Handles for several merchants, sharing one transport.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, RwLock};
+
+use crate::builder::{BraintreeBuilder, Shared};
+use crate::credentials::Credentials;
+use crate::error;
+use crate::Braintree;
+
+/// A set of `Braintree` handles keyed by tenant, e.g. one per brand, each
+/// with its own credentials. Every handle shares the registry's transport,
+/// and so its connection pool, along with its timeouts, retry policy and
+/// other settings:
+///
+/// ```rust,no_run
+/// # use braintree::{ApiKey, Braintree, BraintreeRegistry, Environment};
+/// let registry = BraintreeRegistry::new(Braintree::builder()).unwrap();
+/// registry.insert("brand-a", ApiKey::new(Environment::Sandbox, "<merchant_id>", "<public_key>", "<private_key>")).unwrap();
+///
+/// let bt = registry.get(&"brand-a").expect("unknown tenant");
+/// let client_token = bt.client_token().generate(Default::default());
+/// ```
+///
+/// Inserting credentials for an existing tenant replaces its handle, e.g.
+/// while rotating keys. Requests already in flight finish with the handle
+/// they started with; later calls to `get()` return the new one.
+pub struct BraintreeRegistry<K = String> {
+    shared: Shared,
+    clients: RwLock<HashMap<K, Arc<Braintree>>>,
+}
+
+impl<K: Eq + Hash> BraintreeRegistry<K> {
+    /// Create an empty registry. Any credentials set on the builder are
+    /// ignored; each tenant's are given to `insert()`.
+    pub fn new(builder: BraintreeBuilder) -> error::Result<BraintreeRegistry<K>> {
+        Ok(BraintreeRegistry{
+            shared: builder.shared()?,
+            clients: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Add a tenant, or swap the credentials of an existing one. Returns the
+    /// handle that was replaced, if any.
+    pub fn insert<C: Credentials + 'static>(&self, tenant: K, credentials: C) -> error::Result<Option<Arc<Braintree>>> {
+        let client = Arc::new(self.shared.client(Box::new(credentials))?);
+        Ok(self.clients.write().unwrap().insert(tenant, client))
+    }
+
+    /// The handle for a tenant.
+    pub fn get(&self, tenant: &K) -> Option<Arc<Braintree>> {
+        self.clients.read().unwrap().get(tenant).cloned()
+    }
+
+    /// Remove a tenant, returning its handle.
+    pub fn remove(&self, tenant: &K) -> Option<Arc<Braintree>> {
+        self.clients.write().unwrap().remove(tenant)
+    }
+
+    pub fn contains(&self, tenant: &K) -> bool {
+        self.clients.read().unwrap().contains_key(tenant)
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.read().unwrap().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::BraintreeRegistry;
+    use crate::{ApiKey, Braintree, Environment};
+
+    fn registry() -> BraintreeRegistry<&'static str> {
+        let registry = BraintreeRegistry::new(Braintree::builder()).unwrap();
+        registry.insert("brand-a", ApiKey::new(Environment::Sandbox, "merchant_a", "public_a", "private_a")).unwrap();
+        registry.insert("brand-b", ApiKey::new(Environment::Production, "merchant_b", "public_b", "private_b")).unwrap();
+        registry
+    }
+
+    #[test]
+    fn handles_share_one_transport() {
+        let registry = registry();
+        let (a, b) = (registry.get(&"brand-a").unwrap(), registry.get(&"brand-b").unwrap());
+        assert_eq!(a.merchant_id(), Some("merchant_a"));
+        assert_eq!(b.merchant_id(), Some("merchant_b"));
+        assert_eq!(b.environment(), Environment::Production);
+        assert!(Arc::ptr_eq(&a.transport, &b.transport));
+    }
+
+    #[test]
+    fn insert_swaps_handles() {
+        let registry = registry();
+        let old = registry.get(&"brand-a").unwrap();
+
+        let replaced = registry.insert("brand-a", ApiKey::new(Environment::Sandbox, "merchant_a2", "public_a2", "private_a2")).unwrap();
+        assert!(Arc::ptr_eq(&replaced.unwrap(), &old));
+        let new = registry.get(&"brand-a").unwrap();
+        assert_eq!(new.merchant_id(), Some("merchant_a2"));
+        assert!(Arc::ptr_eq(&new.transport, &old.transport));
+        assert_eq!(old.merchant_id(), Some("merchant_a"));
+        assert_eq!(registry.len(), 2);
+
+        assert!(registry.remove(&"brand-b").is_some());
+        assert!(!registry.contains(&"brand-b"));
+        assert!(registry.get(&"brand-b").is_none());
+    }
+}
@@ -0,0 +1,175 @@
+//! Automatic retries for failed requests.
+//!
+//! Only requests that are safe to repeat are retried: `GET`s, such as
+//! `find()`, and searches. Requests that create or modify something are
+//! never sent twice, with the exception of connection failures, where the
+//! request is known not to have reached Braintree.
+
+use fastrand;
+use hyper;
+use std::io;
+use std::time::Duration;
+
+/// How failed requests are retried.
+///
+/// ```rust,no_run
+/// # use braintree::{Braintree, Environment};
+/// # use braintree::retry::RetryPolicy;
+/// # use std::time::Duration;
+/// let mut bt = Braintree::new(Environment::Sandbox, "<merchant_id>", "<public_key>", "<private_key>");
+/// bt.set_retry_policy(RetryPolicy{
+///     max_attempts: 5,
+///     initial_backoff: Duration::from_millis(250),
+///     ..Default::default()
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request is sent, including the first
+    /// attempt. A value of 1 disables retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry. Each subsequent retry waits twice
+    /// as long as the one before, up to `max_backoff`.
+    pub initial_backoff: Duration,
+    /// The longest delay between two attempts.
+    pub max_backoff: Duration,
+    /// When creating a transaction fails in a way that leaves it unclear
+    /// whether Braintree received it, e.g. the connection dropped before a
+    /// response arrived, look the transaction up by its `order_id` instead of
+    /// returning the error. This only applies to requests with an `order_id`,
+    /// which must be unique for the lookup to be meaningful. Only a
+    /// transaction with the same amount, created since the call started, is
+    /// returned.
+    pub recover_by_order_id: bool,
+}
+
+impl RetryPolicy {
+    /// A policy that sends every request exactly once.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy{max_attempts: 1, ..Default::default()}
+    }
+
+    /// The delay before the given retry, counting from 1. Half of the delay
+    /// is randomized so that clients that failed together don't all retry
+    /// at the same moment.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+        let delay = self.initial_backoff.checked_mul(factor).unwrap_or(self.max_backoff).min(self.max_backoff);
+        let half = delay / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy{
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            recover_by_order_id: false,
+        }
+    }
+}
+
+/// Whether a request can be sent again without side effects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Safety {
+    /// Reads, including searches sent as `POST`s.
+    Idempotent,
+    /// Anything that creates or modifies a resource.
+    Unsafe,
+}
+
+/// Whether a failed attempt should be retried.
+pub(crate) fn should_retry_error(err: &hyper::Error, safety: Safety) -> bool {
+    match *err {
+        // The connection was never established, so nothing was sent.
+        hyper::Error::Io(ref err) if err.kind() == io::ErrorKind::ConnectionRefused => true,
+        hyper::Error::Io(_) => safety == Safety::Idempotent,
+        _ => false,
+    }
+}
+
+/// Whether a response indicates a transient failure worth retrying.
+pub(crate) fn should_retry_status(status: hyper::status::StatusCode, safety: Safety) -> bool {
+    safety == Safety::Idempotent
+        && (status.is_server_error() || status == hyper::status::StatusCode::TooManyRequests)
+}
+
+/// Whether a failure to create a resource leaves its outcome unknown, i.e.
+/// the request may have been processed even though no response came back.
+pub(crate) fn is_ambiguous(err: &hyper::Error) -> bool {
+    match *err {
+        hyper::Error::Io(ref err) => err.kind() != io::ErrorKind::ConnectionRefused,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::status::StatusCode;
+    use std::io;
+    use std::time::Duration;
+
+    use super::{is_ambiguous, should_retry_error, should_retry_status, RetryPolicy, Safety};
+
+    fn io_error(kind: io::ErrorKind) -> hyper::Error {
+        hyper::Error::Io(io::Error::from(kind))
+    }
+
+    #[test]
+    fn retries_errors_by_safety() {
+        let refused = io_error(io::ErrorKind::ConnectionRefused);
+        let reset = io_error(io::ErrorKind::ConnectionReset);
+        let timed_out = io_error(io::ErrorKind::TimedOut);
+        let invalid = hyper::Error::Status;
+
+        assert!(should_retry_error(&refused, Safety::Idempotent));
+        assert!(should_retry_error(&refused, Safety::Unsafe));
+        assert!(should_retry_error(&reset, Safety::Idempotent));
+        assert!(!should_retry_error(&reset, Safety::Unsafe));
+        assert!(should_retry_error(&timed_out, Safety::Idempotent));
+        assert!(!should_retry_error(&timed_out, Safety::Unsafe));
+        assert!(!should_retry_error(&invalid, Safety::Idempotent));
+        assert!(!should_retry_error(&invalid, Safety::Unsafe));
+    }
+
+    #[test]
+    fn retries_statuses_by_safety() {
+        for status in &[StatusCode::InternalServerError, StatusCode::BadGateway, StatusCode::ServiceUnavailable, StatusCode::TooManyRequests] {
+            assert!(should_retry_status(*status, Safety::Idempotent), "{}", status);
+            assert!(!should_retry_status(*status, Safety::Unsafe), "{}", status);
+        }
+        for status in &[StatusCode::Ok, StatusCode::Created, StatusCode::BadRequest, StatusCode::NotFound, StatusCode::UnprocessableEntity] {
+            assert!(!should_retry_status(*status, Safety::Idempotent), "{}", status);
+            assert!(!should_retry_status(*status, Safety::Unsafe), "{}", status);
+        }
+    }
+
+    #[test]
+    fn only_unanswered_requests_are_ambiguous() {
+        assert!(!is_ambiguous(&io_error(io::ErrorKind::ConnectionRefused)));
+        assert!(is_ambiguous(&io_error(io::ErrorKind::ConnectionReset)));
+        assert!(is_ambiguous(&io_error(io::ErrorKind::TimedOut)));
+        assert!(!is_ambiguous(&hyper::Error::Status));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = RetryPolicy{
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let within = |retry: u32, max: Duration| {
+            let delay = policy.backoff(retry);
+            assert!(delay >= max / 2 && delay <= max, "retry {}: {:?} not within {:?}", retry, delay, max);
+        };
+        within(1, Duration::from_millis(100));
+        within(2, Duration::from_millis(200));
+        within(3, Duration::from_millis(400));
+        within(5, Duration::from_secs(1));
+        within(40, Duration::from_secs(1));
+        within(u32::MAX, Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::{ToXml, Writer, XmlValue};
+
+/// A search criterion that matches against a text field.
+#[derive(Clone, Debug)]
+pub enum Text {
+    Is(String),
+    IsNot(String),
+    StartsWith(String),
+    EndsWith(String),
+    Contains(String),
+}
+
+impl ToXml for Text {
+    fn write_xml(&self, w: &mut Writer) {
+        match *self {
+            Text::Is(ref value) => w.value("is", value),
+            Text::IsNot(ref value) => w.value("is-not", value),
+            Text::StartsWith(ref value) => w.value("starts-with", value),
+            Text::EndsWith(ref value) => w.value("ends-with", value),
+            Text::Contains(ref value) => w.value("contains", value),
+        }
+    }
+}
+
+/// A search criterion that matches values falling within an inclusive range,
+/// such as a range of timestamps or amounts. Leaving either end as `None`
+/// leaves that side of the range open.
+#[derive(Clone, Debug)]
+pub struct Range<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T> Default for Range<T> {
+    fn default() -> Range<T> {
+        Range{min: None, max: None}
+    }
+}
+
+impl<T: XmlValue> ToXml for Range<T> {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("min", &self.min);
+        w.field("max", &self.max);
+    }
+}
+
+/// The result of an `advanced_search_ids` call: the ids of every matching
+/// record, along with the number of records that can be fetched at once.
+#[derive(Debug)]
+pub(crate) struct SearchResults {
+    pub page_size: usize,
+    pub ids: Vec<String>,
+}
+
+impl FromXml for SearchResults {
+    fn from_xml(node: &Node) -> Result<SearchResults, DecodeError> {
+        Ok(SearchResults{
+            page_size: node.child("page-size")?,
+            ids: node.child("ids")?,
+        })
+    }
+}
+
+/// A page of records returned by an advanced search, which mixes the
+/// records with paging metadata.
+pub(crate) struct Collection<T>(pub Vec<T>);
+
+impl<T: FromXml> FromXml for Collection<T> {
+    fn from_xml(node: &Node) -> Result<Collection<T>, DecodeError> {
+        node.children()
+            .filter(|child| !matches!(child.element().tag().name(), "current-page-number" | "page-size" | "total-items"))
+            .map(|child| T::from_xml(&child))
+            .collect::<Result<Vec<T>, DecodeError>>()
+            .map(Collection)
+    }
+}
+
+/// The request body used to fetch a page of records by id.
+pub(crate) struct Ids<'a>(pub &'a [String]);
+
+impl<'a> ToXml for Ids<'a> {
+    fn write_xml(&self, w: &mut Writer) {
+        w.values("ids", self.0);
+    }
+}
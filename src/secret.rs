@@ -0,0 +1,162 @@
+//! A wrapper for card data and credentials that keeps them out of logs.
+
+use std::fmt;
+use zeroize::Zeroize;
+
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::XmlValue;
+
+/// A sensitive value, such as a card number, CVV, payment method nonce or
+/// private key.
+///
+/// Its `Debug` and `Display` output masks all but the last four characters
+/// of card data, e.g. `************1111`, and values of eight characters or
+/// fewer, such as CVVs, are masked entirely. Credentials created with
+/// `credential()` are always masked entirely. Use `expose()` to get at the
+/// value itself.
+///
+/// The value's memory is zeroed when the `Secret` is dropped. Copies made
+/// while a request is sent, e.g. into its headers or XML body, are not.
+#[derive(Clone, Default)]
+pub struct Secret {
+    value: String,
+    credential: bool,
+}
+
+impl Secret {
+    pub fn new<S: Into<String>>(value: S) -> Secret {
+        Secret{value: value.into(), credential: false}
+    }
+
+    /// A credential, such as a private key or access token, which is masked
+    /// entirely.
+    pub fn credential<S: Into<String>>(value: S) -> Secret {
+        Secret{value: value.into(), credential: true}
+    }
+
+    /// The unmasked value.
+    pub fn expose(&self) -> &str {
+        &self.value
+    }
+
+    fn masked(&self) -> String {
+        if self.credential {
+            return String::from(CREDENTIAL_MASK);
+        }
+        let chars = self.value.chars().count();
+        let shown = if chars > 8 { 4 } else { 0 };
+        self.value.chars()
+            .enumerate()
+            .map(|(i, c)| if i < chars - shown { '*' } else { c })
+            .collect()
+    }
+}
+
+/// What a credential is shown as, whatever its length.
+const CREDENTIAL_MASK: &str = "********";
+
+impl PartialEq for Secret {
+    fn eq(&self, other: &Secret) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for Secret {}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Secret {
+        Secret::new(value)
+    }
+}
+
+impl<'a> From<&'a str> for Secret {
+    fn from(value: &'a str) -> Secret {
+        Secret::new(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret({:?})", self.masked())
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.masked())
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl XmlValue for Secret {
+    const TYPE: Option<&'static str> = None;
+    fn to_text(&self) -> String { self.value.clone() }
+}
+
+impl FromXml for Secret {
+    fn from_xml(node: &Node) -> Result<Secret, DecodeError> {
+        Ok(Secret::new(node.text()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Secret;
+    use crate::credit_card::CreditCard;
+
+    #[test]
+    fn masks_all_but_the_last_four_characters() {
+        let number = Secret::from("4111111111111111");
+        assert_eq!(format!("{}", number), "************1111");
+        assert_eq!(format!("{:?}", number), "Secret(\"************1111\")");
+        assert_eq!(number.expose(), "4111111111111111");
+    }
+
+    #[test]
+    fn masks_short_values_entirely() {
+        assert_eq!(format!("{}", Secret::from("123")), "***");
+        assert_eq!(format!("{}", Secret::from("")), "");
+    }
+
+    #[test]
+    fn masks_credentials_entirely() {
+        let key = Secret::credential("0123456789abcdef0123456789abcdef");
+        assert_eq!(format!("{}", key), "********");
+        assert_eq!(format!("{:?}", key), "Secret(\"********\")");
+        assert_eq!(key, Secret::from("0123456789abcdef0123456789abcdef"));
+    }
+
+    #[test]
+    fn credentials_are_masked_in_debug_output() {
+        use crate::credentials::{AccessToken, ApiKey, ClientCredentials};
+        use crate::Environment;
+
+        let output = format!("{:?}", ApiKey::new(Environment::Sandbox, "merchant_id", "public_key", "private_key_1234"));
+        assert!(!output.contains("1234"), "{}", output);
+        assert!(!output.contains("Basic"), "{}", output);
+
+        let output = format!("{:?}", ClientCredentials::new("client_id$sandbox$abc", "client_secret$sandbox$5678").unwrap());
+        assert!(!output.contains("5678"), "{}", output);
+
+        let output = format!("{:?}", AccessToken::new("access_token$sandbox$merchant_id$9012").unwrap());
+        assert!(!output.contains("9012"), "{}", output);
+    }
+
+    #[test]
+    fn card_data_is_masked_in_debug_output() {
+        let card = CreditCard{
+            number: Some(Secret::from("4111111111111111")),
+            cvv: Some(Secret::from("737")),
+            ..Default::default()
+        };
+        let output = format!("{:?}", card);
+        assert!(!output.contains("4111111111111111"));
+        assert!(!output.contains("737"));
+        assert!(output.contains("************1111"));
+    }
+}
@@ -0,0 +1,177 @@
+use chrono;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::amount::{self, Amount};
+use crate::credit_card;
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::transaction;
+use crate::encode::{ToXml, Writer};
+
+/// A request for the summary of transactions settled on a given day.
+#[derive(Debug, Default)]
+pub(crate) struct Request {
+    pub settlement_date: chrono::NaiveDate,
+    pub group_by_custom_field: Option<String>,
+}
+
+impl ToXml for Request {
+    fn write_xml(&self, w: &mut Writer) {
+        w.value("settlement-date", &self.settlement_date);
+        w.field("group-by-custom-field", &self.group_by_custom_field);
+    }
+}
+
+/// A summary of the transactions settled in a single day's batch, with one
+/// record per combination of card type, merchant account and kind.
+#[derive(Debug)]
+pub struct SettlementBatchSummary {
+    pub records: Vec<Record>,
+}
+
+impl FromXml for SettlementBatchSummary {
+    fn from_xml(node: &Node) -> Result<SettlementBatchSummary, DecodeError> {
+        Ok(SettlementBatchSummary{
+            records: node.child("records")?,
+        })
+    }
+}
+
+impl SettlementBatchSummary {
+    /// Renders the records as CSV, with a header row. Custom field columns
+    /// are appended after the standard ones in alphabetical order, and
+    /// credits are negative, as Braintree reports them.
+    pub fn to_csv(&self) -> String {
+        let mut custom_fields: Vec<&String> = self.records.iter().flat_map(|r| r.custom_fields.keys()).collect();
+        custom_fields.sort();
+        custom_fields.dedup();
+
+        let mut s = String::from("card_type,merchant_account_id,kind,count,amount_settled");
+        for field in &custom_fields {
+            write!(s, ",{}", csv_escape(field)).unwrap();
+        }
+        s.push('\n');
+
+        for record in &self.records {
+            write!(s, "{},{},{},{},{}{}",
+                   csv_escape(&String::from(record.card_type.clone())),
+                   csv_escape(&record.merchant_account_id),
+                   csv_escape(&String::from(record.kind.clone())),
+                   record.count,
+                   if record.is_credit { "-" } else { "" },
+                   record.amount_settled).unwrap();
+            for field in &custom_fields {
+                write!(s, ",{}", csv_escape(record.custom_fields.get(*field).map(String::as_str).unwrap_or(""))).unwrap();
+            }
+            s.push('\n');
+        }
+        s
+    }
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote or line break.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+#[derive(Debug)]
+pub struct Record {
+    pub card_type: credit_card::CardType,
+    pub merchant_account_id: String,
+    pub kind: transaction::Type,
+    pub count: u32,
+    /// The total settled, as an absolute value. Braintree reports credits
+    /// as negative amounts, which is recorded in `is_credit`.
+    pub amount_settled: Amount,
+    /// Whether Braintree reported `amount_settled` as negative, i.e. money
+    /// moved back to customers. Subtract these records when totalling.
+    pub is_credit: bool,
+    /// The value of the custom field the summary was grouped by, keyed by
+    /// that field's name. Empty unless `group_by_custom_field` was given.
+    pub custom_fields: HashMap<String, String>,
+}
+
+impl FromXml for Record {
+    fn from_xml(node: &Node) -> Result<Record, DecodeError> {
+        let mut custom_fields = HashMap::new();
+        for child in node.children() {
+            match child.element().tag().name() {
+                "card-type" | "merchant-account-id" | "kind" | "count" | "amount-settled" => (),
+                tag => { custom_fields.insert(tag.to_string(), child.text()?.to_string()); },
+            }
+        }
+        let (amount_settled, is_credit) = node.child_with("amount-settled", |n| {
+            let text = n.text()?;
+            let amount = amount::parse_response(text.trim_start_matches('-')).map_err(|_| n.invalid("decimal"))?;
+            Ok((amount, text.starts_with('-')))
+        })?;
+        Ok(Record{
+            card_type: node.child("card-type")?,
+            merchant_account_id: node.child("merchant-account-id")?,
+            kind: node.child("kind")?,
+            count: node.child("count")?,
+            amount_settled,
+            is_credit,
+            custom_fields,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SettlementBatchSummary;
+    use crate::credit_card::CardType;
+    use crate::decode::from_reader;
+    use crate::transaction;
+
+    fn record(card_type: &str, merchant_account_id: &str, kind: &str, count: u32, amount: &str, custom_field: Option<&str>) -> String {
+        format!(
+            "<record><card-type>{}</card-type><merchant-account-id>{}</merchant-account-id><kind>{}</kind>\
+             <count>{}</count><amount-settled>{}</amount-settled>{}</record>",
+            card_type, merchant_account_id, kind, count, amount,
+            custom_field.map(|value| format!("<store-me>{}</store-me>", value)).unwrap_or_default(),
+        )
+    }
+
+    fn summary(records: &[String]) -> SettlementBatchSummary {
+        let xml = format!("<settlement-batch-summary><records type=\"array\">{}</records></settlement-batch-summary>", records.concat());
+        from_reader(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_sign_of_credits() {
+        let summary = summary(&[
+            record("Visa", "store_usd", "sale", 2, "30.00", None),
+            record("Visa", "store_usd", "credit", 1, "-10.00", None),
+        ]);
+        let sale = &summary.records[0];
+        assert_eq!((sale.card_type.clone(), sale.kind.clone(), sale.count), (CardType::Visa, transaction::Type::Sale, 2));
+        assert_eq!(sale.amount_settled, "30.00".parse().unwrap());
+        assert!(!sale.is_credit);
+        let credit = &summary.records[1];
+        assert_eq!(credit.kind, transaction::Type::Credit);
+        assert_eq!(credit.amount_settled, "10.00".parse().unwrap());
+        assert!(credit.is_credit);
+        assert!(credit.custom_fields.is_empty());
+
+        assert_eq!(summary.to_csv(), "card_type,merchant_account_id,kind,count,amount_settled\n\
+                                      Visa,store_usd,sale,2,30.00\n\
+                                      Visa,store_usd,credit,1,-10.00\n");
+    }
+
+    #[test]
+    fn adds_custom_field_columns_and_quotes_values() {
+        let summary = summary(&[
+            record("American Express", "store, \"main\"", "sale", 1, "5.00", Some("Main Street")),
+            record("MasterCard", "store_usd", "sale", 3, "12.50", None),
+        ]);
+        assert_eq!(summary.records[0].custom_fields["store-me"], "Main Street");
+        assert_eq!(summary.to_csv(), "card_type,merchant_account_id,kind,count,amount_settled,store-me\n\
+                                      American Express,\"store, \"\"main\"\"\",sale,1,5.00,Main Street\n\
+                                      MasterCard,store_usd,sale,3,12.50,\n");
+    }
+}
@@ -1,10 +1,8 @@
-use elementtree;
-use std::convert::From;
-use std::io::Read;
-use std::fmt::Write;
-use xml;
+use chrono;
 
-use crate::ToXml;
+use crate::amount::Amount;
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::encode::{ToXml, Writer};
 
 /// A request to retrieve a new client token.
 #[derive(Debug, Default)]
@@ -17,22 +15,24 @@ pub struct Request {
     /// unless you pass a payment_method_nonce instead, which can be done
     /// under certain circumstances
     pub payment_method_token: Option<String>,
+    /// The date the subscription will first be billed. If omitted, billing
+    /// starts immediately.
+    pub first_billing_date: Option<chrono::NaiveDate>,
+    /// Overrides the price of the plan for this subscription.
+    pub price: Option<Amount>,
 }
 
 impl ToXml for Request {
-    fn to_xml(&self, name: Option<&str>) -> String {
-        let name = xml::escape(&name.unwrap_or("subscription"));
-        let mut s = String::new();
-        write!(s, "<{}>", name).unwrap();
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("plan-id", &self.plan_id);
+        w.field("payment-method-token", &self.payment_method_token);
+        w.field("first-billing-date", &self.first_billing_date);
+        w.field("price", &self.price);
 
-        write_xml!(s, "plan-id", self.plan_id);
-        write_xml!(s, "payment-method-token", self.payment_method_token);
-
-        write!(s, "<options>" ).unwrap();
-        write_xml_type!(s, "start-immediately", "boolean", Some(true));
-        write!(s, "</options>" ).unwrap();
-        write!(s, "</{}>", name).unwrap();
-        s
+        // Braintree rejects `start-immediately` alongside a first billing date.
+        if self.first_billing_date.is_none() {
+            w.nested("options", |w| w.value("start-immediately", &true));
+        }
     }
 }
 
@@ -42,23 +42,40 @@ pub struct Subscription {
     pub id: String,
     pub plan_id: String,
     pub payment_method_token: String,
+    pub price: Option<Amount>,
     pub status: String,
-    pub billing_period_start_date: String,
-    pub billing_period_end_date: String,
-    pub created_at: String,
+    /// Not set until the subscription's first billing date.
+    pub billing_period_start_date: Option<chrono::NaiveDate>,
+    /// Not set until the subscription's first billing date.
+    pub billing_period_end_date: Option<chrono::NaiveDate>,
+    pub first_billing_date: Option<chrono::NaiveDate>,
+    pub next_billing_date: Option<chrono::NaiveDate>,
+    pub paid_through_date: Option<chrono::NaiveDate>,
+    /// How many days the subscription is past due, if it is.
+    pub days_past_due: Option<u32>,
+    /// The amount that is past due.
+    pub balance: Option<Amount>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
-impl From<Box<dyn Read>> for Subscription {
-    fn from(xml: Box<dyn Read>) -> Subscription {
-        let root = elementtree::Element::from_reader(xml).unwrap();
-        Subscription{
-            id: String::from(root.find("id").unwrap().text()),
-            plan_id: String::from(root.find("plan-id").unwrap().text()),
-            payment_method_token: String::from(root.find("payment-method-token").unwrap().text()),
-            status: String::from(root.find("status").unwrap().text()),
-            billing_period_start_date: String::from(root.find("billing-period-start-date").unwrap().text()),
-            billing_period_end_date: String::from(root.find("billing-period-end-date").unwrap().text()),
-            created_at: String::from(root.find("created-at").unwrap().text()),
-        }
+impl FromXml for Subscription {
+    fn from_xml(node: &Node) -> Result<Subscription, DecodeError> {
+        Ok(Subscription{
+            id: node.child("id")?,
+            plan_id: node.child("plan-id")?,
+            payment_method_token: node.child("payment-method-token")?,
+            price: node.child_opt("price")?,
+            status: node.child("status")?,
+            billing_period_start_date: node.child_opt("billing-period-start-date")?,
+            billing_period_end_date: node.child_opt("billing-period-end-date")?,
+            first_billing_date: node.child_opt("first-billing-date")?,
+            next_billing_date: node.child_opt("next-billing-date")?,
+            paid_through_date: node.child_opt("paid-through-date")?,
+            days_past_due: node.child_opt("days-past-due")?,
+            balance: node.child_opt("balance")?,
+            created_at: node.child("created-at")?,
+            updated_at: node.child("updated-at")?,
+        })
     }
 }
@@ -0,0 +1,198 @@
+//! Values that trigger specific behavior in the sandbox: fake nonces, test
+//! card numbers and magic amounts.
+//!
+//! ```rust
+//! # use braintree::{test_values, transaction};
+//! # let _ =
+//! transaction::Request{
+//!     amount: test_values::amounts::processor_declined(2001),
+//!     credit_card: Some(test_values::card(test_values::card_numbers::VISA)),
+//!     ..Default::default()
+//! }
+//! # ;
+//! ```
+//!
+//! See Braintree's [testing
+//! reference](https://developer.paypal.com/braintree/docs/reference/general/testing)
+//! for the full list. None of these values work in production.
+
+use chrono::{self, Datelike};
+
+use crate::credit_card::CreditCard;
+use crate::secret::Secret;
+
+/// Nonces that stand in for a payment method collected by a client SDK.
+pub mod nonces {
+    /// A valid Visa card.
+    pub const VALID: &str = "fake-valid-nonce";
+    pub const VALID_VISA: &str = "fake-valid-visa-nonce";
+    pub const VALID_AMEX: &str = "fake-valid-amex-nonce";
+    pub const VALID_MASTERCARD: &str = "fake-valid-mastercard-nonce";
+    pub const VALID_DISCOVER: &str = "fake-valid-discover-nonce";
+    pub const VALID_JCB: &str = "fake-valid-jcb-nonce";
+    pub const VALID_MAESTRO: &str = "fake-valid-maestro-nonce";
+    pub const VALID_DINERS_CLUB: &str = "fake-valid-dinersclub-nonce";
+    /// A card that the processor declines when it's verified.
+    pub const PROCESSOR_DECLINED_VISA: &str = "fake-processor-declined-visa-nonce";
+    pub const PROCESSOR_DECLINED_MASTERCARD: &str = "fake-processor-declined-mastercard-nonce";
+    pub const PROCESSOR_DECLINED_AMEX: &str = "fake-processor-declined-amex-nonce";
+    pub const PROCESSOR_DECLINED_DISCOVER: &str = "fake-processor-declined-discover-nonce";
+    /// A card whose verification fails with a processor error.
+    pub const PROCESSOR_FAILURE_JCB: &str = "fake-processor-failure-jcb-nonce";
+    /// A card number that fails the Luhn check.
+    pub const LUHN_INVALID: &str = "fake-luhn-invalid-nonce";
+    /// A nonce that has already been used.
+    pub const CONSUMED: &str = "fake-consumed-nonce";
+    /// A card that the gateway rejects for fraud.
+    pub const GATEWAY_REJECTED_FRAUD: &str = "fake-gateway-rejected-fraud-nonce";
+    pub const PAYPAL_ONE_TIME: &str = "fake-paypal-one-time-nonce";
+    pub const PAYPAL_BILLING_AGREEMENT: &str = "fake-paypal-billing-agreement-nonce";
+    pub const PAYPAL_FUTURE: &str = "fake-paypal-future-nonce";
+    pub const VENMO_ACCOUNT: &str = "fake-venmo-account-nonce";
+    pub const APPLE_PAY_VISA: &str = "fake-apple-pay-visa-nonce";
+    pub const APPLE_PAY_MASTERCARD: &str = "fake-apple-pay-mastercard-nonce";
+    pub const APPLE_PAY_AMEX: &str = "fake-apple-pay-amex-nonce";
+    pub const GOOGLE_PAY: &str = "fake-android-pay-nonce";
+    pub const GOOGLE_PAY_VISA: &str = "fake-android-pay-visa-nonce";
+    pub const GOOGLE_PAY_MASTERCARD: &str = "fake-android-pay-mastercard-nonce";
+}
+
+/// Card numbers that the sandbox accepts. Any other number is rejected.
+pub mod card_numbers {
+    pub const VISA: &str = "4111111111111111";
+    pub const VISA_2: &str = "4005519200000004";
+    pub const VISA_3: &str = "4012888888881881";
+    pub const MASTERCARD: &str = "5555555555554444";
+    /// A MasterCard in the 2-series BIN range.
+    pub const MASTERCARD_2_SERIES: &str = "2223000048400011";
+    pub const AMEX: &str = "378282246310005";
+    pub const AMEX_2: &str = "371449635398431";
+    pub const DISCOVER: &str = "6011111111111117";
+    pub const JCB: &str = "3530111333300000";
+    pub const MAESTRO: &str = "6304000000000000";
+    pub const DINERS_CLUB: &str = "36259600000004";
+    pub const UNIONPAY: &str = "6243030000000001";
+
+    /// Cards that are declined by the processor, with a `2000` response
+    /// code, when they're verified.
+    pub const DECLINED_VISA: &str = "4000111111111115";
+    pub const DECLINED_MASTERCARD: &str = "5105105105105100";
+    pub const DECLINED_AMEX: &str = "378734493671000";
+    pub const DECLINED_DISCOVER: &str = "6011000990139424";
+    pub const DECLINED_DINERS_CLUB: &str = "38520000009814";
+    /// A card whose verification fails with a `3000` processor error.
+    pub const FAILED_JCB: &str = "3566002020360505";
+
+    /// A card that the sandbox opens a chargeback dispute for once a sale
+    /// on it settles.
+    pub const DISPUTE: &str = "4023898493988028";
+
+    /// Every test card number above, along with the sandbox's other test
+    /// Visa numbers.
+    pub const ALL: &[&str] = &[
+        VISA, VISA_2, VISA_3, MASTERCARD, MASTERCARD_2_SERIES, AMEX, AMEX_2,
+        DISCOVER, JCB, MAESTRO, DINERS_CLUB, UNIONPAY,
+        DECLINED_VISA, DECLINED_MASTERCARD, DECLINED_AMEX, DECLINED_DISCOVER,
+        DECLINED_DINERS_CLUB, FAILED_JCB, DISPUTE,
+        "4009348888881881", "4012000033330026", "4012000077777777",
+        "4217651111111119", "4500600000000061",
+    ];
+}
+
+/// Amounts that determine the processor's response to an authorization.
+/// Ranges are in cents.
+pub mod amounts {
+    use std::ops::RangeInclusive;
+
+    use crate::amount::Amount;
+
+    /// Authorized, with a `1000` response code.
+    pub const AUTHORIZED: RangeInclusive<u64> = 1..=199_999;
+    /// Declined by the processor, with the amount's whole part as the
+    /// response code, e.g. `2001` (Insufficient Funds) for 2001.00.
+    pub const PROCESSOR_DECLINED: RangeInclusive<u64> = 200_000..=299_999;
+    /// Failed, with a `3000` response code.
+    pub const FAILED: RangeInclusive<u64> = 300_000..=300_099;
+
+    /// An amount that is authorized.
+    pub fn authorized() -> Amount {
+        Amount::from_minor_units(1000, "USD")
+    }
+
+    /// An amount that is declined with the given processor response code,
+    /// which must be between `2000` and `2999`.
+    pub fn processor_declined(code: u16) -> Amount {
+        assert!((2000..=2999).contains(&code), "processor declined codes are between 2000 and 2999");
+        Amount::from_minor_units(u64::from(code) * 100, "USD")
+    }
+
+    /// An amount whose authorization fails.
+    pub fn failed() -> Amount {
+        Amount::from_minor_units(*FAILED.start(), "USD")
+    }
+}
+
+/// An expiration date, as `MM/YYYY`, a few years from now.
+pub fn expiration_date() -> String {
+    format!("12/{}", chrono::Utc::now().year() + 3)
+}
+
+/// A card with the given number and an expiration date that hasn't passed.
+pub fn card(number: &str) -> CreditCard {
+    CreditCard{
+        number: Some(Secret::from(number)),
+        expiration_date: Some(expiration_date()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Datelike;
+
+    use super::{amounts, card, card_numbers, expiration_date};
+
+    #[test]
+    fn declined_amounts_carry_their_response_code() {
+        let amount = amounts::processor_declined(2001);
+        assert_eq!(amount.to_string(), "2001.00");
+        assert!(amounts::PROCESSOR_DECLINED.contains(&amount.minor_units()));
+        assert_eq!(amounts::processor_declined(2000).minor_units(), *amounts::PROCESSOR_DECLINED.start());
+        assert_eq!(amounts::processor_declined(2999).to_string(), "2999.00");
+        assert!(amounts::AUTHORIZED.contains(&amounts::authorized().minor_units()));
+    }
+
+    #[test]
+    #[should_panic(expected = "between 2000 and 2999")]
+    fn declined_amounts_reject_other_codes() {
+        amounts::processor_declined(3000);
+    }
+
+    #[test]
+    #[should_panic(expected = "between 2000 and 2999")]
+    fn declined_amounts_reject_approval_codes() {
+        amounts::processor_declined(1999);
+    }
+
+    #[test]
+    fn failed_amounts() {
+        assert_eq!(amounts::failed().to_string(), "3000.00");
+        assert!(amounts::FAILED.contains(&amounts::failed().minor_units()));
+        assert!(!amounts::PROCESSOR_DECLINED.contains(&amounts::failed().minor_units()));
+    }
+
+    #[test]
+    fn expiration_dates_are_in_the_future() {
+        let date = expiration_date();
+        let (month, year) = date.split_once('/').unwrap();
+        assert_eq!((month.len(), year.len()), (2, 4));
+        let (month, year): (u32, i32) = (month.parse().unwrap(), year.parse().unwrap());
+        assert!((1..=12).contains(&month));
+        let today = chrono::Utc::now().date_naive();
+        assert!((year, month) > (today.year(), today.month()));
+
+        let card = card(card_numbers::VISA);
+        assert_eq!(card.expiration_date, Some(date));
+        assert_eq!(card.number.unwrap().expose(), card_numbers::VISA);
+    }
+}
@@ -0,0 +1,1702 @@
+//! An in-process fake of the Braintree gateway, enabled by the `testing`
+//! feature.
+//!
+//! `FakeGateway` is a `Transport` that answers requests itself, with the
+//! same XML the sandbox sends, so that integration tests can run without
+//! network access or credentials:
+//!
+//! ```rust
+//! # use braintree::Secret;
+//! # use braintree::testing::FakeGateway;
+//! # use braintree::transaction;
+//! let gateway = FakeGateway::new();
+//! let bt = gateway.client();
+//!
+//! let transaction = bt.transaction().create(transaction::Request{
+//!     amount: "10.00".parse().unwrap(),
+//!     payment_method_nonce: Some(Secret::from("fake-valid-nonce")),
+//!     options: Some(transaction::Options{
+//!         submit_for_settlement: Some(true),
+//!         ..Default::default()
+//!     }),
+//!     ..Default::default()
+//! }).unwrap();
+//! assert_eq!(transaction.status, transaction::Status::SubmittedForSettlement);
+//!
+//! // Run the nightly batch: submitted_for_settlement -> settling -> settled.
+//! gateway.advance_settlement();
+//! gateway.advance_settlement();
+//! let transaction = bt.transaction().find(transaction.id).unwrap();
+//! assert_eq!(transaction.status, transaction::Status::Settled);
+//! ```
+//!
+//! Like the sandbox, the fake honors the magic test values:
+//!
+//! * amounts from 2000.00 to 2999.99 are declined by the processor, with the
+//!   amount's whole part as the response code, and 3000.00 to 3000.99 fail;
+//! * only Braintree's test card numbers are accepted, and the numbers that
+//!   fail verification (e.g. `4000111111111115`) fail it here too;
+//! * the card nonces, including Apple Pay and Google Pay ones, stand in for
+//!   the corresponding cards or errors. PayPal and Venmo aren't supported.
+//!
+//! These values are listed in the `test_values` module.
+//!
+//! Subscriptions need a plan, which is added with `add_plan()` instead of
+//! the Control Panel. Search criteria the fake doesn't know about match
+//! nothing.
+
+use base64::Engine;
+use chrono;
+use elementtree::Element;
+use hyper;
+use hyper::method::Method;
+use hyper::status::StatusCode;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::amount::Amount;
+use crate::builder::BraintreeBuilder;
+use crate::credit_card::CardType;
+use crate::test_values::{self, amounts, card_numbers, nonces};
+use crate::encode::XmlValue;
+use crate::transaction::{EscrowStatus, Status, Type};
+use crate::transport::{Request, Response, Transport};
+use crate::webhook_notification::Kind;
+use crate::{Braintree, Environment};
+
+/// The merchant ID that clients of a `FakeGateway` use.
+pub const MERCHANT_ID: &str = "fake_merchant_id";
+/// The public key that clients of a `FakeGateway` use.
+pub const PUBLIC_KEY: &str = "fake_public_key";
+/// The private key that clients of a `FakeGateway` use.
+pub const PRIVATE_KEY: &str = "fake_private_key";
+
+const MERCHANT_ACCOUNT_ID: &str = "fake_merchant_account";
+const CURRENCY: &str = "USD";
+
+/// Braintree's test card numbers, with their brand and, for the cards that
+/// fail verification, the processor response code.
+const CARDS: &[(&str, CardKind, Option<u32>)] = &[
+    (card_numbers::VISA, CardKind::Visa, None),
+    (card_numbers::VISA_2, CardKind::Visa, None),
+    (card_numbers::VISA_3, CardKind::Visa, None),
+    ("4009348888881881", CardKind::Visa, None),
+    ("4012000033330026", CardKind::Visa, None),
+    ("4012000077777777", CardKind::Visa, None),
+    ("4217651111111119", CardKind::Visa, None),
+    ("4500600000000061", CardKind::Visa, None),
+    (card_numbers::MASTERCARD, CardKind::MasterCard, None),
+    (card_numbers::MASTERCARD_2_SERIES, CardKind::MasterCard, None),
+    (card_numbers::AMEX, CardKind::AmericanExpress, None),
+    (card_numbers::AMEX_2, CardKind::AmericanExpress, None),
+    (card_numbers::DISCOVER, CardKind::Discover, None),
+    (card_numbers::JCB, CardKind::Jcb, None),
+    (card_numbers::MAESTRO, CardKind::Maestro, None),
+    (card_numbers::DINERS_CLUB, CardKind::DinersClub, None),
+    (card_numbers::UNIONPAY, CardKind::ChinaUnionPay, None),
+    (card_numbers::DECLINED_VISA, CardKind::Visa, Some(2000)),
+    (card_numbers::DECLINED_MASTERCARD, CardKind::MasterCard, Some(2000)),
+    (card_numbers::DECLINED_AMEX, CardKind::AmericanExpress, Some(2000)),
+    (card_numbers::DECLINED_DISCOVER, CardKind::Discover, Some(2000)),
+    (card_numbers::DECLINED_DINERS_CLUB, CardKind::DinersClub, Some(2000)),
+    (card_numbers::FAILED_JCB, CardKind::Jcb, Some(3000)),
+    (card_numbers::DISPUTE, CardKind::Visa, None),
+];
+
+/// The fake nonces for cards, with the card each one stands for.
+const NONCES: &[(&str, &str)] = &[
+    (nonces::VALID, card_numbers::VISA),
+    (nonces::VALID_VISA, card_numbers::VISA),
+    (nonces::VALID_AMEX, card_numbers::AMEX),
+    (nonces::VALID_MASTERCARD, card_numbers::MASTERCARD),
+    (nonces::VALID_DISCOVER, card_numbers::DISCOVER),
+    (nonces::VALID_JCB, card_numbers::JCB),
+    (nonces::VALID_MAESTRO, card_numbers::MAESTRO),
+    (nonces::VALID_DINERS_CLUB, card_numbers::DINERS_CLUB),
+    (nonces::PROCESSOR_DECLINED_VISA, card_numbers::DECLINED_VISA),
+    (nonces::PROCESSOR_DECLINED_MASTERCARD, card_numbers::DECLINED_MASTERCARD),
+    (nonces::PROCESSOR_DECLINED_AMEX, card_numbers::DECLINED_AMEX),
+    (nonces::PROCESSOR_DECLINED_DISCOVER, card_numbers::DECLINED_DISCOVER),
+    (nonces::PROCESSOR_FAILURE_JCB, card_numbers::FAILED_JCB),
+    (nonces::APPLE_PAY_VISA, card_numbers::VISA),
+    (nonces::APPLE_PAY_MASTERCARD, card_numbers::MASTERCARD),
+    (nonces::APPLE_PAY_AMEX, card_numbers::AMEX),
+    (nonces::GOOGLE_PAY, card_numbers::VISA),
+    (nonces::GOOGLE_PAY_VISA, card_numbers::VISA),
+    (nonces::GOOGLE_PAY_MASTERCARD, card_numbers::MASTERCARD),
+];
+
+/// A `CardType` that can be used in a constant.
+#[derive(Clone, Copy)]
+enum CardKind {
+    AmericanExpress,
+    ChinaUnionPay,
+    DinersClub,
+    Discover,
+    Jcb,
+    Maestro,
+    MasterCard,
+    Visa,
+}
+
+impl From<CardKind> for CardType {
+    fn from(kind: CardKind) -> CardType {
+        match kind {
+            CardKind::AmericanExpress => CardType::AmericanExpress,
+            CardKind::ChinaUnionPay => CardType::ChinaUnionPay,
+            CardKind::DinersClub => CardType::DinersClub,
+            CardKind::Discover => CardType::Discover,
+            CardKind::Jcb => CardType::Jcb,
+            CardKind::Maestro => CardType::Maestro,
+            CardKind::MasterCard => CardType::MasterCard,
+            CardKind::Visa => CardType::Visa,
+        }
+    }
+}
+
+/// A fake Braintree gateway. Clones share the same state, so a clone can be
+/// handed to a client while the test keeps another to drive settlement.
+#[derive(Clone, Default)]
+pub struct FakeGateway {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    transactions: Vec<FakeTransaction>,
+    verifications: Vec<FakeVerification>,
+    subscriptions: Vec<FakeSubscription>,
+    customers: HashMap<String, FakeCustomer>,
+    payment_methods: HashMap<String, Card>,
+    plans: HashMap<String, Amount>,
+}
+
+struct FakeTransaction {
+    id: String,
+    typ: Type,
+    amount: Amount,
+    status: Status,
+    escrow_status: Option<EscrowStatus>,
+    processor_response_code: u32,
+    order_id: Option<String>,
+    merchant_account_id: String,
+    customer_id: Option<String>,
+    card: Card,
+    /// The `<line-items>` element of the request, which isn't `Send`.
+    line_items: Option<String>,
+    refunded_transaction_id: Option<String>,
+    refund_ids: Vec<String>,
+    /// The processor's settlement response code and text, once settlement
+    /// is pending or declined.
+    settlement_response: Option<(u32, String)>,
+    /// The amount refunded so far, in minor units.
+    refunded: u64,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+struct FakeVerification {
+    id: String,
+    status: &'static str,
+    processor_response_code: u32,
+    amount: Amount,
+    merchant_account_id: String,
+    card: Card,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+struct FakeSubscription {
+    id: String,
+    plan_id: String,
+    payment_method_token: String,
+    price: Amount,
+    status: &'static str,
+    first_billing_date: chrono::NaiveDate,
+    billing_period: Option<(chrono::NaiveDate, chrono::NaiveDate)>,
+    days_past_due: Option<u32>,
+    transaction_ids: Vec<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+struct FakeCustomer {
+    id: String,
+    fields: Vec<(String, String)>,
+    payment_method_tokens: Vec<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Clone)]
+struct Card {
+    number: String,
+    card_type: CardType,
+    expiration_month: String,
+    expiration_year: String,
+    cardholder_name: Option<String>,
+    /// The processor response code when the card is verified, if it fails.
+    verification_decline: Option<u32>,
+    token: Option<String>,
+    customer_id: Option<String>,
+}
+
+/// A response from the fake, or an error response cutting a request short.
+struct Reply {
+    status: StatusCode,
+    body: Option<Element>,
+}
+
+type Handled = Result<Reply, Reply>;
+
+impl FakeGateway {
+    pub fn new() -> FakeGateway {
+        FakeGateway::default()
+    }
+
+    /// A builder for a client of this gateway, with its credentials already
+    /// set, e.g. to add middleware before building it.
+    pub fn builder(&self) -> BraintreeBuilder {
+        Braintree::builder()
+            .environment(Environment::Sandbox)
+            .merchant_id(MERCHANT_ID)
+            .public_key(PUBLIC_KEY)
+            .private_key(PRIVATE_KEY)
+            .transport(self.clone())
+    }
+
+    /// A client of this gateway.
+    pub fn client(&self) -> Braintree {
+        self.builder().build().expect("failed to build a client of the fake gateway")
+    }
+
+    /// Add a plan that subscriptions can be created for.
+    pub fn add_plan<S: Into<String>>(&self, plan_id: S, price: Amount) {
+        self.state.lock().unwrap().plans.insert(plan_id.into(), price);
+    }
+
+    /// Moves every transaction one step through settlement, as Braintree's
+    /// batches do: `SubmittedForSettlement` becomes `Settling`, and
+    /// `Settling` becomes `Settled`. Funds held in escrow are held once
+    /// their transaction settles, and pending releases are released.
+    pub fn advance_settlement(&self) {
+        let mut state = self.state.lock().unwrap();
+        let now = chrono::Utc::now();
+        for transaction in &mut state.transactions {
+            match transaction.status {
+                Status::SubmittedForSettlement => transaction.status = Status::Settling,
+                Status::Settling => settle(transaction),
+                _ => match transaction.escrow_status {
+                    Some(EscrowStatus::ReleasePending) => transaction.escrow_status = Some(EscrowStatus::Released),
+                    _ => continue,
+                },
+            }
+            transaction.updated_at = now;
+        }
+    }
+
+    /// A webhook notification of the given kind about `id`, as the
+    /// `(bt_signature, bt_payload)` pair that Braintree would post, signed
+    /// with this gateway's keys. The subject is the matching transaction or
+    /// subscription if there is one, and a sample record otherwise.
+    pub fn sample_notification(&self, kind: Kind, id: &str) -> (String, String) {
+        use hmac::{Hmac, Mac};
+        use sha1::{Digest, Sha1};
+
+        let state = self.state.lock().unwrap();
+        let mut notification = Element::new("notification");
+        value(&mut notification, "timestamp", &chrono::Utc::now());
+        value(&mut notification, "kind", &String::from(kind.clone()));
+        notification.append_new_child("subject").append_child(state.sample_subject(&kind, id));
+
+        let payload = base64::engine::general_purpose::STANDARD.encode(to_string(&notification));
+        let mut mac = Hmac::<Sha1>::new_from_slice(&Sha1::digest(PRIVATE_KEY.as_bytes())).unwrap();
+        mac.update(payload.as_bytes());
+        let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        (format!("{}|{}", PUBLIC_KEY, digest), payload)
+    }
+
+    fn handle(&self, request: &Request) -> Reply {
+        let expected = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", PUBLIC_KEY, PRIVATE_KEY)));
+        let authorized = request.headers.get_raw("Authorization")
+            .and_then(|values| values.first())
+            .is_some_and(|value| *value == expected.as_bytes());
+        if !authorized {
+            return Reply::empty(StatusCode::Unauthorized);
+        }
+
+        let segments: Vec<&str> = request.url.path().split('/').filter(|s| !s.is_empty()).collect();
+        let segments = match segments.split_first() {
+            Some((&"merchants", rest)) => match rest.split_first() {
+                Some((&MERCHANT_ID, rest)) => rest,
+                _ => return Reply::empty(StatusCode::Forbidden),
+            },
+            _ => return Reply::empty(StatusCode::NotFound),
+        };
+        let body = match request.body {
+            Some(ref body) if !body.is_empty() => match Element::from_reader(&body[..]) {
+                Ok(body) => Some(body),
+                Err(_) => return Reply::empty(StatusCode::BadRequest),
+            },
+            _ => None,
+        };
+        let body = body.unwrap_or_else(|| Element::new("empty"));
+
+        let mut state = self.state.lock().unwrap();
+        let result = match (&request.method, segments) {
+            (&Method::Post, ["client_token"]) => state.client_token(&body),
+            (&Method::Post, ["customers"]) => state.create_customer(&body),
+            (&Method::Post, ["transactions"]) => state.create_transaction(&body),
+            (&Method::Post, ["transactions", "advanced_search_ids"]) => Ok(state.search_ids(&body, |state| &state.transactions)),
+            (&Method::Post, ["transactions", "advanced_search"]) => Ok(state.search_page(&body, "credit-card-transactions", |state| &state.transactions)),
+            (&Method::Get, ["transactions", id]) => state.transaction(id).map(|transaction| Reply::new(StatusCode::Ok, transaction.render())),
+            (&Method::Get, ["transactions", id, "line_items"]) => state.line_items(id),
+            (&Method::Put, ["transactions", id, action]) => state.transition(id, action, &body),
+            (&Method::Post, ["transactions", id, "refund"]) => state.refund(id, &body),
+            (&Method::Post, ["verifications"]) => state.create_verification(&body),
+            (&Method::Post, ["verifications", "advanced_search_ids"]) => Ok(state.search_ids(&body, |state| &state.verifications)),
+            (&Method::Post, ["verifications", "advanced_search"]) => Ok(state.search_page(&body, "credit-card-verifications", |state| &state.verifications)),
+            (&Method::Get, ["verifications", id]) => match state.verifications.iter().find(|verification| verification.id == *id) {
+                Some(verification) => Ok(Reply::new(StatusCode::Ok, verification.render())),
+                None => Err(Reply::empty(StatusCode::NotFound)),
+            },
+            (&Method::Post, ["subscriptions"]) => state.create_subscription(&body),
+            (&Method::Put, ["subscriptions", id, "make_past_due"]) => {
+                let days = request.url.query_pairs().find(|(name, _)| name == "days_past_due").and_then(|(_, days)| days.parse().ok());
+                state.make_past_due(id, days.unwrap_or(1))
+            },
+            _ => Err(Reply::empty(StatusCode::NotFound)),
+        };
+        result.unwrap_or_else(|reply| reply)
+    }
+}
+
+impl Transport for FakeGateway {
+    fn send(&self, request: Request) -> hyper::Result<Response> {
+        let reply = self.handle(&request);
+        let mut headers = hyper::header::Headers::new();
+        let body = match reply.body {
+            Some(ref body) => {
+                headers.set_raw("Content-Type", vec![b"application/xml; charset=utf-8".to_vec()]);
+                to_string(body).into_bytes()
+            },
+            None => Vec::new(),
+        };
+        Ok(Response{status: reply.status, headers, body: Box::new(io::Cursor::new(body))})
+    }
+}
+
+impl State {
+    fn client_token(&mut self, body: &Element) -> Handled {
+        if let Some(customer_id) = text(body, "customer-id") {
+            if !self.customers.contains_key(customer_id) {
+                return Err(Reply::invalid(&["client-token"], "customer-id", 92804, "Customer specified by customer_id does not exist"));
+            }
+        }
+        let version = text(body, "version").unwrap_or("2");
+        let fingerprint = generate_id(32);
+        let token = format!("{{\"version\":{},\"authorizationFingerprint\":\"{}\",\"merchantId\":\"{}\",\"environment\":\"sandbox\"}}",
+                            version, fingerprint, MERCHANT_ID);
+        let mut response = Element::new("client-token");
+        value(&mut response, "value", &base64::engine::general_purpose::STANDARD.encode(token));
+        Ok(Reply::new(StatusCode::Created, response))
+    }
+
+    fn create_customer(&mut self, body: &Element) -> Handled {
+        let id = match text(body, "id") {
+            Some(id) if self.customers.contains_key(id) => return Err(Reply::invalid(&["customer"], "id", 91609, "Customer ID has already been taken.")),
+            Some(id) => String::from(id),
+            None => self.unique_id(|state, id| state.customers.contains_key(id)),
+        };
+
+        let card = match (body.find("credit-card"), text(body, "payment-method-nonce")) {
+            (Some(card), _) => Some(parse_card(card, &["customer", "credit-card"])?),
+            (None, Some(nonce)) => Some(card_for_nonce(nonce, &["customer"])?),
+            (None, None) => None,
+        };
+
+        let mut customer = FakeCustomer{
+            id,
+            fields: ["first-name", "last-name", "company", "email", "phone", "fax", "website"].iter()
+                .filter_map(|field| text(body, field).map(|value| (String::from(*field), String::from(value))))
+                .collect(),
+            payment_method_tokens: Vec::new(),
+            created_at: chrono::Utc::now(),
+        };
+        if let Some(card) = card {
+            let token = self.vault(card, &customer.id)?;
+            customer.payment_method_tokens.push(token);
+        }
+        let response = customer.render(self, body.find("custom-fields"));
+        self.customers.insert(customer.id.clone(), customer);
+        Ok(Reply::new(StatusCode::Created, response))
+    }
+
+    fn create_transaction(&mut self, body: &Element) -> Handled {
+        let typ = Type::from(String::from(text(body, "type").unwrap_or("sale")));
+        let amount = parse_amount(body, "amount", &["transaction"])?;
+        let options = body.find("options");
+        let option = |name: &str| options.and_then(|options| text(options, name)) == Some("true");
+
+        let mut card = self.payment_method(body, &["transaction"])?;
+        let merchant_account_id = String::from(text(body, "merchant-account-id").unwrap_or(MERCHANT_ACCOUNT_ID));
+        let escrow_status = match option("hold-in-escrow") {
+            true if merchant_account_id == MERCHANT_ACCOUNT_ID => {
+                return Err(Reply::invalid(&["transaction"], "base", 91560, "Transaction could not be held in escrow."));
+            },
+            true => Some(EscrowStatus::HoldPending),
+            false => None,
+        };
+
+        let (status, processor_response_code) = authorize(&amount);
+        let approved = status == Status::Authorized;
+        let mut customer_id = text(body, "customer-id").map(String::from);
+        if approved && card.token.is_none() && (option("store-in-vault") || option("store-in-vault-on-success")) {
+            let owner = match customer_id {
+                Some(ref id) => id.clone(),
+                None => self.store_customer(body.find("customer")),
+            };
+            let token = self.vault(card.clone(), &owner)?;
+            card = self.payment_methods[&token].clone();
+            customer_id = Some(owner);
+        }
+
+        let now = chrono::Utc::now();
+        let transaction = FakeTransaction{
+            id: self.unique_id(|state, id| state.transactions.iter().any(|transaction| transaction.id == id)),
+            typ,
+            amount,
+            status: match status {
+                Status::Authorized if option("submit-for-settlement") => Status::SubmittedForSettlement,
+                status => status,
+            },
+            escrow_status: escrow_status.filter(|_| approved),
+            processor_response_code,
+            order_id: text(body, "order-id").map(String::from),
+            merchant_account_id,
+            customer_id: customer_id.or_else(|| card.customer_id.clone()),
+            card,
+            line_items: body.find("line-items").map(to_string),
+            refunded_transaction_id: None,
+            refund_ids: Vec::new(),
+            settlement_response: None,
+            refunded: 0,
+            created_at: now,
+            updated_at: now,
+        };
+        let rendered = transaction.render();
+        self.transactions.push(transaction);
+        match approved {
+            true => Ok(Reply::new(StatusCode::Created, rendered)),
+            false => Err(Reply::declined(processor_response_code, rendered)),
+        }
+    }
+
+    fn transaction(&mut self, id: &str) -> Result<&mut FakeTransaction, Reply> {
+        self.transactions.iter_mut().find(|transaction| transaction.id == id).ok_or_else(|| Reply::empty(StatusCode::NotFound))
+    }
+
+    fn line_items(&mut self, id: &str) -> Handled {
+        let transaction = self.transaction(id)?;
+        let mut response = Element::new("line-items");
+        response.set_attr("type", "array");
+        if let Some(ref line_items) = transaction.line_items {
+            let line_items = Element::from_reader(line_items.as_bytes()).expect("failed to read stored line items");
+            for line_item in line_items.children() {
+                response.append_child(line_item.clone());
+            }
+        }
+        Ok(Reply::new(StatusCode::Ok, response))
+    }
+
+    /// Handles the `PUT transactions/{id}/{action}` calls that move a
+    /// transaction between statuses.
+    fn transition(&mut self, id: &str, action: &str, body: &Element) -> Handled {
+        let transaction = self.transaction(id)?;
+        let invalid = |attribute, code, message| Err(Reply::invalid(&["transaction"], attribute, code, message));
+        match (action, &transaction.status, &transaction.escrow_status) {
+            ("submit_for_settlement", &Status::Authorized, _) => transaction.status = Status::SubmittedForSettlement,
+            ("submit_for_settlement", _, _) => return invalid("base", 91507, "Cannot submit for settlement unless status is authorized."),
+            ("void", &Status::Authorized, _) | ("void", &Status::SubmittedForSettlement, _) => transaction.status = Status::Voided,
+            ("void", _, _) => return invalid("base", 91504, "Transaction can only be voided if status is authorized, submitted_for_settlement, or - for PayPal - settlement_pending."),
+            ("settle", &Status::SubmittedForSettlement, _) | ("settle", &Status::Settling, _) => settle(transaction),
+            ("settlement_confirm", &Status::SubmittedForSettlement, _) | ("settlement_confirm", &Status::Settling, _)
+                | ("settlement_confirm", &Status::SettlementPending, _) => transaction.status = Status::SettlementConfirmed,
+            ("settlement_decline", &Status::SubmittedForSettlement, _) | ("settlement_decline", &Status::Settling, _)
+                | ("settlement_decline", &Status::SettlementPending, _) => {
+                transaction.status = Status::SettlementDeclined;
+                transaction.settlement_response = Some((4001, String::from("Settlement Declined")));
+            },
+            ("settlement_pending", &Status::SubmittedForSettlement, _) | ("settlement_pending", &Status::Settling, _) => {
+                transaction.status = Status::SettlementPending;
+                let reason = text(body, "processor-settlement-response-text").unwrap_or("Settlement Pending");
+                transaction.settlement_response = Some((4002, String::from(reason)));
+            },
+            ("settle", _, _) | ("settlement_confirm", _, _) | ("settlement_decline", _, _) | ("settlement_pending", _, _) => {
+                return invalid("base", 91576, "Cannot transition transaction to settled, settlement_confirmed, or settlement_declined.");
+            },
+            ("hold_in_escrow", &Status::Authorized, &None) | ("hold_in_escrow", &Status::SubmittedForSettlement, &None)
+                if transaction.merchant_account_id != MERCHANT_ACCOUNT_ID => transaction.escrow_status = Some(EscrowStatus::HoldPending),
+            ("hold_in_escrow", _, _) => return invalid("base", 91560, "Transaction could not be held in escrow."),
+            ("release_from_escrow", _, &Some(EscrowStatus::Held)) => transaction.escrow_status = Some(EscrowStatus::ReleasePending),
+            ("release_from_escrow", _, _) => return invalid("base", 91561, "Cannot release a transaction that is not escrowed."),
+            ("cancel_release", _, &Some(EscrowStatus::ReleasePending)) => transaction.escrow_status = Some(EscrowStatus::Held),
+            ("cancel_release", _, _) => return invalid("base", 91562, "Release can only be cancelled if the transaction is submitted for release."),
+            _ => return Err(Reply::empty(StatusCode::NotFound)),
+        }
+        transaction.updated_at = chrono::Utc::now();
+        Ok(Reply::new(StatusCode::Ok, transaction.render()))
+    }
+
+    fn refund(&mut self, id: &str, body: &Element) -> Handled {
+        let requested = match body.find("amount") {
+            Some(_) => Some(parse_amount(body, "amount", &["transaction"])?),
+            None => None,
+        };
+        let refund_id = self.unique_id(|state, id| state.transactions.iter().any(|transaction| transaction.id == id));
+        let original = self.transaction(id)?;
+        let invalid = |code, message| Err(Reply::invalid(&["transaction"], "base", code, message));
+        if original.typ == Type::Credit {
+            return invalid(91505, "Cannot refund credit");
+        }
+        if original.status != Status::Settled && original.status != Status::Settling {
+            return invalid(91506, "Cannot refund transaction unless it is settled.");
+        }
+        let remaining = original.amount.minor_units() - original.refunded;
+        if remaining == 0 {
+            return invalid(91512, "Transaction has already been completely refunded.");
+        }
+        let amount = requested.unwrap_or_else(|| Amount::from_minor_units(remaining, CURRENCY));
+        if amount.minor_units() > remaining {
+            return Err(Reply::invalid(&["transaction"], "amount", 91521, "Refund amount is too large."));
+        }
+
+        let now = chrono::Utc::now();
+        original.refunded += amount.minor_units();
+        original.refund_ids.push(refund_id.clone());
+        original.updated_at = now;
+        let refund = FakeTransaction{
+            id: refund_id,
+            typ: Type::Credit,
+            amount,
+            status: Status::SubmittedForSettlement,
+            escrow_status: None,
+            processor_response_code: 1002,
+            order_id: original.order_id.clone(),
+            merchant_account_id: original.merchant_account_id.clone(),
+            customer_id: original.customer_id.clone(),
+            card: original.card.clone(),
+            line_items: None,
+            refunded_transaction_id: Some(original.id.clone()),
+            refund_ids: Vec::new(),
+            settlement_response: None,
+            refunded: 0,
+            created_at: now,
+            updated_at: now,
+        };
+        let rendered = refund.render();
+        self.transactions.push(refund);
+        Ok(Reply::new(StatusCode::Created, rendered))
+    }
+
+    fn create_verification(&mut self, body: &Element) -> Handled {
+        let card = match body.find("credit-card") {
+            Some(card) => parse_card(card, &["verification", "credit-card"])?,
+            None => return Err(Reply::invalid(&["verification", "credit-card"], "number", 81714, "Credit card number is required.")),
+        };
+        let options = body.find("options");
+        let amount = match options.filter(|options| options.find("amount").is_some()) {
+            Some(options) => parse_amount(options, "amount", &["verification", "options"])?,
+            None => Amount::from_minor_units(0, CURRENCY),
+        };
+        let (status, processor_response_code) = match card.verification_decline {
+            Some(3000) => ("failed", 3000),
+            Some(code) => ("processor_declined", code),
+            None => ("verified", 1000),
+        };
+        let verification = FakeVerification{
+            id: self.unique_id(|state, id| state.verifications.iter().any(|verification| verification.id == id)),
+            status,
+            processor_response_code,
+            amount,
+            merchant_account_id: String::from(options.and_then(|options| text(options, "merchant-account-id")).unwrap_or(MERCHANT_ACCOUNT_ID)),
+            card,
+            created_at: chrono::Utc::now(),
+        };
+        let rendered = verification.render();
+        self.verifications.push(verification);
+        Ok(Reply::new(StatusCode::Created, rendered))
+    }
+
+    fn create_subscription(&mut self, body: &Element) -> Handled {
+        let invalid = |attribute, code, message| Err(Reply::invalid(&["subscription"], attribute, code, message));
+        let plan_id = match text(body, "plan-id") {
+            Some(plan_id) if self.plans.contains_key(plan_id) => String::from(plan_id),
+            _ => return invalid("plan-id", 91904, "Plan ID is invalid."),
+        };
+        let card = match text(body, "payment-method-token").and_then(|token| self.payment_methods.get(token)) {
+            Some(card) => card.clone(),
+            None => return invalid("payment-method-token", 91903, "Payment method token is invalid."),
+        };
+        let price = match body.find("price") {
+            Some(_) => parse_amount(body, "price", &["subscription"])?,
+            None => self.plans[&plan_id],
+        };
+        let today = chrono::Utc::now().date_naive();
+        let first_billing_date = match text(body, "first-billing-date") {
+            Some(date) => match chrono::NaiveDate::parse_from_str(date, crate::DATE_FORMAT) {
+                Ok(date) if date >= today => date,
+                _ => return invalid("first-billing-date", 91916, "First Billing Date is invalid."),
+            },
+            None => today,
+        };
+
+        let mut subscription = FakeSubscription{
+            id: self.unique_id(|state, id| state.subscriptions.iter().any(|subscription| subscription.id == id)),
+            plan_id,
+            payment_method_token: card.token.clone().unwrap_or_default(),
+            price,
+            status: "Pending",
+            first_billing_date,
+            billing_period: None,
+            days_past_due: None,
+            transaction_ids: Vec::new(),
+            created_at: chrono::Utc::now(),
+        };
+        if first_billing_date == today {
+            let (status, processor_response_code) = authorize(&price);
+            let now = chrono::Utc::now();
+            let charge = FakeTransaction{
+                id: self.unique_id(|state, id| state.transactions.iter().any(|transaction| transaction.id == id)),
+                typ: Type::Sale,
+                amount: price,
+                status: match status {
+                    Status::Authorized => Status::SubmittedForSettlement,
+                    ref status => status.clone(),
+                },
+                escrow_status: None,
+                processor_response_code,
+                order_id: None,
+                merchant_account_id: String::from(MERCHANT_ACCOUNT_ID),
+                customer_id: card.customer_id.clone(),
+                card,
+                line_items: None,
+                refunded_transaction_id: None,
+                refund_ids: Vec::new(),
+                settlement_response: None,
+                refunded: 0,
+                created_at: now,
+                updated_at: now,
+            };
+            let rendered = charge.render();
+            subscription.transaction_ids.push(charge.id.clone());
+            self.transactions.push(charge);
+            if status != Status::Authorized {
+                return Err(Reply::declined(processor_response_code, rendered));
+            }
+            subscription.status = "Active";
+            subscription.billing_period = Some((today, add_month(today).pred_opt().unwrap_or(today)));
+        }
+        let rendered = subscription.render(self);
+        self.subscriptions.push(subscription);
+        Ok(Reply::new(StatusCode::Created, rendered))
+    }
+
+    fn make_past_due(&mut self, id: &str, days: u32) -> Handled {
+        let index = self.subscriptions.iter().position(|subscription| subscription.id == id)
+            .ok_or_else(|| Reply::empty(StatusCode::NotFound))?;
+        self.subscriptions[index].status = "Past Due";
+        self.subscriptions[index].days_past_due = Some(days);
+        Ok(Reply::new(StatusCode::Ok, self.subscriptions[index].render(self)))
+    }
+
+    /// Resolves the payment method of a transaction, from a nonce, a vaulted
+    /// token or card details.
+    fn payment_method(&self, body: &Element, scope: &[&str]) -> Result<Card, Reply> {
+        if let Some(nonce) = text(body, "payment-method-nonce") {
+            return card_for_nonce(nonce, scope);
+        }
+        if let Some(token) = text(body, "payment-method-token") {
+            return self.payment_methods.get(token).cloned()
+                .ok_or_else(|| Reply::invalid(scope, "payment-method-token", 91518, "Payment method token is invalid."));
+        }
+        match body.find("credit-card") {
+            Some(card) => parse_card(card, &[scope, &["credit-card"][..]].concat()),
+            None => Err(Reply::invalid(scope, "base", 91508, "Cannot determine payment method.")),
+        }
+    }
+
+    /// Stores a card in the Vault for a customer, returning its token.
+    fn vault(&mut self, mut card: Card, customer_id: &str) -> Result<String, Reply> {
+        let token = match card.token {
+            Some(ref token) if self.payment_methods.contains_key(token) => {
+                return Err(Reply::invalid(&["customer", "credit-card"], "token", 91718, "Token is in use by another payment method."));
+            },
+            Some(ref token) => token.clone(),
+            None => self.unique_id(|state, id| state.payment_methods.contains_key(id)),
+        };
+        card.token = Some(token.clone());
+        card.customer_id = Some(String::from(customer_id));
+        self.payment_methods.insert(token.clone(), card);
+        if let Some(customer) = self.customers.get_mut(customer_id) {
+            customer.payment_method_tokens.push(token.clone());
+        }
+        Ok(token)
+    }
+
+    /// Creates the customer that a transaction's card is vaulted under.
+    fn store_customer(&mut self, details: Option<&Element>) -> String {
+        let id = match details.and_then(|details| text(details, "id")) {
+            Some(id) if !self.customers.contains_key(id) => String::from(id),
+            _ => self.unique_id(|state, id| state.customers.contains_key(id)),
+        };
+        let fields = ["first-name", "last-name", "company", "email", "phone", "fax", "website"].iter()
+            .filter_map(|field| details.and_then(|details| text(details, field)).map(|value| (String::from(*field), String::from(value))))
+            .collect();
+        self.customers.insert(id.clone(), FakeCustomer{id: id.clone(), fields, payment_method_tokens: Vec::new(), created_at: chrono::Utc::now()});
+        id
+    }
+
+    fn search_ids<T: Searchable, F: Fn(&State) -> &Vec<T>>(&self, criteria: &Element, records: F) -> Reply {
+        let mut response = Element::new("search-results");
+        value(&mut response, "page-size", &50usize);
+        let ids = response.append_new_child("ids");
+        ids.set_attr("type", "array");
+        for record in records(self).iter().filter(|record| matches(criteria, *record)) {
+            value(ids, "item", record.id());
+        }
+        Reply::new(StatusCode::Ok, response)
+    }
+
+    fn search_page<T: Searchable, F: Fn(&State) -> &Vec<T>>(&self, body: &Element, name: &str, records: F) -> Reply {
+        let ids: Vec<&str> = body.find("ids").map(|ids| ids.children().map(Element::text).collect()).unwrap_or_default();
+        let mut response = Element::new(name);
+        value(&mut response, "current-page-number", &1usize);
+        value(&mut response, "page-size", &50usize);
+        value(&mut response, "total-items", &ids.len());
+        for id in ids {
+            if let Some(record) = records(self).iter().find(|record| record.id() == id) {
+                response.append_child(record.render());
+            }
+        }
+        Reply::new(StatusCode::Ok, response)
+    }
+
+    /// The subject of a sample webhook notification.
+    fn sample_subject(&self, kind: &Kind, id: &str) -> Element {
+        let mut subject = match *kind {
+            Kind::Check => {
+                let mut check = Element::new("check");
+                check.set_attr("type", "boolean").set_text("true");
+                return check;
+            },
+            Kind::TransactionSettled | Kind::TransactionSettlementDeclined | Kind::TransactionDisbursed => {
+                if let Some(transaction) = self.transactions.iter().find(|transaction| transaction.id == id) {
+                    return transaction.render();
+                }
+                let mut transaction = Element::new("transaction");
+                value(&mut transaction, "id", id);
+                value(&mut transaction, "type", &Type::Sale);
+                value(&mut transaction, "amount", &Amount::from_minor_units(10000, CURRENCY));
+                value(&mut transaction, "currency-iso-code", CURRENCY);
+                value(&mut transaction, "status", &match *kind {
+                    Kind::TransactionSettlementDeclined => Status::SettlementDeclined,
+                    _ => Status::Settled,
+                });
+                value(&mut transaction, "created-at", &chrono::Utc::now());
+                value(&mut transaction, "updated-at", &chrono::Utc::now());
+                return transaction;
+            },
+            Kind::SubscriptionCanceled | Kind::SubscriptionChargedSuccessfully | Kind::SubscriptionChargedUnsuccessfully
+                | Kind::SubscriptionExpired | Kind::SubscriptionTrialEnded | Kind::SubscriptionWentActive | Kind::SubscriptionWentPastDue => {
+                if let Some(subscription) = self.subscriptions.iter().find(|subscription| subscription.id == id) {
+                    return subscription.render(self);
+                }
+                Element::new("subscription")
+            },
+            Kind::SubMerchantAccountApproved => {
+                let mut merchant_account = Element::new("merchant-account");
+                value(&mut merchant_account, "id", id);
+                value(&mut merchant_account, "status", "active");
+                return merchant_account;
+            },
+            Kind::SubMerchantAccountDeclined => {
+                let mut response = Element::new("api-error-response");
+                value(&mut response, "message", "Applicant declined due to OFAC.");
+                let merchant_account = response.append_new_child("merchant-account");
+                value(merchant_account, "id", id);
+                value(merchant_account, "status", "suspended");
+                return response;
+            },
+            Kind::DisputeOpened | Kind::DisputeLost | Kind::DisputeWon => Element::new("dispute"),
+            Kind::Disbursement | Kind::DisbursementException => Element::new("disbursement"),
+            Kind::PartnerMerchantConnected | Kind::PartnerMerchantDeclined | Kind::PartnerMerchantDisconnected => {
+                let mut partner_merchant = Element::new("partner-merchant");
+                value(&mut partner_merchant, "partner-merchant-id", id);
+                return partner_merchant;
+            },
+            Kind::AccountUpdaterDailyReport | Kind::Unknown(_) => Element::new("subject"),
+        };
+        value(&mut subject, "id", id);
+        subject
+    }
+
+    /// A new random ID that `taken` doesn't reject.
+    fn unique_id<F: Fn(&State, &str) -> bool>(&self, taken: F) -> String {
+        loop {
+            let id = generate_id(8);
+            if !taken(self, &id) {
+                return id;
+            }
+        }
+    }
+}
+
+impl FakeTransaction {
+    fn render(&self) -> Element {
+        let mut transaction = Element::new("transaction");
+        value(&mut transaction, "id", &self.id);
+        value(&mut transaction, "type", &self.typ);
+        value(&mut transaction, "amount", &self.amount);
+        value(&mut transaction, "currency-iso-code", CURRENCY);
+        value(&mut transaction, "status", &self.status);
+        if let Some(ref escrow_status) = self.escrow_status {
+            value(&mut transaction, "escrow-status", &String::from(escrow_status.clone()));
+        }
+        if let Some(ref order_id) = self.order_id {
+            value(&mut transaction, "order-id", order_id);
+        }
+        value(&mut transaction, "merchant-account-id", &self.merchant_account_id);
+        value(&mut transaction, "processor-response-code", &self.processor_response_code.to_string());
+        value(&mut transaction, "processor-response-text", processor_response_text(self.processor_response_code));
+        if let Some((code, ref text)) = self.settlement_response {
+            value(&mut transaction, "processor-settlement-response-code", &code.to_string());
+            value(&mut transaction, "processor-settlement-response-text", text);
+        }
+        if let Some(ref refunded_transaction_id) = self.refunded_transaction_id {
+            value(&mut transaction, "refunded-transaction-id", refunded_transaction_id);
+        }
+        let refund_ids = transaction.append_new_child("refund-ids");
+        refund_ids.set_attr("type", "array");
+        for refund_id in &self.refund_ids {
+            value(refund_ids, "item", refund_id);
+        }
+        let customer = transaction.append_new_child("customer");
+        if let Some(ref customer_id) = self.customer_id {
+            value(customer, "id", customer_id);
+        }
+        transaction.append_child(self.card.render());
+        value(&mut transaction, "created-at", &self.created_at);
+        value(&mut transaction, "updated-at", &self.updated_at);
+        transaction
+    }
+}
+
+impl FakeVerification {
+    fn render(&self) -> Element {
+        let mut verification = Element::new("verification");
+        value(&mut verification, "id", &self.id);
+        value(&mut verification, "status", self.status);
+        value(&mut verification, "amount", &self.amount);
+        value(&mut verification, "currency-iso-code", CURRENCY);
+        value(&mut verification, "merchant-account-id", &self.merchant_account_id);
+        value(&mut verification, "cvv-response-code", "M");
+        value(&mut verification, "avs-postal-code-response-code", "I");
+        value(&mut verification, "avs-street-address-response-code", "I");
+        value(&mut verification, "processor-response-code", &self.processor_response_code.to_string());
+        value(&mut verification, "processor-response-text", processor_response_text(self.processor_response_code));
+        value(&mut verification, "processor-response-type", match self.status {
+            "verified" => "approved",
+            "failed" => "hard_declined",
+            _ => "soft_declined",
+        });
+        verification.append_child(self.card.render());
+        value(&mut verification, "created-at", &self.created_at);
+        verification
+    }
+}
+
+impl FakeSubscription {
+    fn render(&self, state: &State) -> Element {
+        let mut subscription = Element::new("subscription");
+        value(&mut subscription, "id", &self.id);
+        value(&mut subscription, "plan-id", &self.plan_id);
+        value(&mut subscription, "payment-method-token", &self.payment_method_token);
+        value(&mut subscription, "price", &self.price);
+        value(&mut subscription, "status", self.status);
+        value(&mut subscription, "first-billing-date", &self.first_billing_date);
+        match self.billing_period {
+            Some((start, end)) => {
+                value(&mut subscription, "billing-period-start-date", &start);
+                value(&mut subscription, "billing-period-end-date", &end);
+                value(&mut subscription, "paid-through-date", &end);
+                value(&mut subscription, "next-billing-date", &add_month(start));
+            },
+            None => value(&mut subscription, "next-billing-date", &self.first_billing_date),
+        }
+        if let Some(days) = self.days_past_due {
+            value(&mut subscription, "days-past-due", &days);
+            value(&mut subscription, "balance", &self.price);
+        }
+        let transactions = subscription.append_new_child("transactions");
+        transactions.set_attr("type", "array");
+        for transaction in state.transactions.iter().filter(|transaction| self.transaction_ids.contains(&transaction.id)) {
+            transactions.append_child(transaction.render());
+        }
+        value(&mut subscription, "created-at", &self.created_at);
+        value(&mut subscription, "updated-at", &self.created_at);
+        subscription
+    }
+}
+
+impl FakeCustomer {
+    fn render(&self, state: &State, custom_fields: Option<&Element>) -> Element {
+        let mut customer = Element::new("customer");
+        value(&mut customer, "id", &self.id);
+        for (field, text) in &self.fields {
+            value(&mut customer, field, text);
+        }
+        if let Some(custom_fields) = custom_fields {
+            customer.append_child(custom_fields.clone());
+        }
+        let cards = customer.append_new_child("credit-cards");
+        cards.set_attr("type", "array");
+        for token in &self.payment_method_tokens {
+            cards.append_child(state.payment_methods[token].render());
+        }
+        value(&mut customer, "created-at", &self.created_at);
+        value(&mut customer, "updated-at", &self.created_at);
+        customer
+    }
+}
+
+impl Card {
+    fn render(&self) -> Element {
+        let mut card = Element::new("credit-card");
+        if let Some(ref token) = self.token {
+            value(&mut card, "token", token);
+        }
+        value(&mut card, "bin", &self.number[..6]);
+        value(&mut card, "last-4", &self.number[self.number.len() - 4..]);
+        value(&mut card, "card-type", &self.card_type);
+        value(&mut card, "expiration-month", &self.expiration_month);
+        value(&mut card, "expiration-year", &self.expiration_year);
+        if let Some(ref cardholder_name) = self.cardholder_name {
+            value(&mut card, "cardholder-name", cardholder_name);
+        }
+        if let Some(ref customer_id) = self.customer_id {
+            value(&mut card, "customer-id", customer_id);
+        }
+        card
+    }
+}
+
+/// A record that can be found with an advanced search.
+trait Searchable {
+    fn id(&self) -> &str;
+    /// The value of a search field, or `None` if the field isn't supported.
+    fn field(&self, name: &str) -> Option<String>;
+    fn render(&self) -> Element;
+}
+
+impl Searchable for FakeTransaction {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => Some(self.id.clone()),
+            "order-id" => self.order_id.clone(),
+            "type" => Some(String::from(self.typ.clone())),
+            "status" => Some(String::from(self.status.clone())),
+            "amount" => Some(self.amount.to_string()),
+            "created-at" => Some(self.created_at.to_text()),
+            "merchant-account-id" => Some(self.merchant_account_id.clone()),
+            "customer-id" => self.customer_id.clone(),
+            "payment-method-token" => self.card.token.clone(),
+            _ => None,
+        }
+    }
+
+    fn render(&self) -> Element {
+        FakeTransaction::render(self)
+    }
+}
+
+impl Searchable for FakeVerification {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => Some(self.id.clone()),
+            "status" => Some(String::from(self.status)),
+            "created-at" => Some(self.created_at.to_text()),
+            "credit-card-card-type" => Some(String::from(self.card.card_type.clone())),
+            "credit-card-cardholder-name" => self.card.cardholder_name.clone(),
+            "credit-card-number" => Some(self.card.number.clone()),
+            "customer-id" => self.card.customer_id.clone(),
+            "payment-method-token" => self.card.token.clone(),
+            _ => None,
+        }
+    }
+
+    fn render(&self) -> Element {
+        FakeVerification::render(self)
+    }
+}
+
+/// Whether a record matches every criterion of a search.
+fn matches<T: Searchable>(criteria: &Element, record: &T) -> bool {
+    criteria.children().all(|criterion| {
+        let field = match record.field(criterion.tag().name()) {
+            Some(field) => field,
+            None => return false,
+        };
+        if criterion.get_attr("type") == Some("array") {
+            return criterion.children().any(|item| item.text() == field);
+        }
+        criterion.children().all(|operator| {
+            let operand = operator.text();
+            match operator.tag().name() {
+                "is" => field == operand,
+                "is-not" => field != operand,
+                "starts-with" => field.starts_with(operand),
+                "ends-with" => field.ends_with(operand),
+                "contains" => field.contains(operand),
+                "min" => compare(&field, operand) != std::cmp::Ordering::Less,
+                "max" => compare(&field, operand) != std::cmp::Ordering::Greater,
+                _ => false,
+            }
+        })
+    })
+}
+
+/// Compares amounts by value, and anything else (i.e. timestamps in the
+/// same format) as text.
+fn compare(a: &str, b: &str) -> std::cmp::Ordering {
+    match (Amount::parse_for_currency(a, CURRENCY), Amount::parse_for_currency(b, CURRENCY)) {
+        (Ok(a), Ok(b)) => a.minor_units().cmp(&b.minor_units()),
+        _ => a.cmp(b),
+    }
+}
+
+impl Reply {
+    fn new(status: StatusCode, body: Element) -> Reply {
+        Reply{status, body: Some(body)}
+    }
+
+    fn empty(status: StatusCode) -> Reply {
+        Reply{status, body: None}
+    }
+
+    /// A validation error on `attribute` of the resource at `scope`, e.g.
+    /// `["transaction", "credit-card"]`.
+    fn invalid(scope: &[&str], attribute: &str, code: u32, message: &str) -> Reply {
+        let mut response = Element::new("api-error-response");
+        let mut errors = response.append_new_child("errors");
+        errors.append_new_child("errors").set_attr("type", "array");
+        for resource in scope {
+            errors = errors.append_new_child(*resource);
+        }
+        let error = errors.append_new_child("errors");
+        error.set_attr("type", "array");
+        let error = error.append_new_child("error");
+        value(error, "code", &code.to_string());
+        value(error, "attribute", attribute);
+        value(error, "message", message);
+        value(&mut response, "message", message);
+        Reply::new(StatusCode::UnprocessableEntity, response)
+    }
+
+    /// A transaction that the processor declined or that failed.
+    fn declined(code: u32, transaction: Element) -> Reply {
+        let mut response = Element::new("api-error-response");
+        response.append_new_child("errors").append_new_child("errors").set_attr("type", "array");
+        value(&mut response, "message", processor_response_text(code));
+        response.append_child(transaction);
+        Reply::new(StatusCode::UnprocessableEntity, response)
+    }
+}
+
+/// The status and processor response code for a new authorization, based on
+/// the sandbox's magic amounts.
+fn authorize(amount: &Amount) -> (Status, u32) {
+    let cents = amount.minor_units() * 100 / 10u64.pow(amount.exponent());
+    if amounts::PROCESSOR_DECLINED.contains(&cents) {
+        (Status::ProcessorDeclined, (cents / 100) as u32)
+    } else if amounts::FAILED.contains(&cents) {
+        (Status::Failed, 3000)
+    } else {
+        (Status::Authorized, 1000)
+    }
+}
+
+fn settle(transaction: &mut FakeTransaction) {
+    transaction.status = Status::Settled;
+    if transaction.escrow_status == Some(EscrowStatus::HoldPending) {
+        transaction.escrow_status = Some(EscrowStatus::Held);
+    }
+}
+
+fn processor_response_text(code: u32) -> &'static str {
+    match code {
+        1000 => "Approved",
+        1002 => "Processed",
+        2000 => "Do Not Honor",
+        2001 => "Insufficient Funds",
+        2002 => "Limit Exceeded",
+        2003 => "Cardholder's Activity Limit Exceeded",
+        2004 => "Expired Card",
+        2005 => "Invalid Credit Card Number",
+        2006 => "Invalid Expiration Date",
+        2010 => "Card Issuer Declined CVV",
+        2015 => "Transaction Not Allowed",
+        2046 => "Declined",
+        3000 => "Processor Network Unavailable - Try Again",
+        _ => "Processor Declined",
+    }
+}
+
+fn card_for_nonce(nonce: &str, scope: &[&str]) -> Result<Card, Reply> {
+    match nonce {
+        nonces::CONSUMED => return Err(Reply::invalid(scope, "payment-method-nonce", 93107, "Cannot use a payment_method_nonce more than once.")),
+        nonces::LUHN_INVALID => return Err(Reply::invalid(&[scope, &["credit-card"][..]].concat(), "number", 81715, "Credit card number is invalid.")),
+        _ => {},
+    }
+    let number = NONCES.iter().find(|&&(known, _)| known == nonce).map(|&(_, number)| number)
+        .ok_or_else(|| Reply::invalid(scope, "payment-method-nonce", 91565, "Unknown payment_method_nonce."))?;
+    let expiration_date = test_values::expiration_date();
+    let (month, year) = expiration_date.split_once('/').unwrap();
+    card(number, String::from(month), String::from(year), None, scope)
+}
+
+fn parse_card(element: &Element, scope: &[&str]) -> Result<Card, Reply> {
+    let number = text(element, "number")
+        .ok_or_else(|| Reply::invalid(scope, "number", 81714, "Credit card number is required."))?;
+    let (month, year) = match (text(element, "expiration-date"), text(element, "expiration-month"), text(element, "expiration-year")) {
+        (Some(date), _, _) => match date.split_once('/') {
+            Some((month, year)) => (month, year),
+            None => return Err(Reply::invalid(scope, "expiration-date", 81710, "Expiration date is invalid.")),
+        },
+        (None, Some(month), Some(year)) => (month, year),
+        _ => return Err(Reply::invalid(scope, "expiration-date", 81709, "Expiration date is required.")),
+    };
+    let month = match month.parse::<u32>() {
+        Ok(m @ 1..=12) => format!("{:02}", m),
+        _ => return Err(Reply::invalid(scope, "expiration-month", 81712, "Expiration month is invalid.")),
+    };
+    let year = match (year.len(), year.parse::<u32>()) {
+        (2, Ok(y)) => (2000 + y).to_string(),
+        (4, Ok(y)) => y.to_string(),
+        _ => return Err(Reply::invalid(scope, "expiration-year", 81713, "Expiration year is invalid.")),
+    };
+    let mut card = card(number, month, year, text(element, "cardholder-name").map(String::from), scope)?;
+    card.token = text(element, "token").map(String::from);
+    Ok(card)
+}
+
+fn card(number: &str, expiration_month: String, expiration_year: String, cardholder_name: Option<String>, scope: &[&str]) -> Result<Card, Reply> {
+    if !luhn(number) {
+        return Err(Reply::invalid(scope, "number", 81715, "Credit card number is invalid."));
+    }
+    let &(_, kind, verification_decline) = CARDS.iter().find(|&&(known, _, _)| known == number)
+        .ok_or_else(|| Reply::invalid(scope, "number", 81717, "Credit card number is not an accepted test number."))?;
+    Ok(Card{
+        number: String::from(number),
+        card_type: CardType::from(kind),
+        expiration_month,
+        expiration_year,
+        cardholder_name,
+        verification_decline,
+        token: None,
+        customer_id: None,
+    })
+}
+
+fn luhn(number: &str) -> bool {
+    if number.len() < 12 || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let sum: u32 = number.bytes().rev().enumerate().map(|(i, b)| {
+        let digit = u32::from(b - b'0');
+        match i % 2 {
+            0 => digit,
+            _ if digit > 4 => digit * 2 - 9,
+            _ => digit * 2,
+        }
+    }).sum();
+    sum.is_multiple_of(10)
+}
+
+fn parse_amount(element: &Element, tag: &str, scope: &[&str]) -> Result<Amount, Reply> {
+    let amount = match text(element, tag) {
+        Some(amount) => Amount::parse_for_currency(amount, CURRENCY)
+            .map_err(|_| Reply::invalid(scope, tag, 81503, "Amount is an invalid format."))?,
+        None => return Err(Reply::invalid(scope, tag, 81502, "Amount is required.")),
+    };
+    match amount.is_zero() {
+        true => Err(Reply::invalid(scope, tag, 81531, "Amount must be greater than zero.")),
+        false => Ok(amount),
+    }
+}
+
+fn add_month(date: chrono::NaiveDate) -> chrono::NaiveDate {
+    date.checked_add_months(chrono::Months::new(1)).unwrap_or(date)
+}
+
+/// The text of a child element, unless it's missing or empty.
+fn text<'a>(element: &'a Element, tag: &str) -> Option<&'a str> {
+    element.children().find(|child| child.tag().name() == tag).map(Element::text).filter(|text| !text.is_empty())
+}
+
+/// Appends a child element holding a value, annotated with its type as in
+/// a request body.
+fn value<T: XmlValue + ?Sized>(parent: &mut Element, tag: &str, value: &T) {
+    let child = parent.append_new_child(tag);
+    if let Some(typ) = T::TYPE {
+        child.set_attr("type", typ);
+    }
+    child.set_text(value.to_text());
+}
+
+fn generate_id(len: usize) -> String {
+    std::iter::repeat_with(|| fastrand::alphanumeric().to_ascii_lowercase()).take(len).collect()
+}
+
+fn to_string(element: &Element) -> String {
+    element.to_string().expect("failed to write a fake response")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FakeGateway, PRIVATE_KEY, PUBLIC_KEY};
+    use crate::amount::Amount;
+    use crate::credit_card::CreditCard;
+    use crate::customer::Customer;
+    use crate::error::Error;
+    use crate::line_item::{self, LineItem};
+    use crate::middleware::{self, BoxError, Middleware};
+    use crate::retry::RetryPolicy;
+    use crate::search;
+    use crate::transport::{self, Transport};
+    use crate::secret::Secret;
+    use crate::subscription;
+    use crate::test_values::card_numbers;
+    use crate::transaction;
+    use crate::webhook_notification::Kind;
+
+    fn sale(amount: &str) -> transaction::Request {
+        transaction::Request{
+            amount: amount.parse().unwrap(),
+            payment_method_nonce: Some(Secret::from("fake-valid-nonce")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn honors_magic_amounts_and_transitions() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.client();
+
+        match bt.transaction().create(sale("2001.00")) {
+            Err(Error::Api(response)) => {
+                assert_eq!(response.message, "Insufficient Funds");
+                assert_eq!(response.transaction().unwrap().status, transaction::Status::ProcessorDeclined);
+            },
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        let sale = bt.transaction().create(sale("10.00")).unwrap();
+        assert!(bt.transaction().refund(sale.id.clone()).is_err());
+        bt.transaction().submit_for_settlement(sale.id.clone()).unwrap();
+        gateway.advance_settlement();
+        gateway.advance_settlement();
+        assert!(bt.transaction().void(sale.id.clone()).is_err());
+
+        let refund = bt.transaction().refund_partial(sale.id.clone(), "4.00".parse().unwrap()).unwrap();
+        assert_eq!(refund.typ, transaction::Type::Credit);
+        assert!(bt.transaction().refund_partial(sale.id, "6.01".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn signs_sample_notifications() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.client();
+
+        let (signature, payload) = gateway.sample_notification(Kind::SubMerchantAccountApproved, "seller");
+        let notification = bt.webhook_notification().parse(&signature, &payload).unwrap();
+        assert_eq!(notification.merchant_account().unwrap().unwrap().id, "seller");
+
+        let (signature, payload) = gateway.sample_notification(Kind::SubMerchantAccountDeclined, "seller");
+        let notification = bt.webhook_notification().parse(&signature, &payload).unwrap();
+        assert_eq!(notification.kind, Kind::SubMerchantAccountDeclined);
+        assert!(notification.error_message().is_some());
+        assert!(bt.webhook_notification().parse(&signature, &format!("{}=", payload)).is_err());
+    }
+
+    fn customer_with_card(token: &str) -> Customer {
+        Customer{
+            credit_card: Some(CreditCard{
+                number: Some(Secret::from(card_numbers::VISA)),
+                expiration_date: Some(String::from("12/2030")),
+                token: Some(String::from(token)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn vaults_cards_and_rejects_token_reuse() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.client();
+
+        let customer = bt.customer().generate(customer_with_card("card-1")).unwrap();
+        assert!(customer.id.is_some());
+        assert_eq!(customer.credit_card.unwrap().token.as_deref(), Some("card-1"));
+
+        let sale = bt.transaction().create(transaction::Request{
+            payment_method_token: Some(String::from("card-1")),
+            payment_method_nonce: None,
+            ..sale("10.00")
+        }).unwrap();
+        assert_eq!(sale.status, transaction::Status::Authorized);
+
+        match bt.customer().generate(customer_with_card("card-1")) {
+            Err(Error::Api(response)) => assert_eq!(response.message, "Token is in use by another payment method."),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn creates_subscriptions() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.client();
+        gateway.add_plan("monthly", "12.00".parse().unwrap());
+        bt.customer().generate(customer_with_card("card-1")).unwrap();
+        let request = || subscription::Request{
+            plan_id: Some(String::from("monthly")),
+            payment_method_token: Some(String::from("card-1")),
+            ..Default::default()
+        };
+
+        let active = bt.subscription().create(request()).unwrap();
+        let today = chrono::Utc::now().date_naive();
+        assert_eq!(active.status, "Active");
+        assert_eq!(active.billing_period_start_date, Some(today));
+        assert_eq!(bt.transaction().search(Default::default()).unwrap().len(), 1);
+
+        let first_billing_date = today + chrono::Duration::days(10);
+        let pending = bt.subscription().create(subscription::Request{first_billing_date: Some(first_billing_date), ..request()}).unwrap();
+        assert_eq!(pending.status, "Pending");
+        assert_eq!(pending.first_billing_date, Some(first_billing_date));
+        assert_eq!(pending.billing_period_start_date, None);
+        assert_eq!(bt.transaction().search(Default::default()).unwrap().len(), 1);
+
+        let yesterday = today - chrono::Duration::days(1);
+        assert!(bt.subscription().create(subscription::Request{first_billing_date: Some(yesterday), ..request()}).is_err());
+    }
+
+    #[test]
+    fn holds_and_releases_escrow() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.client();
+
+        let unescrowed = bt.transaction().create(sale("10.00")).unwrap();
+        assert!(bt.transaction().hold_in_escrow(unescrowed.id).is_err());
+
+        let held = bt.transaction().create(transaction::Request{merchant_account_id: Some(String::from("seller")), ..sale("10.00")}).unwrap();
+        let sale = bt.transaction().hold_in_escrow(held.id).unwrap();
+        assert_eq!(sale.escrow_status, Some(transaction::EscrowStatus::HoldPending));
+        assert!(bt.transaction().release_from_escrow(sale.id.clone()).is_err());
+
+        bt.transaction().submit_for_settlement(sale.id.clone()).unwrap();
+        gateway.advance_settlement();
+        gateway.advance_settlement();
+        assert_eq!(bt.transaction().find(sale.id.clone()).unwrap().escrow_status, Some(transaction::EscrowStatus::Held));
+
+        let releasing = bt.transaction().release_from_escrow(sale.id.clone()).unwrap();
+        assert_eq!(releasing.escrow_status, Some(transaction::EscrowStatus::ReleasePending));
+        let cancelled = bt.transaction().cancel_release(sale.id.clone()).unwrap();
+        assert_eq!(cancelled.escrow_status, Some(transaction::EscrowStatus::Held));
+        assert!(bt.transaction().cancel_release(sale.id.clone()).is_err());
+
+        bt.transaction().release_from_escrow(sale.id.clone()).unwrap();
+        gateway.advance_settlement();
+        assert_eq!(bt.transaction().find(sale.id).unwrap().escrow_status, Some(transaction::EscrowStatus::Released));
+    }
+
+    #[test]
+    fn searches_by_order_id() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.client();
+        let first = bt.transaction().create(order("order-1")).unwrap();
+        bt.transaction().create(order("order-2")).unwrap();
+        bt.transaction().create(sale("10.00")).unwrap();
+
+        let search = |order_id: &str| transaction::Search{order_id: Some(search::Text::Is(String::from(order_id))), ..Default::default()};
+        let found = bt.transaction().search(search("order-1")).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, first.id);
+        assert!(bt.transaction().search(search("order-3")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn requires_the_fake_credentials() {
+        use base64::Engine;
+
+        let gateway = FakeGateway::new();
+        let request = |authorization: Option<String>| {
+            let mut headers = hyper::header::Headers::new();
+            if let Some(authorization) = authorization {
+                headers.set_raw("Authorization", vec![authorization.into_bytes()]);
+            }
+            let url = format!("https://example.com/merchants/{}/transactions/missing", super::MERCHANT_ID);
+            let request = transport::Request{method: hyper::method::Method::Get, url: url.parse().unwrap(), headers, body: None};
+            gateway.send(request).unwrap().status
+        };
+        let basic = |private_key: &str| {
+            Some(format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", PUBLIC_KEY, private_key))))
+        };
+        assert_eq!(request(None), hyper::status::StatusCode::Unauthorized);
+        assert_eq!(request(basic("wrong_private_key")), hyper::status::StatusCode::Unauthorized);
+        assert_eq!(request(basic(PRIVATE_KEY)), hyper::status::StatusCode::NotFound);
+
+        let bt = gateway.builder().private_key("wrong_private_key").build().unwrap();
+        assert!(bt.transaction().create(sale("10.00")).is_err());
+        assert!(gateway.client().transaction().search(Default::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn returns_line_items() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.client();
+        let amount = |amount: &str| Some(amount.parse::<Amount>().unwrap());
+        let with_line_items = bt.transaction().create(transaction::Request{
+            line_items: vec![
+                LineItem{
+                    quantity: Some(String::from("2")),
+                    name: Some(String::from("Widget")),
+                    kind: Some(line_item::Kind::Debit),
+                    unit_amount: amount("5.00"),
+                    total_amount: amount("10.00"),
+                    product_code: Some(String::from("W-1")),
+                    ..Default::default()
+                },
+                LineItem{
+                    quantity: Some(String::from("1")),
+                    name: Some(String::from("Coupon")),
+                    kind: Some(line_item::Kind::Credit),
+                    unit_amount: amount("1.00"),
+                    total_amount: amount("1.00"),
+                    ..Default::default()
+                },
+            ],
+            ..sale("9.00")
+        }).unwrap();
+
+        let line_items = bt.transaction().line_items(with_line_items.id).unwrap();
+        assert_eq!(line_items.len(), 2);
+        assert_eq!(line_items[0].name.as_deref(), Some("Widget"));
+        assert_eq!(line_items[0].quantity.as_deref(), Some("2"));
+        assert_eq!(line_items[0].kind, Some(line_item::Kind::Debit));
+        assert_eq!(line_items[0].unit_amount, amount("5.00"));
+        assert_eq!(line_items[0].total_amount, amount("10.00"));
+        assert_eq!(line_items[0].product_code.as_deref(), Some("W-1"));
+        assert_eq!(line_items[1].kind, Some(line_item::Kind::Credit));
+        assert_eq!(line_items[1].description, None);
+
+        let other = bt.transaction().create(sale("10.00")).unwrap();
+        assert!(bt.transaction().line_items(other.id).unwrap().is_empty());
+    }
+
+    /// Turns the response to the first transaction create into a server
+    /// error, after the fake has recorded the transaction.
+    struct FailFirstCreate(std::sync::atomic::AtomicBool);
+
+    impl Middleware for FailFirstCreate {
+        fn on_response(&self, request: &transport::Request, response: &mut middleware::Response) -> Result<(), BoxError> {
+            if request.url.path().ends_with("/transactions") && !self.0.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                response.status = hyper::status::StatusCode::InternalServerError;
+                response.body = b"<api-error-response><message>Internal Server Error</message></api-error-response>".to_vec();
+            }
+            Ok(())
+        }
+    }
+
+    /// Delivers every request, then drops the connection before the
+    /// response to a transaction create arrives.
+    #[derive(Clone)]
+    struct DropCreateResponses(FakeGateway);
+
+    impl Transport for DropCreateResponses {
+        fn send(&self, request: transport::Request) -> hyper::Result<transport::Response> {
+            let create = request.url.path().ends_with("/transactions");
+            let response = self.0.send(request)?;
+            if create {
+                return Err(hyper::Error::Io(std::io::Error::from(std::io::ErrorKind::ConnectionReset)));
+            }
+            Ok(response)
+        }
+    }
+
+    fn order(order_id: &str) -> transaction::Request {
+        transaction::Request{order_id: Some(String::from(order_id)), ..sale("10.00")}
+    }
+
+    fn recovering() -> RetryPolicy {
+        RetryPolicy{recover_by_order_id: true, initial_backoff: Duration::from_millis(1), ..Default::default()}
+    }
+
+    #[test]
+    fn recovers_creates_by_order_id() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.builder()
+            .middleware(FailFirstCreate(Default::default()))
+            .retry_policy(recovering())
+            .build()
+            .unwrap();
+        let recovered = bt.transaction().create(order("order-1")).unwrap();
+        assert_eq!(recovered.status, transaction::Status::Authorized);
+        let search = transaction::Search{order_id: Some(search::Text::Is(String::from("order-1"))), ..Default::default()};
+        assert_eq!(bt.transaction().search(search).unwrap().len(), 1);
+
+        let gateway = FakeGateway::new();
+        let bt = gateway.builder().transport(DropCreateResponses(gateway.clone())).retry_policy(recovering()).build().unwrap();
+        let recovered = bt.transaction().create(order("order-2")).unwrap();
+        let all = bt.transaction().search(Default::default()).unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].id, recovered.id);
+    }
+
+    /// Drops transaction creates before they reach the gateway.
+    #[derive(Clone)]
+    struct DropCreates(FakeGateway);
+
+    impl Transport for DropCreates {
+        fn send(&self, request: transport::Request) -> hyper::Result<transport::Response> {
+            if request.url.path().ends_with("/transactions") {
+                return Err(hyper::Error::Io(std::io::Error::from(std::io::ErrorKind::ConnectionReset)));
+            }
+            self.0.send(request)
+        }
+    }
+
+    #[test]
+    fn does_not_recover_other_transactions_with_the_order_id() {
+        let gateway = FakeGateway::new();
+        gateway.client().transaction().create(transaction::Request{order_id: Some(String::from("order-1")), ..sale("5.00")}).unwrap();
+        gateway.client().transaction().create(order("order-2")).unwrap();
+        gateway.state.lock().unwrap().transactions[1].created_at -= chrono::Duration::hours(1);
+
+        let bt = gateway.builder().transport(DropCreates(gateway.clone())).retry_policy(recovering()).build().unwrap();
+        assert!(matches!(bt.transaction().create(order("order-1")), Err(Error::Http(_))));
+        assert!(matches!(bt.transaction().create(order("order-2")), Err(Error::Http(_))));
+        assert_eq!(bt.transaction().search(Default::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn does_not_recover_without_the_option() {
+        let gateway = FakeGateway::new();
+        let bt = gateway.builder().middleware(FailFirstCreate(Default::default())).build().unwrap();
+        assert!(matches!(bt.transaction().create(order("order-1")), Err(Error::Api(_))));
+        assert_eq!(bt.transaction().search(Default::default()).unwrap().len(), 1);
+
+        let gateway = FakeGateway::new();
+        let bt = gateway.builder().transport(DropCreateResponses(gateway.clone())).build().unwrap();
+        assert!(matches!(bt.transaction().create(order("order-2")), Err(Error::Http(_))));
+        assert_eq!(bt.transaction().search(Default::default()).unwrap().len(), 1);
+
+        let bt = gateway.builder().transport(DropCreateResponses(gateway.clone())).retry_policy(recovering()).build().unwrap();
+        assert!(matches!(bt.transaction().create(sale("10.00")), Err(Error::Http(_))));
+    }
+
+    fn stub() -> transport::Stub {
+        transport::Stub::new(|request| {
+            let timestamps = "<created-at type=\"datetime\">2024-01-02T03:04:05Z</created-at><updated-at type=\"datetime\">2024-01-02T03:04:05Z</updated-at>";
+            let body = match request.url.path().contains("/subscriptions/") {
+                true => format!("<subscription><id>sub</id><plan-id>monthly</plan-id><payment-method-token>card</payment-method-token>\
+                                 <status>Past Due</status>{}</subscription>", timestamps),
+                false => format!("<transaction><id>abc</id><type>sale</type><amount>10.00</amount><currency-iso-code>USD</currency-iso-code>\
+                                  <status>settled</status>{}</transaction>", timestamps),
+            };
+            match request.method {
+                hyper::method::Method::Post => (hyper::status::StatusCode::Created, body),
+                _ => (hyper::status::StatusCode::Ok, body),
+            }
+        })
+    }
+
+    fn stub_client(env: crate::Environment, stub: &transport::Stub) -> crate::Braintree {
+        crate::Braintree::builder()
+            .environment(env)
+            .merchant_id("my_merchant_id")
+            .public_key("my_public_key")
+            .private_key("my_private_key")
+            .transport(stub.clone())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn testing_helpers_refuse_production() {
+        let stub = stub();
+        let bt = stub_client(crate::Environment::Production, &stub);
+        let testing = bt.testing();
+        let id = || String::from("abc");
+        let results = [
+            testing.settle(id()).err(),
+            testing.settlement_confirm(id()).err(),
+            testing.settlement_decline(id()).err(),
+            testing.settlement_pending(id()).err(),
+            testing.settlement_pending_with_reason(id(), "Reason").err(),
+            testing.create_disputed_transaction("10.00".parse().unwrap()).err(),
+        ];
+        for result in results {
+            assert!(matches!(result, Some(Error::TestOperationInProduction)), "{:?}", result);
+        }
+        assert!(matches!(testing.make_past_due(String::from("sub"), 3), Err(Error::TestOperationInProduction)));
+        assert!(stub.requests().is_empty());
+    }
+
+    #[test]
+    fn testing_helpers_send_their_requests() {
+        let stub = stub();
+        let bt = stub_client(crate::Environment::Sandbox, &stub);
+        let testing = bt.testing();
+        testing.settle(String::from("abc")).unwrap();
+        testing.settlement_confirm(String::from("abc")).unwrap();
+        testing.settlement_decline(String::from("abc")).unwrap();
+        testing.settlement_pending(String::from("abc")).unwrap();
+        testing.settlement_pending_with_reason(String::from("abc"), "Awaiting funds").unwrap();
+        testing.create_disputed_transaction("10.00".parse().unwrap()).unwrap();
+        testing.make_past_due(String::from("sub"), 3).unwrap();
+
+        let requests = stub.requests();
+        let sent: Vec<(String, String)> = requests.iter()
+            .map(|request| (request.method.to_string(), format!("{}{}", request.url.path(), request.url.query().map(|q| format!("?{}", q)).unwrap_or_default())))
+            .collect();
+        let expected = [
+            ("PUT", "/merchants/my_merchant_id/transactions/abc/settle"),
+            ("PUT", "/merchants/my_merchant_id/transactions/abc/settlement_confirm"),
+            ("PUT", "/merchants/my_merchant_id/transactions/abc/settlement_decline"),
+            ("PUT", "/merchants/my_merchant_id/transactions/abc/settlement_pending"),
+            ("PUT", "/merchants/my_merchant_id/transactions/abc/settlement_pending"),
+            ("POST", "/merchants/my_merchant_id/transactions"),
+            ("PUT", "/merchants/my_merchant_id/subscriptions/sub/make_past_due?days_past_due=3"),
+        ];
+        assert_eq!(sent, expected.iter().map(|(method, path)| (String::from(*method), String::from(*path))).collect::<Vec<_>>());
+
+        let body = |i: usize| requests[i].body.as_ref().map(|body| String::from_utf8(body.clone()).unwrap());
+        for i in [0, 1, 2, 3, 6] {
+            assert_eq!(body(i), None);
+        }
+        assert!(body(4).unwrap().contains("<processor-settlement-response-text>Awaiting funds</processor-settlement-response-text>"));
+        let dispute = body(5).unwrap();
+        assert!(dispute.contains(&format!("<number>{}</number>", crate::test_values::card_numbers::DISPUTE)), "{}", dispute);
+        assert!(dispute.contains(r#"<submit-for-settlement type="boolean">true</submit-for-settlement>"#), "{}", dispute);
+    }
+}
@@ -0,0 +1,223 @@
+//! Instrumentation of API calls with `tracing`, enabled by the `tracing`
+//! feature.
+//!
+//! Every request is wrapped in a `braintree.request` span, carrying its
+//! method, path template (e.g. `transactions/{id}/void`, so that no IDs end
+//! up in the logs), merchant ID, status code, latency, retry count and
+//! request ID. Request and response bodies are logged at the `TRACE` level,
+//! with card data and credentials masked. Without the feature, this is all
+//! compiled away.
+
+use std::io::Read;
+
+use crate::error;
+use crate::transport;
+
+/// Card data whose text is masked when bodies are logged or recorded,
+/// keeping the last four characters of long values, as `Secret` does.
+const CARD_DATA: &[&str] = &["number", "cvv", "payment-method-nonce", "account-number"];
+
+/// Credentials and personal data whose text is masked entirely. The `code`
+/// of a validation error, as opposed to an OAuth authorization code, is
+/// left alone.
+const SENSITIVE: &[&str] = &[
+    "private-key", "client-secret", "access-token", "refresh-token", "token", "code",
+    "ssn", "tax-id", "date-of-birth", "routing-number",
+    "cardholder-name", "first-name", "last-name", "email", "phone", "mobile-phone", "fax",
+    "street-address", "extended-address", "postal-code",
+];
+
+/// The span of a single API call, including its retries.
+pub(crate) struct RequestSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "tracing")]
+    started: std::time::Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl RequestSpan {
+    pub fn start(method: &hyper::method::Method, template: &str, merchant_id: Option<&str>, body: Option<&[u8]>) -> RequestSpan {
+        use tracing::field::Empty;
+
+        let span = tracing::info_span!(
+            "braintree.request",
+            method = %method,
+            path = template,
+            merchant_id = merchant_id.unwrap_or(""),
+            status = Empty,
+            latency_ms = Empty,
+            retries = Empty,
+            request_id = Empty,
+            error = Empty,
+        ).entered();
+        if let Some(body) = body {
+            tracing::trace!(body = %redact(&String::from_utf8_lossy(body)), "request body");
+        }
+        RequestSpan{span, started: std::time::Instant::now()}
+    }
+
+    pub fn retrying(&self, attempt: u32) {
+        tracing::debug!(attempt, "retrying request");
+    }
+
+    pub fn finish(self, result: &error::Result<transport::Response>, retries: u32) {
+        self.span.record("latency_ms", self.started.elapsed().as_millis() as u64);
+        self.span.record("retries", retries);
+        match *result {
+            Ok(ref response) => {
+                self.span.record("status", response.status.to_u16());
+                if let Some(request_id) = request_id(&response.headers) {
+                    self.span.record("request_id", request_id.as_str());
+                }
+            },
+            Err(ref err) => {
+                self.span.record("error", tracing::field::display(err));
+            },
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl RequestSpan {
+    pub fn start(_method: &hyper::method::Method, _template: &str, _merchant_id: Option<&str>, _body: Option<&[u8]>) -> RequestSpan {
+        RequestSpan{}
+    }
+
+    pub fn retrying(&self, _attempt: u32) {}
+
+    pub fn finish(self, _result: &error::Result<transport::Response>, _retries: u32) {}
+}
+
+/// Logs a decoded response body, if `TRACE` is enabled. The body has to be
+/// buffered to do so, and is handed back as a new reader.
+#[cfg(feature = "tracing")]
+pub(crate) fn response_body(mut reader: Box<dyn Read>) -> hyper::Result<Box<dyn Read>> {
+    if !tracing::enabled!(tracing::Level::TRACE) {
+        return Ok(reader);
+    }
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body)?;
+    tracing::trace!(body = %redact(&String::from_utf8_lossy(&body)), "response body");
+    Ok(Box::new(std::io::Cursor::new(body)))
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn response_body(reader: Box<dyn Read>) -> hyper::Result<Box<dyn Read>> {
+    Ok(reader)
+}
+
+#[cfg(feature = "tracing")]
+fn request_id(headers: &hyper::header::Headers) -> Option<String> {
+    ["X-Request-Id", "Request-Id"].iter()
+        .filter_map(|name| headers.get_raw(name))
+        .filter_map(|values| values.first())
+        .map(|value| String::from_utf8_lossy(value).into_owned())
+        .next()
+}
+
+/// Masks the text of card data, credentials and personal data in an XML
+/// body.
+pub(crate) fn redact(xml: &str) -> String {
+    use crate::secret::Secret;
+
+    let mut out = String::with_capacity(xml.len());
+    let mut open_elements = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let tag = &rest[start..];
+        let end = match tag.find('>') {
+            Some(end) => end + 1,
+            None => {
+                rest = tag;
+                break;
+            },
+        };
+        let open = &tag[..end];
+        out.push_str(open);
+        rest = &tag[end..];
+
+        if open.starts_with("</") {
+            open_elements.pop();
+            continue;
+        }
+        if open.starts_with("<?") || open.starts_with("<!") || open.ends_with("/>") {
+            continue;
+        }
+        let name = open[1..].split(|c: char| c.is_whitespace() || c == '>').next().unwrap_or("");
+        let parent = open_elements.last().copied();
+        open_elements.push(name);
+        let card_data = CARD_DATA.contains(&name);
+        if !(card_data || SENSITIVE.contains(&name)) || (name == "code" && parent == Some("error")) {
+            continue;
+        }
+        if let Some(len) = rest.find(&format!("</{}>", name)) {
+            let text = &rest[..len];
+            let secret = if card_data { Secret::new(text) } else { Secret::credential(text) };
+            out.push_str(&secret.to_string());
+            rest = &rest[len..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redact;
+
+    #[test]
+    fn redacts_sensitive_elements() {
+        let xml = "<?xml version=\"1.0\"?><transaction><amount type=\"decimal\">10.00</amount>\
+                   <credit-card><number>4111111111111111</number><cvv>123</cvv></credit-card>\
+                   <payment-method-nonce>fake-valid-nonce</payment-method-nonce><customer-id/></transaction>";
+        assert_eq!(redact(xml), "<?xml version=\"1.0\"?><transaction><amount type=\"decimal\">10.00</amount>\
+                                 <credit-card><number>************1111</number><cvv>***</cvv></credit-card>\
+                                 <payment-method-nonce>************once</payment-method-nonce><customer-id/></transaction>");
+    }
+
+    #[test]
+    fn redacts_personal_data() {
+        let xml = "<merchant-account><individual><first-name>Jane</first-name><last-name>Doe</last-name>\
+                   <email>jane@example.com</email><date-of-birth type=\"date\">1981-11-19</date-of-birth>\
+                   <ssn>123-45-6789</ssn><address><street-address>111 Main St</street-address>\
+                   <postal-code>60622</postal-code><region>IL</region></address></individual>\
+                   <business><legal-name>Jane's Ladders</legal-name><tax-id>98-7654321</tax-id></business>\
+                   <funding><destination>bank</destination><account-number>1123581321</account-number>\
+                   <routing-number>071101307</routing-number></funding><tos-accepted>true</tos-accepted></merchant-account>";
+        let redacted = redact(xml);
+        for value in ["Jane<", "Doe", "jane@example.com", "1981-11-19", "6789", "111 Main St", "60622", "7654321", "071101307", "11235813"] {
+            assert!(!redacted.contains(value), "{} in {}", value, redacted);
+        }
+        assert!(redacted.contains("<ssn>********</ssn>"));
+        assert!(redacted.contains("<account-number>******1321</account-number>"));
+        assert!(redacted.contains("<region>IL</region>"));
+        assert!(redacted.contains("<legal-name>Jane's Ladders</legal-name>"));
+        assert!(redacted.contains("<tos-accepted>true</tos-accepted>"));
+    }
+
+    #[test]
+    fn keeps_validation_error_codes() {
+        let xml = "<api-error-response><errors><transaction><errors type=\"array\"><error>\
+                   <code>81502</code><attribute>amount</attribute></error></errors></transaction></errors></api-error-response>";
+        assert_eq!(redact(xml), xml);
+    }
+
+    #[test]
+    fn redacts_oauth_tokens_and_codes() {
+        use crate::encode;
+        use crate::oauth::TokenRequest;
+
+        let revoke = encode::value_to_xml("token", "access_token$sandbox$merchant$abc123").unwrap();
+        let redacted = redact(&revoke);
+        assert!(!redacted.contains("abc123"), "{}", redacted);
+        assert!(redacted.contains("<token>********</token>"));
+
+        let grant = encode::to_xml("credentials", &TokenRequest::Code{code: "a1b2c3d4e5", scope: Some("read_write")}).unwrap();
+        let redacted = redact(&grant);
+        assert!(!redacted.contains("a1b2c3d4e5"), "{}", redacted);
+        assert!(redacted.contains("<code>********</code>"));
+        assert!(redacted.contains("authorization_code"));
+    }
+}
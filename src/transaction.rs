@@ -1,36 +1,60 @@
-use elementtree;
+use chrono;
 use std::convert::From;
 use std::collections::HashMap;
-use std::io::Read;
-use std::fmt::Write;
-use xml;
 
 use crate::address;
+use crate::amount::Amount;
 use crate::credit_card;
 use crate::customer;
+use crate::decode::{DecodeError, FromXml, Node};
 use crate::descriptor;
+use crate::line_item;
+use crate::search;
+use crate::secret::Secret;
 
-use crate::ToXml;
+use crate::encode::{ToXml, Writer};
 /// A record containing transaction details.
 #[derive(Debug)]
 pub struct Transaction {
     pub id: String,
     pub typ: Type,
-    pub amount: String, // change to a decmial?
+    pub amount: Amount,
     pub currency_iso_code: String,
     pub status: Status,
+    /// Only set for transactions on a sub-merchant account that were held
+    /// in escrow.
+    pub escrow_status: Option<EscrowStatus>,
+    /// The processor's response to the authorization, e.g. `1000` for an
+    /// approval or `2001` for insufficient funds.
+    pub processor_response_code: Option<String>,
+    pub processor_response_text: Option<String>,
+    /// The processor's response to settlement, set for transactions that
+    /// are `SettlementPending` or `SettlementDeclined`.
+    pub processor_settlement_response_code: Option<String>,
+    pub processor_settlement_response_text: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
-impl From<Box<dyn Read>> for Transaction {
-    fn from(xml: Box<dyn Read>) -> Transaction {
-        let root = elementtree::Element::from_reader(xml).unwrap();
-        Transaction{
-            id: String::from(root.find("id").unwrap().text()),
-            typ: Type::from(String::from(root.find("type").unwrap().text())),
-            amount: String::from(root.find("amount").unwrap().text()),
-            currency_iso_code: String::from(root.find("currency-iso-code").unwrap().text()),
-            status: Status::from(String::from(root.find("status").unwrap().text())),
-        }
+impl FromXml for Transaction {
+    fn from_xml(node: &Node) -> Result<Transaction, DecodeError> {
+        let currency_iso_code: String = node.child("currency-iso-code")?;
+        Ok(Transaction{
+            id: node.child("id")?,
+            typ: node.child("type")?,
+            amount: node.child_with("amount", |n| {
+                Amount::parse_for_currency(n.text()?, &currency_iso_code).map_err(|_| n.invalid("decimal"))
+            })?,
+            currency_iso_code,
+            status: node.child("status")?,
+            escrow_status: node.child_opt("escrow-status")?,
+            processor_response_code: node.child_opt("processor-response-code")?,
+            processor_response_text: node.child_opt("processor-response-text")?,
+            processor_settlement_response_code: node.child_opt("processor-settlement-response-code")?,
+            processor_settlement_response_text: node.child_opt("processor-settlement-response-text")?,
+            created_at: node.child("created-at")?,
+            updated_at: node.child("updated-at")?,
+        })
     }
 }
 
@@ -40,15 +64,18 @@ impl From<Box<dyn Read>> for Transaction {
 /// you'll want to use the `Default` trait to fill it out:
 ///
 /// ```rust
+/// # use braintree::transaction;
+/// # let _ =
 /// transaction::Request{
-///     amount: String::from("10.00"),
+///     amount: "10.00".parse().unwrap(),
 ///     ..Default::default()
 /// }
+/// # ;
 /// ```
 #[derive(Debug, Default)]
 pub struct Request {
     pub typ: Type,
-    pub amount: String, // change to a decmial?
+    pub amount: Amount,
     pub order_id: Option<String>,
     pub billing_address_id: Option<String>,
     pub billing: Option<address::Address>,
@@ -57,65 +84,59 @@ pub struct Request {
     pub customer: Option<customer::Customer>,
     pub customer_id: Option<String>,
     pub descriptor: Option<descriptor::Descriptor>,
+    /// The discount applied to the whole order, for Level 2/3 processing.
+    pub discount_amount: Option<Amount>,
+    /// Itemized order details, for Level 3 processing. Up to 249 line items
+    /// may be sent with a transaction.
+    pub line_items: Vec<line_item::LineItem>,
+    /// The merchant account to process the transaction with. For
+    /// marketplaces, this is the seller's sub-merchant account.
+    pub merchant_account_id: Option<String>,
     pub options: Option<Options>,
-    pub payment_method_nonce: Option<String>,
+    pub payment_method_nonce: Option<Secret>,
     pub payment_method_token: Option<String>,
     pub purchase_order_number: Option<String>,
     pub recurring: Option<bool>,
-    pub service_fee_amount: Option<String>,
+    /// The marketplace's fee, which is withheld from the sub-merchant's
+    /// funds. Requires `merchant_account_id`.
+    pub service_fee_amount: Option<Amount>,
     pub shipping: Option<address::Address>,
     pub shipping_address_id: Option<String>,
-    pub tax_amount: Option<String>,
+    /// The shipping cost for the whole order, for Level 2/3 processing.
+    pub shipping_amount: Option<Amount>,
+    /// The postal code the order ships from, for Level 3 processing.
+    pub ships_from_postal_code: Option<String>,
+    pub tax_amount: Option<Amount>,
     pub tax_exempt: Option<bool>,
 }
 
 impl ToXml for Request {
-    fn to_xml(&self, name: Option<&str>) -> String {
-        let name = xml::escape(&name.unwrap_or("transaction"));
-        let mut s = String::new();
-        write!(s, "<{}>", name).unwrap();
-
-        write!(s, "<type>{}</type>", String::from(self.typ)).unwrap();
-        write!(s, "<amount>{}</amount>", xml::escape(&self.amount)).unwrap();
-        write_xml!(s, "order-id", self.order_id);
-        write_xml!(s, "billing-address-id", self.billing_address_id);
-
-        if let Some(ref billing) = self.billing { write!(s, "{}", billing.to_xml(Some("billing"))).unwrap(); }
-
-        if let Some(ref credit_card) = self.credit_card { write!(s, "{}", credit_card.to_xml(None)).unwrap(); }
-
-        if !self.custom_fields.is_empty() {
-            write!(s, "<custom-fields>").unwrap();
-            for (k, v) in &self.custom_fields {
-                let k = xml::escape(k);
-                let v = xml::escape(v);
-                write!(s, "<{}>{}</{}>", k, v, k).unwrap();
-            }
-            write!(s, "</custom-fields>").unwrap();
-        }
-
-        if let Some(ref customer) = self.customer { write!(s, "{}", customer.to_xml(None)).unwrap(); }
-
-        write_xml!(s, "customer-id", self.customer_id);
-
-        if let Some(ref descriptor) = self.descriptor { write!(s, "{}", descriptor.to_xml(None)).unwrap(); }
-
-        if let Some(ref options) = self.options { write!(s, "{}", options.to_xml(None)).unwrap(); }
-
-        write_xml!(s, "payment-method-nonce", self.payment_method_nonce);
-        write_xml!(s, "payment-method-token", self.payment_method_token);
-        write_xml!(s, "purchase-order-number", self.purchase_order_number);
-        write_xml!(s, "recurring", self.recurring);
-        write_xml!(s, "service-fee-amount", self.recurring);
-
-        if let Some(ref shipping) = self.shipping { write!(s, "{}", shipping.to_xml(Some("shipping"))).unwrap(); }
-
-        write_xml!(s, "shipping-address-id", self.shipping_address_id);
-        write_xml!(s, "tax-amount", self.tax_amount);
-        write_xml!(s, "tax-exempt", self.tax_exempt);
-
-        write!(s, "</{}>", name).unwrap();
-        s
+    fn write_xml(&self, w: &mut Writer) {
+        w.value("type", &self.typ);
+        w.value("amount", &self.amount);
+        w.field("order-id", &self.order_id);
+        w.field("billing-address-id", &self.billing_address_id);
+        w.optional_element("billing", &self.billing);
+        w.optional_element("credit-card", &self.credit_card);
+        w.custom_fields("custom-fields", &self.custom_fields);
+        w.optional_element("customer", &self.customer);
+        w.field("customer-id", &self.customer_id);
+        w.optional_element("descriptor", &self.descriptor);
+        w.field("discount-amount", &self.discount_amount);
+        w.array("line-items", "line-item", &self.line_items);
+        w.field("merchant-account-id", &self.merchant_account_id);
+        w.optional_element("options", &self.options);
+        w.field("payment-method-nonce", &self.payment_method_nonce);
+        w.field("payment-method-token", &self.payment_method_token);
+        w.field("purchase-order-number", &self.purchase_order_number);
+        w.field("recurring", &self.recurring);
+        w.field("service-fee-amount", &self.service_fee_amount);
+        w.optional_element("shipping", &self.shipping);
+        w.field("shipping-address-id", &self.shipping_address_id);
+        w.field("shipping-amount", &self.shipping_amount);
+        w.field("ships-from-postal-code", &self.ships_from_postal_code);
+        w.field("tax-amount", &self.tax_amount);
+        w.field("tax-exempt", &self.tax_exempt);
     }
 }
 
@@ -133,23 +154,71 @@ pub struct Options {
 }
 
 impl ToXml for Options {
-    fn to_xml(&self, name: Option<&str>) -> String {
-        let name = xml::escape(&name.unwrap_or("options"));
-        let mut s = String::new();
-        write!(s, "<{}>", name).unwrap();
-
-        write_xml!(s, "add-billing-address-to-payment-method", self.add_billing_address_to_payment_method);
-        write_xml!(s, "hold-in-escrow", self.hold_in_escrow);
-        // write_xml!(s, "paypal", self.paypal);
-        write_xml!(s, "skip-avs", self.skip_avs);
-        write_xml!(s, "skip-cvv", self.skip_cvv);
-        write_xml!(s, "store-in-vault", self.store_in_vault);
-        write_xml!(s, "store-in-vault-on-success", self.store_in_vault_on_success);
-        write_xml!(s, "store-shipping-address-in-vault", self.store_shipping_address_in_vault);
-        write_xml!(s, "submit-for-settlement", self.submit_for_settlement);
-
-        write!(s, "</{}>", name).unwrap();
-        s
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("add-billing-address-to-payment-method", &self.add_billing_address_to_payment_method);
+        w.field("hold-in-escrow", &self.hold_in_escrow);
+        // w.optional_element("paypal", &self.paypal);
+        w.field("skip-avs", &self.skip_avs);
+        w.field("skip-cvv", &self.skip_cvv);
+        w.field("store-in-vault", &self.store_in_vault);
+        w.field("store-in-vault-on-success", &self.store_in_vault_on_success);
+        w.field("store-shipping-address-in-vault", &self.store_shipping_address_in_vault);
+        w.field("submit-for-settlement", &self.submit_for_settlement);
+    }
+}
+
+/// A request to refund a settled transaction.
+#[derive(Debug, Default)]
+pub(crate) struct RefundRequest {
+    /// The amount to refund. If omitted, the full amount is refunded.
+    pub amount: Option<Amount>,
+}
+
+impl ToXml for RefundRequest {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("amount", &self.amount);
+    }
+}
+
+/// A request to force a transaction into a settlement status, in the
+/// sandbox.
+#[derive(Debug, Default)]
+pub(crate) struct SettlementStatusRequest {
+    pub processor_settlement_response_text: Option<String>,
+}
+
+impl ToXml for SettlementStatusRequest {
+    fn write_xml(&self, w: &mut Writer) {
+        w.field("processor-settlement-response-text", &self.processor_settlement_response_text);
+    }
+}
+
+/// Criteria for searching transactions. Every criterion that is set must
+/// match; the default value matches every transaction.
+#[derive(Debug, Default)]
+pub struct Search {
+    pub id: Option<search::Text>,
+    pub order_id: Option<search::Text>,
+    pub typ: Vec<Type>,
+    pub status: Vec<Status>,
+    pub amount: Option<search::Range<Amount>>,
+    pub created_at: Option<search::Range<chrono::DateTime<chrono::Utc>>>,
+    pub merchant_account_id: Vec<String>,
+    pub customer_id: Option<search::Text>,
+    pub payment_method_token: Option<search::Text>,
+}
+
+impl ToXml for Search {
+    fn write_xml(&self, w: &mut Writer) {
+        w.optional_element("id", &self.id);
+        w.optional_element("order-id", &self.order_id);
+        w.values("type", &self.typ);
+        w.values("status", &self.status);
+        w.optional_element("amount", &self.amount);
+        w.optional_element("created-at", &self.created_at);
+        w.values("merchant-account-id", &self.merchant_account_id);
+        w.optional_element("customer-id", &self.customer_id);
+        w.optional_element("payment-method-token", &self.payment_method_token);
     }
 }
 
@@ -158,16 +227,13 @@ impl ToXml for Options {
 //
 // }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Default)]
 pub enum Type {
+    #[default]
     Sale,
     Credit,
-}
-
-impl Default for Type {
-    fn default() -> Type {
-        Type::Sale
-    }
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
 }
 
 impl From<String> for Type {
@@ -175,7 +241,7 @@ impl From<String> for Type {
         match s.as_ref() {
             "sale" => Type::Sale,
             "credit" => Type::Credit,
-            _ => panic!("unknown transaction type: {}", s),
+            _ => Type::Unknown(s),
         }
     }
 }
@@ -185,11 +251,15 @@ impl From<Type> for String {
         match t {
             Type::Sale => String::from("sale"),
             Type::Credit => String::from("credit"),
+            Type::Unknown(value) => value,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+from_xml_via_string!(Type);
+xml_value_via_string!(Type);
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Status {
     AuthorizationExpired,
     Authorizing,
@@ -205,6 +275,8 @@ pub enum Status {
     SubmittedForSettlement,
     Voided,
     Unrecognized,
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
 }
 
 impl From<String> for Status {
@@ -224,7 +296,7 @@ impl From<String> for Status {
             "submitted_for_settlement" => Status::SubmittedForSettlement,
             "voided" => Status::Voided,
             "unrecognized" => Status::Unrecognized,
-            _ => panic!("unknown transaction status: {}", s),
+            _ => Status::Unknown(s),
         }
     }
 }
@@ -246,6 +318,51 @@ impl From<Status> for String {
             Status::SubmittedForSettlement => String::from("submitted_for_settlement"),
             Status::Voided => String::from("voided"),
             Status::Unrecognized => String::from("unrecognized"),
+            Status::Unknown(value) => value,
         }
     }
 }
+
+from_xml_via_string!(Status);
+xml_value_via_string!(Status);
+
+/// The escrow state of a transaction. Funds held in escrow are only
+/// disbursed to the sub-merchant once released.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EscrowStatus {
+    HoldPending,
+    Held,
+    ReleasePending,
+    Released,
+    Refunded,
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
+}
+
+impl From<String> for EscrowStatus {
+    fn from(s: String) -> EscrowStatus {
+        match s.as_ref() {
+            "hold_pending" => EscrowStatus::HoldPending,
+            "held" => EscrowStatus::Held,
+            "release_pending" => EscrowStatus::ReleasePending,
+            "released" => EscrowStatus::Released,
+            "refunded" => EscrowStatus::Refunded,
+            _ => EscrowStatus::Unknown(s),
+        }
+    }
+}
+
+impl From<EscrowStatus> for String {
+    fn from(s: EscrowStatus) -> String {
+        match s {
+            EscrowStatus::HoldPending => String::from("hold_pending"),
+            EscrowStatus::Held => String::from("held"),
+            EscrowStatus::ReleasePending => String::from("release_pending"),
+            EscrowStatus::Released => String::from("released"),
+            EscrowStatus::Refunded => String::from("refunded"),
+            EscrowStatus::Unknown(value) => value,
+        }
+    }
+}
+
+from_xml_via_string!(EscrowStatus);
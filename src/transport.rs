@@ -0,0 +1,166 @@
+//! The HTTP layer underneath the gateways.
+//!
+//! Every API call is turned into a `Request` and handed to a `Transport`.
+//! The default, `HttpTransport`, sends it over the network with hyper, but a
+//! custom transport can be supplied with `BraintreeBuilder::transport()`,
+//! e.g. to route requests through an existing HTTP stack.
+
+use hyper;
+use hyper::net::{HttpStream, NetworkConnector};
+use hyper_native_tls;
+use native_tls;
+use std::io::{self, Read};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Sends requests to Braintree.
+pub trait Transport: Send + Sync {
+    fn send(&self, request: Request) -> hyper::Result<Response>;
+}
+
+/// A fully-formed request, including its authorization headers.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: hyper::method::Method,
+    pub url: hyper::Url,
+    pub headers: hyper::header::Headers,
+    pub body: Option<Vec<u8>>,
+}
+
+/// A response, whose body may still be compressed according to its
+/// `Content-Encoding` header.
+pub struct Response {
+    pub status: hyper::status::StatusCode,
+    pub headers: hyper::header::Headers,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl Response {
+    /// The body, decompressed according to its `Content-Encoding` header.
+    pub(crate) fn decoded_body(self) -> hyper::Result<Box<dyn Read + Send>> {
+        // ???: Use Content-Length somehow to provide a hint to the consumer?
+        match self.headers.get::<hyper::header::ContentEncoding>().and_then(|encodings| encodings.first()) {
+            None | Some(hyper::header::Encoding::Identity) => Ok(self.body),
+            Some(hyper::header::Encoding::Gzip) => Ok(Box::new(libflate::gzip::Decoder::new(self.body)?)),
+            Some(encoding) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported content encoding: {}", encoding)).into()),
+        }
+    }
+}
+
+/// Network settings for `HttpTransport`.
+#[derive(Clone, Debug, Default)]
+pub struct HttpSettings {
+    pub connect_timeout: Option<Duration>,
+    pub read_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    /// An HTTP proxy, as a host and port. HTTPS requests are tunneled
+    /// through it with `CONNECT`.
+    pub proxy: Option<(String, u16)>,
+    /// Additional PEM-encoded certificates to trust, e.g. for a proxy that
+    /// inspects TLS traffic.
+    pub root_certificates: Vec<Vec<u8>>,
+}
+
+/// The default transport, which sends requests over the network.
+pub struct HttpTransport {
+    client: hyper::Client,
+}
+
+impl HttpTransport {
+    pub fn new(settings: &HttpSettings) -> Result<HttpTransport, native_tls::Error> {
+        let mut tls = native_tls::TlsConnector::builder();
+        for pem in &settings.root_certificates {
+            tls.add_root_certificate(native_tls::Certificate::from_pem(pem)?);
+        }
+        let ssl = hyper_native_tls::NativeTlsClient::from(tls.build()?);
+        let connector = Connector{connect_timeout: settings.connect_timeout};
+
+        let mut client = match settings.proxy {
+            Some((ref host, port)) => hyper::Client::with_proxy_config(hyper::client::ProxyConfig::new("http", host.clone(), port, connector, ssl)),
+            // Plain `with_connector()` opens a new connection for every
+            // request, so keep them alive in a pool.
+            None => hyper::Client::with_connector(hyper::client::pool::Pool::with_connector(Default::default(), hyper::net::HttpsConnector::with_connector(ssl, connector))),
+        };
+        client.set_read_timeout(settings.read_timeout);
+        client.set_write_timeout(settings.write_timeout);
+        Ok(HttpTransport{client})
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(&self, request: Request) -> hyper::Result<Response> {
+        let mut req = self.client.request(request.method, request.url).headers(request.headers);
+        if let Some(ref data) = request.body {
+            req = req.body(hyper::client::Body::BufBody(data, data.len()));
+        }
+        let response = req.send()?;
+        Ok(Response{
+            status: response.status,
+            headers: response.headers.clone(),
+            body: Box::new(response),
+        })
+    }
+}
+
+/// Opens plain TCP connections, giving up on each address after the connect
+/// timeout.
+struct Connector {
+    connect_timeout: Option<Duration>,
+}
+
+impl NetworkConnector for Connector {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<HttpStream> {
+        if scheme != "http" {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid scheme for http").into());
+        }
+        let timeout = match self.connect_timeout {
+            Some(timeout) => timeout,
+            None => return Ok(HttpStream(TcpStream::connect((host, port))?)),
+        };
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, format!("no addresses found for {}", host));
+        for addr in (host, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Ok(HttpStream(stream)),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err.into())
+    }
+}
+
+#[cfg(test)]
+type Respond = dyn Fn(&Request) -> (hyper::status::StatusCode, String) + Send + Sync;
+
+/// A transport for unit tests, which records every request and answers it
+/// with the status and body that `respond` gives.
+#[cfg(test)]
+#[derive(Clone)]
+pub(crate) struct Stub {
+    requests: std::sync::Arc<std::sync::Mutex<Vec<Request>>>,
+    respond: std::sync::Arc<Respond>,
+}
+
+#[cfg(test)]
+impl Stub {
+    pub fn new<F>(respond: F) -> Stub
+        where F: Fn(&Request) -> (hyper::status::StatusCode, String) + Send + Sync + 'static
+    {
+        Stub{requests: Default::default(), respond: std::sync::Arc::new(respond)}
+    }
+
+    /// The requests sent so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Transport for Stub {
+    fn send(&self, request: Request) -> hyper::Result<Response> {
+        let (status, body) = (self.respond)(&request);
+        self.requests.lock().unwrap().push(request);
+        Ok(Response{status, headers: hyper::header::Headers::new(), body: Box::new(io::Cursor::new(body.into_bytes()))})
+    }
+}
@@ -0,0 +1,270 @@
+use chrono;
+use elementtree;
+use std::convert::From;
+
+use crate::decode::{DecodeError, FromXml, Node};
+use crate::merchant_account;
+
+/// A webhook notification sent by Braintree, parsed from the `bt_signature`
+/// and `bt_payload` parameters of the webhook request by
+/// `bt.webhook_notification().parse()`.
+#[derive(Debug)]
+pub struct WebhookNotification {
+    pub kind: Kind,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// The raw `<subject>` element. Prefer the typed accessors below where
+    /// one exists for the notification's kind.
+    pub subject: elementtree::Element,
+}
+
+impl FromXml for WebhookNotification {
+    fn from_xml(node: &Node) -> Result<WebhookNotification, DecodeError> {
+        Ok(WebhookNotification{
+            kind: node.child("kind")?,
+            timestamp: node.child("timestamp")?,
+            subject: node.child_with("subject", |n| Ok(n.element().clone()))?,
+        })
+    }
+}
+
+impl WebhookNotification {
+    /// The merchant account the notification is about. This is set for
+    /// `SubMerchantAccountApproved` and `SubMerchantAccountDeclined`
+    /// notifications.
+    pub fn merchant_account(&self) -> Result<Option<merchant_account::MerchantAccount>, DecodeError> {
+        let subject = Node::root(&self.subject);
+        match subject.child_opt("merchant-account")? {
+            Some(merchant_account) => Ok(Some(merchant_account)),
+            None => match subject.find("api-error-response") {
+                Some(error_response) => error_response.child_opt("merchant-account"),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// The error message for notifications that report a failure, such as
+    /// `SubMerchantAccountDeclined`.
+    pub fn error_message(&self) -> Option<String> {
+        Node::root(&self.subject).find("api-error-response")
+            .and_then(|error_response| error_response.child_opt("message").ok().flatten())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Kind {
+    /// Sent when testing your webhook endpoint from the Control Panel.
+    Check,
+    AccountUpdaterDailyReport,
+    Disbursement,
+    DisbursementException,
+    DisputeLost,
+    DisputeOpened,
+    DisputeWon,
+    PartnerMerchantConnected,
+    PartnerMerchantDeclined,
+    PartnerMerchantDisconnected,
+    SubMerchantAccountApproved,
+    SubMerchantAccountDeclined,
+    SubscriptionCanceled,
+    SubscriptionChargedSuccessfully,
+    SubscriptionChargedUnsuccessfully,
+    SubscriptionExpired,
+    SubscriptionTrialEnded,
+    SubscriptionWentActive,
+    SubscriptionWentPastDue,
+    TransactionDisbursed,
+    TransactionSettled,
+    TransactionSettlementDeclined,
+    /// A value this version of the library doesn't know about.
+    Unknown(String),
+}
+
+impl From<String> for Kind {
+    fn from(s: String) -> Kind {
+        match s.as_ref() {
+            "check" => Kind::Check,
+            "account_updater_daily_report" => Kind::AccountUpdaterDailyReport,
+            "disbursement" => Kind::Disbursement,
+            "disbursement_exception" => Kind::DisbursementException,
+            "dispute_lost" => Kind::DisputeLost,
+            "dispute_opened" => Kind::DisputeOpened,
+            "dispute_won" => Kind::DisputeWon,
+            "partner_merchant_connected" => Kind::PartnerMerchantConnected,
+            "partner_merchant_declined" => Kind::PartnerMerchantDeclined,
+            "partner_merchant_disconnected" => Kind::PartnerMerchantDisconnected,
+            "sub_merchant_account_approved" => Kind::SubMerchantAccountApproved,
+            "sub_merchant_account_declined" => Kind::SubMerchantAccountDeclined,
+            "subscription_canceled" => Kind::SubscriptionCanceled,
+            "subscription_charged_successfully" => Kind::SubscriptionChargedSuccessfully,
+            "subscription_charged_unsuccessfully" => Kind::SubscriptionChargedUnsuccessfully,
+            "subscription_expired" => Kind::SubscriptionExpired,
+            "subscription_trial_ended" => Kind::SubscriptionTrialEnded,
+            "subscription_went_active" => Kind::SubscriptionWentActive,
+            "subscription_went_past_due" => Kind::SubscriptionWentPastDue,
+            "transaction_disbursed" => Kind::TransactionDisbursed,
+            "transaction_settled" => Kind::TransactionSettled,
+            "transaction_settlement_declined" => Kind::TransactionSettlementDeclined,
+            _ => Kind::Unknown(s),
+        }
+    }
+}
+
+impl From<Kind> for String {
+    fn from(k: Kind) -> String {
+        match k {
+            Kind::Check => String::from("check"),
+            Kind::AccountUpdaterDailyReport => String::from("account_updater_daily_report"),
+            Kind::Disbursement => String::from("disbursement"),
+            Kind::DisbursementException => String::from("disbursement_exception"),
+            Kind::DisputeLost => String::from("dispute_lost"),
+            Kind::DisputeOpened => String::from("dispute_opened"),
+            Kind::DisputeWon => String::from("dispute_won"),
+            Kind::PartnerMerchantConnected => String::from("partner_merchant_connected"),
+            Kind::PartnerMerchantDeclined => String::from("partner_merchant_declined"),
+            Kind::PartnerMerchantDisconnected => String::from("partner_merchant_disconnected"),
+            Kind::SubMerchantAccountApproved => String::from("sub_merchant_account_approved"),
+            Kind::SubMerchantAccountDeclined => String::from("sub_merchant_account_declined"),
+            Kind::SubscriptionCanceled => String::from("subscription_canceled"),
+            Kind::SubscriptionChargedSuccessfully => String::from("subscription_charged_successfully"),
+            Kind::SubscriptionChargedUnsuccessfully => String::from("subscription_charged_unsuccessfully"),
+            Kind::SubscriptionExpired => String::from("subscription_expired"),
+            Kind::SubscriptionTrialEnded => String::from("subscription_trial_ended"),
+            Kind::SubscriptionWentActive => String::from("subscription_went_active"),
+            Kind::SubscriptionWentPastDue => String::from("subscription_went_past_due"),
+            Kind::TransactionDisbursed => String::from("transaction_disbursed"),
+            Kind::TransactionSettled => String::from("transaction_settled"),
+            Kind::TransactionSettlementDeclined => String::from("transaction_settlement_declined"),
+            Kind::Unknown(value) => value,
+        }
+    }
+}
+
+from_xml_via_string!(Kind);
+
+/// Checks a `bt_signature` value against the payload. The signature is a
+/// list of `public_key|hex_digest` pairs separated by `&`, where each digest
+/// is an HMAC-SHA1 of the payload keyed by the SHA1 of the private key.
+pub(crate) fn verify(public_key: &str, private_key: &str, signature: &str, payload: &str) -> bool {
+    use hmac::{Hmac, Mac};
+    use sha1::{Digest, Sha1};
+
+    let digest = signature.split('&').find_map(|pair| {
+        let mut parts = pair.splitn(2, '|');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(digest)) if key == public_key => Some(digest),
+            _ => None,
+        }
+    });
+    let digest = match digest.and_then(decode_hex) {
+        Some(digest) => digest,
+        None => return false,
+    };
+
+    let mut mac = Hmac::<Sha1>::new_from_slice(&Sha1::digest(private_key.as_bytes())).unwrap();
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&digest).is_ok()
+}
+
+/// Decodes a base64 payload into its XML document.
+pub(crate) fn decode(payload: &str) -> Option<elementtree::Element> {
+    use base64::Engine;
+
+    let payload: String = payload.chars().filter(|c| !c.is_whitespace()).collect();
+    let xml = base64::engine::general_purpose::STANDARD.decode(payload).ok()?;
+    elementtree::Element::from_reader(&xml[..]).ok()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+
+    use super::{verify, Kind};
+    use crate::error::Error;
+    use crate::{Braintree, Environment};
+
+    const PUBLIC_KEY: &str = "public_key";
+    const PRIVATE_KEY: &str = "private_key";
+
+    fn sign(public_key: &str, payload: &str) -> String {
+        use hmac::{Hmac, Mac};
+        use sha1::{Digest, Sha1};
+
+        let mut mac = Hmac::<Sha1>::new_from_slice(&Sha1::digest(PRIVATE_KEY.as_bytes())).unwrap();
+        mac.update(payload.as_bytes());
+        let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}|{}", public_key, digest)
+    }
+
+    fn payload(kind: &str, subject: &str) -> String {
+        let xml = format!(
+            "<notification><timestamp type=\"datetime\">2024-01-02T03:04:05Z</timestamp><kind>{}</kind><subject>{}</subject></notification>",
+            kind, subject,
+        );
+        base64::engine::general_purpose::STANDARD.encode(xml)
+    }
+
+    fn client() -> Braintree {
+        Braintree::new(Environment::Sandbox, "merchant_id", PUBLIC_KEY, PRIVATE_KEY)
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        let payload = payload("check", "<check type=\"boolean\">true</check>");
+        let signature = sign(PUBLIC_KEY, &payload);
+        assert!(verify(PUBLIC_KEY, PRIVATE_KEY, &signature, &payload));
+        assert!(verify(PUBLIC_KEY, PRIVATE_KEY, &format!("other|00&{}", signature), &payload));
+
+        let mut tampered = payload.clone();
+        tampered.insert(0, 'A');
+        assert!(!verify(PUBLIC_KEY, PRIVATE_KEY, &signature, &tampered));
+        assert!(!verify(PUBLIC_KEY, PRIVATE_KEY, &sign("other_public_key", &payload), &payload));
+        assert!(!verify(PUBLIC_KEY, "other_private_key", &signature, &payload));
+        assert!(!verify(PUBLIC_KEY, PRIVATE_KEY, signature.split('|').nth(1).unwrap(), &payload));
+        assert!(!verify(PUBLIC_KEY, PRIVATE_KEY, &format!("{}|not hex", PUBLIC_KEY), &payload));
+        assert!(!verify(PUBLIC_KEY, PRIVATE_KEY, "", &payload));
+    }
+
+    #[test]
+    fn parse_rejects_bad_signatures_and_payloads() {
+        let bt = client();
+        let payload = payload("check", "<check type=\"boolean\">true</check>");
+        let notification = bt.webhook_notification().parse(&sign(PUBLIC_KEY, &payload), &payload).unwrap();
+        assert_eq!(notification.kind, Kind::Check);
+
+        let result = bt.webhook_notification().parse(&sign("other_public_key", &payload), &payload);
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+        let result = bt.webhook_notification().parse("no-separator", &payload);
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+
+        let invalid = "not*base64!";
+        let result = bt.webhook_notification().parse(&sign(PUBLIC_KEY, invalid), invalid);
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+    }
+
+    #[test]
+    fn decodes_sub_merchant_subjects() {
+        let bt = client();
+
+        let approved = payload("sub_merchant_account_approved", "<merchant-account><id>seller</id><status>active</status></merchant-account>");
+        let notification = bt.webhook_notification().parse(&sign(PUBLIC_KEY, &approved), &approved).unwrap();
+        assert_eq!(notification.kind, Kind::SubMerchantAccountApproved);
+        assert_eq!(notification.merchant_account().unwrap().unwrap().id, "seller");
+        assert_eq!(notification.error_message(), None);
+
+        let declined = payload(
+            "sub_merchant_account_declined",
+            "<api-error-response><message>Applicant declined due to OFAC.</message><merchant-account><id>seller</id><status>suspended</status></merchant-account></api-error-response>",
+        );
+        let notification = bt.webhook_notification().parse(&sign(PUBLIC_KEY, &declined), &declined).unwrap();
+        assert_eq!(notification.kind, Kind::SubMerchantAccountDeclined);
+        assert_eq!(notification.merchant_account().unwrap().unwrap().id, "seller");
+        assert_eq!(notification.error_message().as_deref(), Some("Applicant declined due to OFAC."));
+    }
+}